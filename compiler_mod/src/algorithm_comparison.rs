@@ -0,0 +1,129 @@
+// © 2020, ETH Zurich
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+/// Code for comparing the errors that different algorithms of Polonius find for the same facts.
+/// The explanations are based on the output of a single algorithm (see
+/// RustLifeOptions::polonius_algorithm), and the algorithms are not guaranteed to agree: e.g. the
+/// location insensitive one reports errors that the location sensitive ones rule out, and the
+/// optimized ones may (due to bugs) differ from the naive one. A comparison tells if an error (and
+/// hence its explanation) depends on the algorithm that was used.
+
+use dump_borrowck_info::polonius_engine::{Algorithm, Output};
+use dump_borrowck_info::serde_derive;
+use facts::{self, Loan, MovePath, PointIndex, Region};
+use std::collections::BTreeSet;
+
+/// The errors of all kinds that an algorithm of Polonius found, in a form that can be compared.
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde_derive::Serialize)]
+pub struct ErrorSet {
+    /// Loans that are invalidated at a point while they are live, see Output::errors.
+    pub errors: BTreeSet<(PointIndex, Loan)>,
+    /// Pairs of universal regions that must outlive each other (the first one the second one),
+    /// without this being known from the signature, see Output::subset_errors.
+    pub subset_errors: BTreeSet<(PointIndex, Region, Region)>,
+    /// Move paths that are used at a point while they may be moved out, see Output::move_errors.
+    pub move_errors: BTreeSet<(PointIndex, MovePath)>,
+}
+
+impl ErrorSet {
+    /// Collects the errors of the output of Polonius.
+    pub fn from_output(output: &facts::AllOutputFacts) -> Self {
+        ErrorSet {
+            errors: output.errors.iter()
+                .flat_map(|(&point, loans)| loans.iter().map(move |&loan| (point, loan)))
+                .collect(),
+            subset_errors: output.subset_errors.iter()
+                .flat_map(|(&point, subsets)|
+                    subsets.iter().map(move |&(region1, region2)| (point, region1, region2))
+                )
+                .collect(),
+            move_errors: output.move_errors.iter()
+                .flat_map(|(&point, paths)| paths.iter().map(move |&path| (point, path)))
+                .collect(),
+        }
+    }
+
+    /// Gives the errors of this set that are not in the other one.
+    fn difference(&self, other: &ErrorSet) -> ErrorSet {
+        ErrorSet {
+            errors: self.errors.difference(&other.errors).cloned().collect(),
+            subset_errors: self.subset_errors.difference(&other.subset_errors).cloned().collect(),
+            move_errors: self.move_errors.difference(&other.move_errors).cloned().collect(),
+        }
+    }
+
+    /// The number of errors of all kinds.
+    pub fn len(&self) -> usize {
+        self.errors.len() + self.subset_errors.len() + self.move_errors.len()
+    }
+}
+
+/// The difference between the errors of an algorithm and the ones of the reference algorithm.
+#[derive(Debug, serde_derive::Serialize)]
+pub struct AlgorithmDisagreement {
+    /// The name of the algorithm, as it is given on the command line (e.g. "DatafrogOpt").
+    pub algorithm: String,
+    /// The errors that only the reference algorithm found. The explanations of these errors depend
+    /// on the reference algorithm.
+    pub missing: ErrorSet,
+    /// The errors that only this algorithm found.
+    pub additional: ErrorSet,
+}
+
+/// The result of comparing the errors of several algorithms with the ones of the reference
+/// algorithm, i.e. the one whose output is used for the explanations.
+#[derive(Debug, serde_derive::Serialize)]
+pub struct AlgorithmComparison {
+    /// The name of the reference algorithm.
+    pub reference_algorithm: String,
+    /// The errors that the reference algorithm found.
+    pub reference_errors: ErrorSet,
+    /// The algorithms that found exactly the same errors as the reference algorithm.
+    pub agreeing_algorithms: Vec<String>,
+    /// The differences of the algorithms that did not find the same errors.
+    pub disagreements: Vec<AlgorithmDisagreement>,
+}
+
+/// Runs each of the algorithms on the facts and compares the errors that it finds with the ones of
+/// the reference output, which must have been computed by the reference algorithm for the same
+/// facts. (The reference algorithm is not run again, even if it is among the algorithms.)
+pub fn compare_algorithms(all_facts: &facts::AllInputFacts, reference_algorithm: Algorithm,
+                          reference_output: &facts::AllOutputFacts, algorithms: &[Algorithm])
+        -> AlgorithmComparison {
+    trace!("[compare_algorithms] enter");
+    let reference_errors = ErrorSet::from_output(reference_output);
+    let mut comparison = AlgorithmComparison {
+        reference_algorithm: algorithm_name(reference_algorithm),
+        reference_errors,
+        agreeing_algorithms: Vec::new(),
+        disagreements: Vec::new(),
+    };
+    for &algorithm in algorithms.iter() {
+        let name = algorithm_name(algorithm);
+        if name == comparison.reference_algorithm {
+            continue;
+        }
+        let output = Output::compute(all_facts, algorithm, false);
+        let errors = ErrorSet::from_output(&output);
+        debug!("errors of {}: {:?}", name, errors);
+        if errors == comparison.reference_errors {
+            comparison.agreeing_algorithms.push(name);
+        } else {
+            comparison.disagreements.push(AlgorithmDisagreement {
+                algorithm: name,
+                missing: comparison.reference_errors.difference(&errors),
+                additional: errors.difference(&comparison.reference_errors),
+            });
+        }
+    }
+    trace!("[compare_algorithms] exit");
+    comparison
+}
+
+/// Gives the name of an algorithm, as it is given on the command line (e.g. "DatafrogOpt").
+pub fn algorithm_name(algorithm: Algorithm) -> String {
+    format!("{:?}", algorithm)
+}
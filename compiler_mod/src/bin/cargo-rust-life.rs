@@ -0,0 +1,418 @@
+// © 2020, ETH Zurich
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+/// Front end that allows to run rust-life on a whole Cargo package (or workspace) by running
+/// `cargo rust-life [<options>] [<cargo check arguments>]`.
+///
+/// This binary has two roles:
+/// * If it is called by Cargo as the subcommand `rust-life` (i.e. the first argument is
+///   `rust-life`), it runs `cargo check` with itself set as `RUSTC_WORKSPACE_WRAPPER`. Thus, Cargo
+///   calls it for every crate of a workspace member (but not for dependencies), with the same flags
+///   (`--extern`, `--edition`, features, ...) that rustc would get. In the end, the results for all
+///   crates are collected into one report.
+/// * If it is called as wrapper, it runs the rust-life driver (that must be located in the same
+///   directory) on the crate, and then the real rustc to produce the artifacts that Cargo expects.
+///
+/// All options that start with `--rust-life-` are passed on to the driver (see option_names),
+/// except for `--rust-life-out-dir`, which gives the directory of the report. The results for every
+/// crate are written to a subdirectory `crates/<crate name>-<metadata hash>` of this directory, and
+/// the file `report.json` in it lists the manifests of all crates.
+/// Note that the driver must be built for the same toolchain that Cargo uses for the package.
+
+extern crate serde_json;
+
+#[path = "../option_names.rs"]
+mod option_names;
+
+use option_names::{FLAG_OPTIONS, OPTION_PREFIX, VALUE_OPTIONS};
+use serde_json::Value;
+use std::env;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+
+/// Name of the environment variable that passes the directory of the report to the wrapper.
+const REPORT_DIR_VAR: &str = "RUST_LIFE_REPORT_DIR";
+/// Name of the environment variable that passes the options for the driver to the wrapper. (As JSON
+/// array, to retain arguments that contain spaces.)
+const DRIVER_ARGS_VAR: &str = "RUST_LIFE_DRIVER_ARGS";
+/// The option that gives the directory of the report.
+const OUT_DIR_OPTION: &str = "--rust-life-out-dir";
+/// The directory (relative to the working directory) of the report, if no --rust-life-out-dir is given.
+const DEFAULT_REPORT_DIR: &str = "rust-life-out";
+/// Name of the directory in the report directory that contains the results of the single crates.
+const CRATES_DIR_NAME: &str = "crates";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let exit_code = if args.first().map(String::as_str) == Some("rust-life") {
+        run_cargo(&args[1..])
+    } else {
+        run_wrapper(&args)
+    };
+    process::exit(exit_code);
+}
+
+/// The arguments of `cargo rust-life`, split by whom they are for.
+#[derive(Debug, PartialEq)]
+struct SplitArgs {
+    /// The directory of the report, as given (i.e. possibly relative).
+    report_dir: PathBuf,
+    /// The options for the driver, each with its value if it takes a separate one.
+    driver_args: Vec<String>,
+    /// All other arguments, they are passed on to `cargo check`.
+    cargo_args: Vec<String>,
+}
+
+/// Splits the arguments of `cargo rust-life` into the directory of the report, the options for the
+/// driver and the arguments for cargo. An option of the driver that takes a value (see
+/// option_names::VALUE_OPTIONS) takes the next argument as value, unless it is given as
+/// `<option>=<value>`. Options with the prefix of rust-life that the driver does not know are
+/// reported right away, instead of failing for every crate.
+fn split_args(args: &[String]) -> Result<SplitArgs, String> {
+    let mut report_dir = PathBuf::from(DEFAULT_REPORT_DIR);
+    let mut driver_args = Vec::new();
+    let mut cargo_args = Vec::new();
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        if arg == OUT_DIR_OPTION {
+            match args_iter.next() {
+                Some(value) => report_dir = PathBuf::from(value),
+                None => return Err(format!("missing value for option `{}`", arg)),
+            }
+        } else if arg.starts_with(&format!("{}=", OUT_DIR_OPTION)) {
+            report_dir = PathBuf::from(&arg[OUT_DIR_OPTION.len() + 1..]);
+        } else if let Some(option) = arg.strip_prefix(OPTION_PREFIX) {
+            let name = option.split('=').next().unwrap_or(option);
+            if !VALUE_OPTIONS.contains(&name) && !FLAG_OPTIONS.contains(&name) {
+                return Err(format!("unknown option `{}`", arg));
+            }
+            driver_args.push(arg.clone());
+            // options of the driver that take a value can also be given as separate argument, pass
+            // it on as well:
+            if VALUE_OPTIONS.contains(&option) {
+                match args_iter.next() {
+                    Some(value) => driver_args.push(value.clone()),
+                    None => return Err(format!("missing value for option `{}`", arg)),
+                }
+            }
+        } else {
+            cargo_args.push(arg.clone());
+        }
+    }
+    Ok(SplitArgs {
+        report_dir,
+        driver_args,
+        cargo_args,
+    })
+}
+
+/// Runs `cargo check` on the package in the current directory, with this executable as wrapper for
+/// rustc, and writes the report afterwards. Returns the exit code.
+fn run_cargo(args: &[String]) -> i32 {
+    let SplitArgs { report_dir, driver_args, cargo_args } = match split_args(args) {
+        Ok(split_args) => split_args,
+        Err(msg) => return fail(&msg),
+    };
+    let report_dir = match env::current_dir() {
+        Ok(cur_dir) => cur_dir.join(report_dir),
+        Err(err) => return fail(&err.to_string()),
+    };
+    let wrapper = match env::current_exe() {
+        Ok(exe) => exe,
+        Err(err) => return fail(&format!("cannot locate the cargo-rust-life executable: {}", err)),
+    };
+    let cargo = env::var_os("CARGO").unwrap_or_else(|| OsString::from("cargo"));
+
+    // Start from a clean report, so that it does not list crates of an earlier run.
+    let crates_dir = report_dir.join(CRATES_DIR_NAME);
+    if crates_dir.exists() {
+        if let Err(err) = fs::remove_dir_all(&crates_dir) {
+            return fail(&format!("cannot remove the old results in {:?}: {}", crates_dir, err));
+        }
+    }
+
+    // A separate target directory is used, to not interfere with normal builds. The workspace
+    // members are cleaned in it, since Cargo would not call the wrapper for crates that are fresh.
+    let target_dir = match workspace_target_dir(&cargo) {
+        Ok(target_dir) => target_dir.join("rust-life"),
+        Err(msg) => return fail(&msg),
+    };
+    for member in workspace_member_names(&cargo).unwrap_or_default() {
+        let status = Command::new(&cargo)
+            .arg("clean")
+            .arg("--target-dir").arg(&target_dir)
+            .arg("-p").arg(&member)
+            .status();
+        if !status.map(|status| status.success()).unwrap_or(false) {
+            eprintln!("warning: cannot clean the package `{}`, its results may be missing", member);
+        }
+    }
+
+    let status = Command::new(&cargo)
+        .arg("check")
+        .arg("--target-dir").arg(&target_dir)
+        .args(&cargo_args)
+        .env("RUSTC_WORKSPACE_WRAPPER", &wrapper)
+        .env(REPORT_DIR_VAR, &report_dir)
+        .env(DRIVER_ARGS_VAR, Value::from(driver_args).to_string())
+        .status();
+    let exit_code = match status {
+        Ok(status) => status.code().unwrap_or(1),
+        Err(err) => return fail(&format!("cannot run cargo: {}", err)),
+    };
+
+    // The report is also written if cargo failed, since this is the case if there were errors.
+    if let Err(msg) = write_report(&report_dir) {
+        return fail(&msg);
+    }
+    exit_code
+}
+
+/// Runs the driver on a crate that Cargo wants to compile, and then the real rustc if the driver
+/// did succeed. `args` are the arguments that Cargo passes to the wrapper, i.e. the path of the
+/// real rustc followed by the arguments for it. Returns the exit code.
+fn run_wrapper(args: &[String]) -> i32 {
+    let (rustc, rustc_args) = match args.split_first() {
+        Some(split) => split,
+        None => return fail("cargo-rust-life must be run as `cargo rust-life`"),
+    };
+    let out_dir = match crate_out_dir(rustc_args, env::var_os(REPORT_DIR_VAR).map(PathBuf::from)) {
+        Some(out_dir) => out_dir,
+        None => return run_rustc(rustc, rustc_args),
+    };
+
+    let driver = match env::current_exe() {
+        Ok(exe) => exe.with_file_name("rust-life"),
+        Err(err) => return fail(&format!("cannot locate the rust-life driver: {}", err)),
+    };
+    let driver_args: Vec<String> = env::var(DRIVER_ARGS_VAR)
+        .ok()
+        .and_then(|value| serde_json::from_str(&value).ok())
+        .unwrap_or_default();
+    let mut command = Command::new(&driver);
+    // The warnings are reported by the real rustc, the driver shall not report them a second time.
+    command.args(rustc_args).args(["-A", "warnings"]).args(&driver_args).arg(OUT_DIR_OPTION).arg(&out_dir);
+    if find_arg_value(rustc_args, "--sysroot").is_none() {
+        match sysroot(rustc) {
+            Ok(sysroot) => { command.arg("--sysroot").arg(sysroot); },
+            Err(msg) => return fail(&msg),
+        }
+    }
+    match command.status() {
+        Ok(ref status) if status.success() => run_rustc(rustc, rustc_args),
+        Ok(status) => status.code().unwrap_or(1),
+        Err(err) => fail(&format!("cannot run the rust-life driver {:?}: {}", driver, err)),
+    }
+}
+
+/// Gives the directory for the results of the crate that the wrapper is called for with the given
+/// arguments for rustc, if the driver shall be run for it. Cargo also calls the wrapper to query
+/// information about rustc, these calls (and all calls if the wrapper is not used by
+/// `cargo rust-life`, i.e. no report directory is given) are passed on directly, hence None is
+/// returned for them.
+fn crate_out_dir(rustc_args: &[String], report_dir: Option<PathBuf>) -> Option<PathBuf> {
+    let is_query = rustc_args.iter().any(|arg| arg.starts_with("--print") || arg == "-vV");
+    if is_query {
+        return None;
+    }
+    let crate_name = find_arg_value(rustc_args, "--crate-name")?;
+    let report_dir = report_dir?;
+
+    // The metadata hash distinguishes the different targets of a package, e.g. the library and its
+    // tests, which all have the same crate name.
+    let crate_dir_name = match find_arg_values(rustc_args, "-C")
+        .into_iter()
+        .find(|value| value.starts_with("metadata="))
+    {
+        Some(metadata) => format!("{}-{}", crate_name, &metadata["metadata=".len()..]),
+        None => crate_name,
+    };
+    Some(report_dir.join(CRATES_DIR_NAME).join(crate_dir_name))
+}
+
+/// Runs the real rustc with the given arguments and returns its exit code.
+fn run_rustc(rustc: &str, args: &[String]) -> i32 {
+    match Command::new(rustc).args(args).status() {
+        Ok(status) => status.code().unwrap_or(1),
+        Err(err) => fail(&format!("cannot run rustc {:?}: {}", rustc, err)),
+    }
+}
+
+/// Gives the value of the first occurrence of an option that is given as `<option> <value>` or as
+/// `<option>=<value>`.
+fn find_arg_value(args: &[String], option: &str) -> Option<String> {
+    find_arg_values(args, option).into_iter().next()
+}
+
+/// Gives the values of all occurrences of an option that is given as `<option> <value>` or as
+/// `<option>=<value>`.
+fn find_arg_values(args: &[String], option: &str) -> Vec<String> {
+    let mut values = Vec::new();
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        if arg == option {
+            values.extend(args_iter.next().cloned());
+        } else if arg.starts_with(option) && arg[option.len()..].starts_with('=') {
+            values.push(arg[option.len() + 1..].to_owned());
+        }
+    }
+    values
+}
+
+/// Asks the given rustc for its sysroot, the driver needs it to find the standard library.
+fn sysroot(rustc: &str) -> Result<String, String> {
+    let output = Command::new(rustc)
+        .args(["--print", "sysroot"])
+        .output()
+        .map_err(|err| format!("cannot run rustc {:?}: {}", rustc, err))?;
+    if !output.status.success() {
+        return Err(format!("cannot get the sysroot from rustc {:?}", rustc));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+/// Runs `cargo metadata` for the package in the current directory.
+fn cargo_metadata(cargo: &OsString) -> Result<Value, String> {
+    let output = Command::new(cargo)
+        .args(["metadata", "--no-deps", "--format-version", "1"])
+        .output()
+        .map_err(|err| format!("cannot run cargo: {}", err))?;
+    if !output.status.success() {
+        return Err(format!("cargo metadata failed: {}", String::from_utf8_lossy(&output.stderr)));
+    }
+    serde_json::from_slice(&output.stdout).map_err(|err| format!("cannot parse cargo metadata: {}", err))
+}
+
+/// Gives the target directory of the workspace.
+fn workspace_target_dir(cargo: &OsString) -> Result<PathBuf, String> {
+    cargo_metadata(cargo)?["target_directory"]
+        .as_str()
+        .map(PathBuf::from)
+        .ok_or_else(|| "cargo metadata does not give a target directory".to_owned())
+}
+
+/// Gives the names of all packages that are members of the workspace.
+fn workspace_member_names(cargo: &OsString) -> Result<Vec<String>, String> {
+    // with --no-deps, the packages are exactly the workspace members
+    let metadata = cargo_metadata(cargo)?;
+    Ok(metadata["packages"]
+        .as_array()
+        .map(|packages| {
+            packages.iter()
+                .filter_map(|package| package["name"].as_str().map(str::to_owned))
+                .collect()
+        })
+        .unwrap_or_default())
+}
+
+/// Collects the manifests of all crates that were analyzed into the file report.json in the
+/// report directory. For every crate, its entry gives the directory (relative to the report
+/// directory) that contains its results, all paths in its manifest are relative to this directory.
+fn write_report(report_dir: &Path) -> Result<(), String> {
+    let crates_dir = report_dir.join(CRATES_DIR_NAME);
+    let mut crate_dir_names: Vec<String> = match fs::read_dir(&crates_dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .collect(),
+        // no crate was analyzed (e.g. because cargo failed to resolve the dependencies)
+        Err(_) => Vec::new(),
+    };
+    crate_dir_names.sort();
+
+    let mut crates = Vec::new();
+    for crate_dir_name in crate_dir_names {
+        let crate_dir = crates_dir.join(&crate_dir_name);
+        let manifest: Value = match File::open(crate_dir.join("manifest.json")) {
+            Ok(file) => serde_json::from_reader(file)
+                .map_err(|err| format!("cannot parse the manifest in {:?}: {}", crate_dir, err))?,
+            // the driver did not get to write the manifest, e.g. because the crate did not compile
+            Err(_) => continue,
+        };
+        crates.push(serde_json::json!({
+            "dir": Path::new(CRATES_DIR_NAME).join(&crate_dir_name),
+            "manifest": manifest,
+        }));
+    }
+
+    fs::create_dir_all(report_dir)
+        .map_err(|err| format!("cannot create the report directory {:?}: {}", report_dir, err))?;
+    let report_path = report_dir.join("report.json");
+    let report_file = File::create(&report_path)
+        .map_err(|err| format!("cannot create the report {:?}: {}", report_path, err))?;
+    serde_json::to_writer_pretty(report_file, &serde_json::json!({ "crates": crates }))
+        .map_err(|err| format!("cannot write the report {:?}: {}", report_path, err))
+}
+
+/// Reports an error to the user and gives the exit code for it.
+fn fail(msg: &str) -> i32 {
+    eprintln!("error: {}", msg);
+    1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{crate_out_dir, split_args, SplitArgs};
+    use std::path::PathBuf;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn arguments_are_split_between_driver_and_cargo() {
+        let args = strings(&["--rust-life-fn", "main", "--workspace", "--rust-life-keep-work-dir",
+                             "--rust-life-explain=text", "--features", "foo",
+                             "--rust-life-out-dir", "report"]);
+        assert_eq!(split_args(&args), Ok(SplitArgs {
+            report_dir: PathBuf::from("report"),
+            driver_args: strings(&["--rust-life-fn", "main", "--rust-life-keep-work-dir",
+                                   "--rust-life-explain=text"]),
+            cargo_args: strings(&["--workspace", "--features", "foo"]),
+        }));
+    }
+
+    #[test]
+    fn flags_of_the_driver_do_not_take_the_next_argument() {
+        let args = strings(&["--rust-life-json-diagnostics", "--release",
+                             "--rust-life-out-dir=report"]);
+        let split = split_args(&args).unwrap();
+        assert_eq!(split.report_dir, PathBuf::from("report"));
+        assert_eq!(split.driver_args, strings(&["--rust-life-json-diagnostics"]));
+        assert_eq!(split.cargo_args, strings(&["--release"]));
+    }
+
+    #[test]
+    fn missing_values_and_unknown_options_are_reported() {
+        assert!(split_args(&strings(&["--rust-life-out-dir"])).is_err());
+        assert!(split_args(&strings(&["--rust-life-position"])).is_err());
+        assert!(split_args(&strings(&["--rust-life-unknown", "value"])).is_err());
+        assert!(split_args(&strings(&["--rust-life-unknown=value"])).is_err());
+    }
+
+    #[test]
+    fn crates_are_analyzed_into_a_directory_per_target() {
+        let report_dir = Some(PathBuf::from("report"));
+        let args = strings(&["--crate-name", "foo", "--edition=2021", "-C", "metadata=abc",
+                             "src/lib.rs"]);
+        assert_eq!(crate_out_dir(&args, report_dir.clone()),
+                   Some(PathBuf::from("report/crates/foo-abc")));
+        let args = strings(&["--crate-name=foo", "src/lib.rs"]);
+        assert_eq!(crate_out_dir(&args, report_dir), Some(PathBuf::from("report/crates/foo")));
+    }
+
+    #[test]
+    fn queries_and_calls_outside_of_cargo_rust_life_are_passed_on() {
+        let report_dir = Some(PathBuf::from("report"));
+        assert_eq!(crate_out_dir(&strings(&["-vV"]), report_dir.clone()), None);
+        let args = strings(&["--crate-name", "___", "--print=file-names", "-"]);
+        assert_eq!(crate_out_dir(&args, report_dir.clone()), None);
+        assert_eq!(crate_out_dir(&strings(&["src/lib.rs"]), report_dir), None);
+        assert_eq!(crate_out_dir(&strings(&["--crate-name", "foo", "src/lib.rs"]), None), None);
+    }
+}
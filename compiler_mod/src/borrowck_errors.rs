@@ -0,0 +1,180 @@
+// © 2020, ETH Zurich
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+/// Code for attaching the explanations of rust-life to the errors that the borrow checker of rustc
+/// reports, as additional notes.
+/// The emission of diagnostics is hooked (by rustc_errors::TRACK_DIAGNOSTIC), and the errors that
+/// the borrow checker reports for a body are held back instead of being emitted right away. Once
+/// the explanations for the errors of the body are computed, notes are added to them, and in the
+/// end all errors that were held back are emitted (in their original order).
+/// Like this, the explanations are part of the normal output of rustc (and hence of cargo), in
+/// both the human readable and the JSON format.
+
+use rustc_errors::{DiagInner, DiagMessage, ErrorGuaranteed, Level, MultiSpan, Style, Subdiag};
+use rustc_errors::TRACK_DIAGNOSTIC;
+use rustc_hir::def_id::LocalDefId;
+use rustc_middle::ty::TyCtxt;
+use std::cell::RefCell;
+use std::sync::OnceLock;
+
+/// The type of the hook for the emission of diagnostics, see rustc_errors::TRACK_DIAGNOSTIC.
+type TrackDiagnostic = fn(DiagInner, &mut dyn FnMut(DiagInner) -> Option<ErrorGuaranteed>)
+    -> Option<ErrorGuaranteed>;
+
+/// The hook that was installed before ours (by rustc), all diagnostics that are not held back are
+/// passed on to it.
+static PREVIOUS_TRACK_DIAGNOSTIC: OnceLock<TrackDiagnostic> = OnceLock::new();
+
+/// Tells what shall happen to the errors that are reported while borrow checking a body.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Capture {
+    /// The errors are dropped, they are reported a second time by another run of the borrow
+    /// checker on the same body.
+    Drop,
+    /// The errors are held back until emit_held_errors() is called.
+    Hold,
+}
+
+thread_local! {
+    /// The bodies that are borrow checked right now, with what shall happen to their errors. (The
+    /// borrow checking of a body may require the one of another body, e.g. of a closure, hence
+    /// this is a stack.)
+    static CAPTURING: RefCell<Vec<(LocalDefId, Capture)>> = const { RefCell::new(Vec::new()) };
+    /// The errors that were held back so far, in the order they were reported, together with the
+    /// body they were reported for.
+    static HELD_ERRORS: RefCell<Vec<(LocalDefId, DiagInner)>> =
+        const { RefCell::new(Vec::new()) };
+}
+
+/// Installs the hook for the emission of diagnostics. Must be called after rustc installed its own
+/// hook, i.e. once the compiler is running (e.g. in a callback), and before the borrow checker runs.
+pub fn install_hook() {
+    let previous = TRACK_DIAGNOSTIC.swap(&(track_diagnostic as TrackDiagnostic));
+    if PREVIOUS_TRACK_DIAGNOSTIC.set(*previous).is_err() {
+        // the hook was installed before already, hence the swap replaced it by itself.
+        debug!("The hook for the emission of diagnostics was already installed.");
+    }
+}
+
+/// Runs the passed function (that runs the borrow checker on the body with the given id) and
+/// captures the errors that are reported meanwhile as given by capture.
+pub fn capture_errors<R, F: FnOnce() -> R>(def_id: LocalDefId, capture: Capture, f: F) -> R {
+    CAPTURING.with(|capturing| capturing.borrow_mut().push((def_id, capture)));
+    let result = f();
+    CAPTURING.with(|capturing| capturing.borrow_mut().pop());
+    result
+}
+
+/// Adds notes to the errors that were held back for the body with the given id. The function
+/// add_notes gets the index of every error (in the order of summarize_held_errors()), and gives the
+/// messages of the notes that shall be added to it. (Possibly none.)
+pub fn add_notes_to_held_errors<F: FnMut(usize) -> Vec<String>>(def_id: LocalDefId,
+                                                                mut add_notes: F) {
+    HELD_ERRORS.with(|held_errors| {
+        let mut held_errors = held_errors.borrow_mut();
+        let body_errors = held_errors.iter_mut()
+            .filter(|&&mut (error_def_id, _)| error_def_id == def_id);
+        for (error_index, (_, diagnostic)) in body_errors.enumerate() {
+            for note in add_notes(error_index) {
+                diagnostic.children.push(Subdiag {
+                    level: Level::Note,
+                    messages: vec![(DiagMessage::from(note), Style::NoStyle)],
+                    span: MultiSpan::new(),
+                });
+            }
+        }
+    });
+}
+
+/// A summary of an error that was held back, see summarize_held_errors().
+pub struct HeldErrorSummary {
+    /// The error code, e.g. "E0499", if the error has one.
+    pub code: Option<String>,
+    /// The (main) message of the error.
+    pub message: String,
+    /// The spans of the error.
+    pub span: MultiSpan,
+}
+
+/// Gives summaries of the errors that were held back for the body with the given id, in the order
+/// they were reported. This tells which errors the user sees for the body.
+pub fn summarize_held_errors(def_id: LocalDefId) -> Vec<HeldErrorSummary> {
+    HELD_ERRORS.with(|held_errors| {
+        held_errors.borrow().iter()
+            .filter(|&&(error_def_id, _)| error_def_id == def_id)
+            .map(|(_, diagnostic)| HeldErrorSummary {
+                code: diagnostic.code.map(|code| code.to_string()),
+                message: diagnostic.messages.iter()
+                    .map(|(message, _)| message.as_str()
+                        .map_or_else(|| format!("{:?}", message), str::to_string))
+                    .collect::<Vec<String>>()
+                    .join(""),
+                span: diagnostic.span.clone(),
+            })
+            .collect()
+    })
+}
+
+/// Emits all errors that were held back so far, in the order they were reported.
+pub fn emit_held_errors(tcx: TyCtxt<'_>) {
+    // No body is borrow checked any more at this point, but a panic in the borrow checker may have
+    // left its entry behind, which would capture the errors again instead of emitting them.
+    CAPTURING.with(|capturing| capturing.borrow_mut().clear());
+    let held_errors = HELD_ERRORS.with(|held_errors| held_errors.replace(Vec::new()));
+    for (_, diagnostic) in held_errors {
+        tcx.dcx().emit_diagnostic(diagnostic);
+    }
+}
+
+/// Emits the errors that were held back (see emit_held_errors()) when it is dropped, i.e. also if
+/// the analysis or the computation of the explanations panics. This matters since the errors that
+/// are held back are already counted as reported (see track_diagnostic()), hence rustc would not
+/// complain if they were never emitted.
+pub struct HeldErrorsGuard<'tcx> {
+    tcx: TyCtxt<'tcx>,
+}
+
+impl<'tcx> HeldErrorsGuard<'tcx> {
+    pub fn new(tcx: TyCtxt<'tcx>) -> Self {
+        HeldErrorsGuard {
+            tcx,
+        }
+    }
+}
+
+impl<'tcx> Drop for HeldErrorsGuard<'tcx> {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            warn!("Emitting the errors of the borrow checker after a panic, without explanations.");
+        }
+        emit_held_errors(self.tcx);
+    }
+}
+
+/// The hook for the emission of diagnostics, it captures the errors that are reported while a body
+/// is borrow checked (see capture_errors()) and passes on all other diagnostics.
+fn track_diagnostic(diagnostic: DiagInner,
+                    emit: &mut dyn FnMut(DiagInner) -> Option<ErrorGuaranteed>)
+        -> Option<ErrorGuaranteed> {
+    let capturing = CAPTURING.with(|capturing| capturing.borrow().last().cloned());
+    match capturing {
+        Some((def_id, capture)) if diagnostic.level() == Level::Error => {
+            trace!("[track_diagnostic] capturing error for {:?}: {:?}", def_id, capture);
+            if capture == Capture::Hold {
+                HELD_ERRORS.with(|held_errors| held_errors.borrow_mut().push((def_id, diagnostic)));
+            }
+            // The error is (or was) reported for sure, either by another run of the borrow checker
+            // or by emit_held_errors().
+            #[allow(deprecated)]
+            Some(ErrorGuaranteed::unchecked_error_guaranteed())
+        },
+        _ => {
+            let previous = PREVIOUS_TRACK_DIAGNOSTIC.get()
+                .expect("The hook for the emission of diagnostics was not installed");
+            previous(diagnostic, emit)
+        },
+    }
+}
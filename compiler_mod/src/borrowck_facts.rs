@@ -0,0 +1,86 @@
+// © 2020, ETH Zurich
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+/// Code for getting the MIR bodies together with the facts of the borrow checker straight from the
+/// compiler, using the borrowck consumer interface of rustc.
+/// The mir_borrowck query is replaced by a version that asks the borrow checker for the body with
+/// its facts and stores it, before running the original query. Like this, the facts of every body
+/// that is borrow checked (e.g. during the analysis of the crate) are available afterwards.
+/// The errors of the first run of the borrow checker are dropped, the ones of the original query
+/// are held back, so that the explanations can be attached to them. (See borrowck_errors.)
+
+use borrowck_errors::{self, Capture};
+use rustc_borrowck;
+use rustc_borrowck::consumers::{self, BodyWithBorrowckFacts, ConsumerOptions};
+use rustc_middle::query::Providers as QueryProviders;
+use rustc_middle::query::queries::mir_borrowck::ProvidedValue;
+use rustc_middle::ty::TyCtxt;
+use rustc_middle::util::Providers;
+use rustc_session::Session;
+use rustc_hir::def_id::LocalDefId;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::mem;
+
+thread_local! {
+    /// The bodies (with their facts) of all functions that were borrow checked so far and were not
+    /// taken yet. The lifetime 'tcx is replaced by 'static to be able to store them, see
+    /// take_body_with_facts() for why this is fine.
+    static BODIES: RefCell<HashMap<LocalDefId, BodyWithBorrowckFacts<'static>>> =
+        RefCell::new(HashMap::new());
+}
+
+/// Replaces the mir_borrowck query by mir_borrowck() below. Shall be set as override_queries in
+/// the config of the compiler.
+pub fn override_queries(_session: &Session, providers: &mut Providers) {
+    providers.queries.mir_borrowck = mir_borrowck;
+}
+
+/// Provider for the mir_borrowck query that stores the body together with its borrowck facts,
+/// and then runs the original borrow checker, which reports the errors. (These are held back
+/// until the explanations are added to them.)
+fn mir_borrowck<'tcx>(tcx: TyCtxt<'tcx>, def_id: LocalDefId) -> ProvidedValue<'tcx> {
+    trace!("[mir_borrowck] enter def_id={:?}", def_id);
+    let body_with_facts = borrowck_errors::capture_errors(def_id, Capture::Drop, || {
+        consumers::get_body_with_borrowck_facts(tcx, def_id, ConsumerOptions::PoloniusInputFacts)
+    });
+    // SAFETY: The bodies are only taken out by take_body_with_facts(), which restores 'tcx.
+    let body_with_facts: BodyWithBorrowckFacts<'static> = unsafe {
+        mem::transmute(body_with_facts)
+    };
+    BODIES.with(|bodies| bodies.borrow_mut().insert(def_id, body_with_facts));
+
+    let mut providers = QueryProviders::default();
+    rustc_borrowck::provide(&mut providers);
+    borrowck_errors::capture_errors(def_id, Capture::Hold, || (providers.mir_borrowck)(tcx, def_id))
+}
+
+/// Runs the passed function on the body (with its borrowck facts) of the function with the given
+/// id, if it was borrow checked before and was not taken yet. Unlike take_body_with_facts(), this
+/// leaves the body in place. (E.g. to look at the body of the parent of a closure.)
+pub fn with_body_with_facts<'tcx, R, F>(_tcx: TyCtxt<'tcx>, def_id: LocalDefId, f: F) -> Option<R>
+        where F: FnOnce(&BodyWithBorrowckFacts<'tcx>) -> R {
+    BODIES.with(|bodies| bodies.borrow().get(&def_id).map(|body_with_facts| {
+        // SAFETY: See take_body_with_facts().
+        let body_with_facts: &BodyWithBorrowckFacts<'tcx> = unsafe {
+            mem::transmute::<&BodyWithBorrowckFacts<'static>, &BodyWithBorrowckFacts<'tcx>>(body_with_facts)
+        };
+        f(body_with_facts)
+    }))
+}
+
+/// Takes the body (with its borrowck facts) of the function with the given id, if it was borrow
+/// checked before. (E.g. by tcx.analysis(), or by calling tcx.mir_borrowck().) Every body can be
+/// taken only once.
+pub fn take_body_with_facts<'tcx>(_tcx: TyCtxt<'tcx>, def_id: LocalDefId)
+        -> Option<BodyWithBorrowckFacts<'tcx>> {
+    let body_with_facts = BODIES.with(|bodies| bodies.borrow_mut().remove(&def_id));
+    // SAFETY: The body was created with the TyCtxt that is passed, so it lives for 'tcx. (The
+    // TyCtxt is only passed to make sure that the caller has one with the right lifetime.)
+    body_with_facts.map(|body_with_facts| unsafe {
+        mem::transmute::<BodyWithBorrowckFacts<'static>, BodyWithBorrowckFacts<'tcx>>(body_with_facts)
+    })
+}
@@ -0,0 +1,183 @@
+// © 2020, ETH Zurich
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+/// Code for finding the constraints (i.e. outlives edges, given by the subset_base facts) that have
+/// to be broken to make an error disappear. The path of an error graph tells why the error occurs,
+/// but not which of the constraints on it the programmer should change. A constraint that is
+/// sufficient on its own to fix the error (if it was not there) is a much better hint.
+/// Every candidate is confirmed by running Polonius again on the facts without it, so the result
+/// is exact with regard to the facts (but of course it does not tell if the code that causes the
+/// constraint can be changed).
+
+use dump_borrowck_info::polonius_engine::{Algorithm, Output};
+use dump_borrowck_info::serde_derive;
+use facts::{self, Region};
+
+/// A set of constraints whose removal makes an error disappear.
+#[derive(Clone, Debug, serde_derive::Serialize)]
+pub struct ConstraintCut {
+    /// The constraints, given by the two regions of their edge in the outlives relation.
+    pub edges: Vec<(Region, Region)>,
+    /// If true, removing any single one of the edges fixes the error. Otherwise all of them have
+    /// to be removed together, and none of them can be kept. (I.e. the set is minimal.)
+    pub any_one_suffices: bool,
+}
+
+/// This struct holds the data that is needed to find a constraint cut for an error. After
+/// initializing all fields (best done by using the provided constructor), call compute_cut() to run
+/// the actual computation.
+pub struct ConstraintCutFinder<'a, F: Fn(&facts::AllOutputFacts) -> bool> {
+    all_facts: &'a facts::AllInputFacts,
+    /// The algorithm that Polonius uses for checking the candidates.
+    algorithm: Algorithm,
+    /// Tells if the error is (still) reported in an output of Polonius.
+    error_remains: F,
+}
+
+impl<'a, F: Fn(&facts::AllOutputFacts) -> bool> ConstraintCutFinder<'a, F> {
+    pub fn new(all_facts: &'a facts::AllInputFacts, algorithm: Algorithm, error_remains: F)
+            -> Self {
+        ConstraintCutFinder {
+            all_facts,
+            algorithm,
+            error_remains,
+        }
+    }
+
+    /// Computes a cut among the candidate edges (typically the edges of the path that explains the
+    /// error). If removing single edges fixes the error, the result consists of all these edges.
+    /// Otherwise, a minimal set of candidates whose removal fixes the error is computed: starting
+    /// from all candidates, every edge that can be kept (i.e. the error stays fixed if it is
+    /// restored) is dropped from the set.
+    /// Returns None if even removing all candidates does not fix the error (e.g. because it is also
+    /// caused by another path).
+    pub fn compute_cut(&self, candidate_edges: &[(Region, Region)]) -> Option<ConstraintCut> {
+        trace!("[compute_cut] enter");
+
+        let single_edges: Vec<(Region, Region)> = candidate_edges.iter().cloned()
+            .filter(|&edge| self.is_fixed_without(&[edge]))
+            .collect();
+        if !single_edges.is_empty() {
+            debug!("single edges that fix the error: {:?}", single_edges);
+            trace!("[compute_cut] exit");
+            return Some(ConstraintCut {
+                edges: single_edges,
+                any_one_suffices: true,
+            });
+        }
+
+        let mut cut_edges: Vec<(Region, Region)> = candidate_edges.to_vec();
+        if !self.is_fixed_without(&cut_edges) {
+            debug!("Removing all candidate edges does not fix the error.");
+            trace!("[compute_cut] exit");
+            return None;
+        }
+        let mut index = 0;
+        while index < cut_edges.len() {
+            let restored_edge = cut_edges.remove(index);
+            if !self.is_fixed_without(&cut_edges) {
+                // the edge is needed in the cut.
+                cut_edges.insert(index, restored_edge);
+                index += 1;
+            }
+        }
+        debug!("minimal cut: {:?}", cut_edges);
+        trace!("[compute_cut] exit");
+        Some(ConstraintCut {
+            edges: cut_edges,
+            any_one_suffices: false,
+        })
+    }
+
+    /// Runs Polonius on the facts without the given edges (i.e. without any of the subset_base facts
+    /// of these edges, at any point) and checks if the error disappears.
+    fn is_fixed_without(&self, removed_edges: &[(Region, Region)]) -> bool {
+        let mut modified_facts = self.all_facts.clone();
+        modified_facts.subset_base.retain(|&(region1, region2, _)|
+            !removed_edges.contains(&(region1, region2))
+        );
+        let output = Output::compute(&modified_facts, self.algorithm, false);
+        let is_fixed = !(self.error_remains)(&output);
+        trace!("without {:?} the error is fixed: {:?}", removed_edges, is_fixed);
+        is_fixed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConstraintCut, ConstraintCutFinder};
+    use dump_borrowck_info::polonius_engine::{Algorithm, Output};
+    use facts::{AllInputFacts, AllOutputFacts, Loan, PointIndex, Region, Variable};
+
+    /// Builds the facts of a function with the points 0 to 4 in a row, where the loan 0 is created
+    /// in region 0 at point 0 and invalidated at point 3, while region 3 is live (it is dereferenced
+    /// by the variable 0 that is used at point 4). The given edges of the outlives relation all hold
+    /// at point 0, the error occurs if they connect region 0 to region 3.
+    fn facts(edges: &[(usize, usize)]) -> AllInputFacts {
+        AllInputFacts {
+            cfg_edge: (0..4)
+                .map(|point| (PointIndex::from(point), PointIndex::from(point + 1)))
+                .collect(),
+            loan_issued_at: vec![(Region::from(0), Loan::from(0), PointIndex::from(0))],
+            loan_invalidated_at: vec![(PointIndex::from(3), Loan::from(0))],
+            var_used_at: vec![(Variable::from(0), PointIndex::from(4))],
+            use_of_var_derefs_origin: vec![(Variable::from(0), Region::from(3))],
+            subset_base: edges.iter()
+                .map(|&(region1, region2)|
+                    (Region::from(region1), Region::from(region2), PointIndex::from(0)))
+                .collect(),
+            ..AllInputFacts::default()
+        }
+    }
+
+    fn edges(edges: &[(usize, usize)]) -> Vec<(Region, Region)> {
+        edges.iter().map(|&(region1, region2)| (Region::from(region1), Region::from(region2)))
+            .collect()
+    }
+
+    fn error_remains(output: &AllOutputFacts) -> bool {
+        output.errors.values().any(|loans| loans.contains(&Loan::from(0)))
+    }
+
+    fn compute_cut(all_facts: &AllInputFacts, candidate_edges: &[(usize, usize)])
+            -> Option<ConstraintCut> {
+        ConstraintCutFinder::new(all_facts, Algorithm::Naive, error_remains)
+            .compute_cut(&edges(candidate_edges))
+    }
+
+    #[test]
+    fn single_edges_that_fix_the_error_are_found() {
+        // on the single path 0-1-2-3, removing any one of the edges fixes the error.
+        let all_facts = facts(&[(0, 1), (1, 2), (2, 3)]);
+        assert!(error_remains(&Output::compute(&all_facts, Algorithm::Naive, false)));
+        let cut = compute_cut(&all_facts, &[(0, 1), (1, 2), (2, 3)]).unwrap();
+        assert!(cut.any_one_suffices);
+        assert_eq!(cut.edges, edges(&[(0, 1), (1, 2), (2, 3)]));
+
+        // the path 0-1-3-4 has an alternative from 0 over 2 to 3, hence only its last edge is a
+        // bottleneck. (Region 4 is the live one here.)
+        let all_facts = AllInputFacts {
+            use_of_var_derefs_origin: vec![(Variable::from(0), Region::from(4))],
+            ..facts(&[(0, 1), (1, 3), (0, 2), (2, 3), (3, 4)])
+        };
+        let cut = compute_cut(&all_facts, &[(0, 1), (1, 3), (3, 4)]).unwrap();
+        assert!(cut.any_one_suffices);
+        assert_eq!(cut.edges, edges(&[(3, 4)]));
+    }
+
+    #[test]
+    fn a_minimal_set_of_edges_is_found_greedily() {
+        // the two paths 0-1-3 and 0-2-3 both have to be broken, the greedy search keeps the last
+        // edge of each of them.
+        let all_facts = facts(&[(0, 1), (1, 3), (0, 2), (2, 3)]);
+        let cut = compute_cut(&all_facts, &[(0, 1), (1, 3), (0, 2), (2, 3)]).unwrap();
+        assert!(!cut.any_one_suffices);
+        assert_eq!(cut.edges, edges(&[(1, 3), (2, 3)]));
+
+        // if the candidates only cover one of the paths, there is no cut.
+        assert!(compute_cut(&all_facts, &[(0, 1), (1, 3)]).is_none());
+    }
+}
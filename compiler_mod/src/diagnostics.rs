@@ -0,0 +1,207 @@
+// © 2020, ETH Zurich
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+/// Code for emitting the explanations of errors as diagnostics in the JSON format of rustc (i.e. the
+/// one that rustc uses if it gets `--error-format=json`). Like this, tools that already consume
+/// the diagnostics of rustc (editors, cargo, CI annotators) can show the explanations without any
+/// changes.
+/// The explanations are emitted as top level notes (next to the error of rustc that they explain,
+/// which carries the same code), so they do not report any error a second time.
+/// The structs in this module mirror the (private) ones that rustc uses for serializing its
+/// diagnostics, see rustc_errors::json. Fields that rust-life never fills (e.g. suggestions and
+/// macro expansions) are always serialized as null.
+
+use dump_borrowck_info::serde_derive;
+use dump_borrowck_info::serde_json;
+use rustc_span::source_map::SourceMap;
+use rustc_span::Span;
+use std::io::{self, Write};
+
+/// A diagnostic (or a child of it, e.g. a note), as serialized by rustc.
+#[derive(serde_derive::Serialize)]
+pub struct Diagnostic {
+    /// Always "diagnostic" for top level diagnostics, children do not have this field.
+    #[serde(rename = "$message_type", skip_serializing_if = "Option::is_none")]
+    message_type: Option<&'static str>,
+    pub message: String,
+    pub code: Option<DiagnosticCode>,
+    /// The level, e.g. "error" or "note".
+    pub level: &'static str,
+    pub spans: Vec<DiagnosticSpan>,
+    pub children: Vec<Diagnostic>,
+    /// The diagnostic as it would be printed by rustc in the human readable format, this is what
+    /// cargo shows to the user. Always None for children, as they are part of their parent's.
+    pub rendered: Option<String>,
+}
+
+/// The error code of a diagnostic, e.g. E0499.
+#[derive(serde_derive::Serialize)]
+pub struct DiagnosticCode {
+    pub code: String,
+    /// The long explanation of the code, rust-life never includes it.
+    pub explanation: Option<&'static str>,
+}
+
+/// A span of a diagnostic, given by its position in a source file. Lines and columns are counted
+/// from 1, bytes from 0 (relative to the start of the file), as rustc does.
+#[derive(serde_derive::Serialize)]
+pub struct DiagnosticSpan {
+    pub file_name: String,
+    pub byte_start: u32,
+    pub byte_end: u32,
+    pub line_start: usize,
+    pub line_end: usize,
+    pub column_start: usize,
+    pub column_end: usize,
+    /// There should be exactly one primary span in a top level diagnostic, it is the one that is
+    /// pointed at first.
+    pub is_primary: bool,
+    /// The source lines that are covered by the span.
+    pub text: Vec<DiagnosticSpanLine>,
+    pub label: Option<String>,
+    suggested_replacement: Option<String>,
+    suggestion_applicability: Option<String>,
+    expansion: Option<String>,
+}
+
+/// A source line that is (partly) covered by a span, with the columns (counted from 1) of the part
+/// that is covered.
+#[derive(serde_derive::Serialize)]
+pub struct DiagnosticSpanLine {
+    pub text: String,
+    pub highlight_start: usize,
+    pub highlight_end: usize,
+}
+
+impl Diagnostic {
+    /// Creates a top level diagnostic without any spans or children.
+    pub fn new(level: &'static str, message: String, code: Option<&str>) -> Self {
+        Diagnostic {
+            message_type: Some("diagnostic"),
+            message,
+            code: code.map(|code| DiagnosticCode {
+                code: code.to_string(),
+                explanation: None,
+            }),
+            level,
+            spans: Vec::new(),
+            children: Vec::new(),
+            rendered: None,
+        }
+    }
+
+    /// Adds a child with the given level (e.g. "note") and message, optionally attached to a span.
+    pub fn add_child(&mut self, level: &'static str, message: String,
+                     span: Option<DiagnosticSpan>) {
+        self.children.push(Diagnostic {
+            message_type: None,
+            message,
+            code: None,
+            level,
+            spans: span.into_iter().collect(),
+            children: Vec::new(),
+            rendered: None,
+        });
+    }
+
+    /// Sets the rendered field to a human readable form of the diagnostic, in the style of rustc.
+    /// Must be called once all spans and children are added.
+    pub fn render(&mut self) {
+        let mut rendered = String::new();
+        render_diagnostic(self, &mut rendered);
+        for child in self.children.iter() {
+            render_diagnostic(child, &mut rendered);
+        }
+        self.rendered = Some(rendered);
+    }
+
+    /// Writes the diagnostic as a single line of JSON to the given writer, as rustc does it (on
+    /// stderr) with `--error-format=json`.
+    pub fn write_to<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let json = serde_json::to_string(self)
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+        writeln!(out, "{}", json)
+    }
+
+    /// Writes the diagnostic as a single line of JSON to stderr, i.e. to the stream where rustc
+    /// writes its own diagnostics.
+    pub fn emit(&self) -> io::Result<()> {
+        self.write_to(&mut io::stderr().lock())
+    }
+}
+
+impl DiagnosticSpan {
+    /// Creates the diagnostic span for a span of the source map. Returns None for dummy spans and
+    /// spans that cannot be mapped to source lines.
+    pub fn from_span(source_map: &SourceMap, span: Span, is_primary: bool, label: Option<String>)
+            -> Option<Self> {
+        if span.is_dummy() {
+            return None;
+        }
+        let start = source_map.lookup_char_pos(span.lo());
+        let end = source_map.lookup_char_pos(span.hi());
+        let file_lines = source_map.span_to_lines(span).ok()?;
+        let text = file_lines.lines.iter().map(|line_info| DiagnosticSpanLine {
+            text: file_lines.file.get_line(line_info.line_index)
+                .map_or_else(String::new, |line| line.into_owned()),
+            highlight_start: line_info.start_col.0 + 1,
+            highlight_end: line_info.end_col.0 + 1,
+        }).collect();
+        Some(DiagnosticSpan {
+            file_name: source_map.filename_for_diagnostics(&start.file.name).to_string(),
+            byte_start: start.file.original_relative_byte_pos(span.lo()).0,
+            byte_end: start.file.original_relative_byte_pos(span.hi()).0,
+            line_start: start.line,
+            line_end: end.line,
+            column_start: start.col.0 + 1,
+            column_end: end.col.0 + 1,
+            is_primary,
+            text,
+            label,
+            suggested_replacement: None,
+            suggestion_applicability: None,
+            expansion: None,
+        })
+    }
+}
+
+/// Appends a rendering of the diagnostic (without its children) to the string. Every span is shown
+/// with the first of its source lines, marked with `^` for primary and `-` for secondary spans.
+fn render_diagnostic(diagnostic: &Diagnostic, rendered: &mut String) {
+    let code = diagnostic.code.as_ref().map_or_else(String::new, |code| format!("[{}]", code.code));
+    rendered.push_str(&format!("{}{}: {}\n", diagnostic.level, code, diagnostic.message));
+    let gutter_width = diagnostic.spans.iter()
+        .map(|span| span.line_start.to_string().len())
+        .max()
+        .unwrap_or(0);
+    let gutter = " ".repeat(gutter_width);
+    for span in diagnostic.spans.iter() {
+        rendered.push_str(&format!("{}--> {}:{}:{}\n", gutter, span.file_name, span.line_start,
+                                   span.column_start));
+        let line = match span.text.first() {
+            Some(line) => line,
+            None => continue,
+        };
+        let marker = if span.is_primary { "^" } else { "-" };
+        let marker_len = if line.highlight_end > line.highlight_start {
+            line.highlight_end - line.highlight_start
+        } else {
+            1
+        };
+        rendered.push_str(&format!("{} |\n", gutter));
+        rendered.push_str(&format!("{:>width$} | {}\n", span.line_start, line.text.trim_end(),
+                                   width = gutter_width));
+        let marker_line = format!("{} | {}{} {}", gutter,
+                                  " ".repeat(line.highlight_start.saturating_sub(1)),
+                                  marker.repeat(marker_len),
+                                  span.label.as_ref().map_or("", String::as_str));
+        rendered.push_str(marker_line.trim_end());
+        rendered.push('\n');
+    }
+    if !diagnostic.spans.is_empty() {
+        rendered.push('\n');
+    }
+}
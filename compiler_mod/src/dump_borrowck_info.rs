@@ -8,122 +8,259 @@ pub extern crate csv;
 extern crate datafrog;
 pub extern crate polonius_engine;
 pub extern crate regex;
-pub extern crate rustc;
 pub extern crate rustc_data_structures;
 pub extern crate serde;
 pub extern crate serde_json;
 pub extern crate serde_derive;
-pub extern crate syntax_pos;
 
+use super::algorithm_comparison;
+use super::borrowck_errors;
+use super::borrowck_facts;
+use super::constraint_cut::{ConstraintCut, ConstraintCutFinder};
+use super::diagnostics::{Diagnostic, DiagnosticSpan};
+use super::error_codes::{self, RustcErrorCode};
+use super::explain;
 use super::facts;
+use super::liveness::{LivenessTrace, LivenessTraceFinder};
+use super::location_sensitive::LocationSensitivePathFinder;
+use super::move_errors::{MoveErrorGraph, MoveErrorPathFinder};
+use super::options::{RustLifeOptions, SourcePosition};
+use super::output;
+use super::path_search::{find_shortest_paths_backwards, PathSearch};
 use super::regions;
 
-use std::{cell};
-use std::env;
+use std::cmp;
 use std::collections::{HashMap};
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::{self, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use self::polonius_engine::{Algorithm, Output};
-use rustc::hir::{self, intravisit};
-use rustc::mir;
-use rustc::ty::TyCtxt;
+use rustc_hir as hir;
+use rustc_hir::intravisit;
+use rustc_hir::def_id::{LocalDefId, LOCAL_CRATE};
+use rustc_middle::hir::nested_filter;
+use rustc_middle::mir;
+use rustc_middle::ty::{self, TyCtxt};
+use rustc_errors::MultiSpan;
+use rustc_span::{FileName, Loc, Span, DUMMY_SP};
 use self::rustc_data_structures::fx::FxHashMap;
-use self::facts::{PointIndex, Loan, Region};
+use self::facts::{PointIndex, Loan, MovePath, Region};
 
-pub fn dump_borrowck_info<'a, 'tcx>(tcx: TyCtxt<'a, 'tcx, 'tcx>) {
+pub fn dump_borrowck_info<'tcx>(tcx: TyCtxt<'tcx>, options: &RustLifeOptions) {
     trace!("[dump_borrowck_info] enter");
 
-    //assert!(tcx.use_mir_borrowck(), "NLL is not enabled."); // maybe use borrowck_mode(&self) -> BorrowckMode instead?
+    // If a position is given, only the function that contains it is analyzed.
+    let function_at_position = options.position.as_ref().and_then(|position| {
+        let mut locator = FunctionLocator {
+            tcx,
+            options,
+            position,
+            found: None,
+        };
+        tcx.hir().walk_toplevel_module(&mut locator);
+        if locator.found.is_none() {
+            info!("No function contains the position {:?}:{}", position.file, position.line);
+        }
+        locator.found.map(|(def_id, _)| def_id)
+    });
 
     let mut printer = InfoPrinter {
         tcx: tcx,
+        options,
+        function_at_position,
+        output_layout: output::OutputLayout::new(options.out_dir.clone()),
+        manifest: output::Manifest {
+            crate_name: tcx.crate_name(LOCAL_CRATE).to_string(),
+            functions: Vec::new(),
+        },
     };
-    intravisit::walk_crate(&mut printer, tcx.hir().krate());
+    tcx.hir().walk_toplevel_module(&mut printer);
+
+    printer.manifest.functions.sort_by(|f1, f2| f1.def_path.cmp(&f2.def_path));
+    printer.output_layout.write_manifest(&printer.manifest).expect("Unable to write manifest");
 
     trace!("[dump_borrowck_info] exit");
 }
 
-struct InfoPrinter<'a, 'tcx: 'a> {
-    pub tcx: TyCtxt<'a, 'tcx, 'tcx>,
+/// Visitor that finds the innermost function that contains a given source position, i.e. the
+/// function with the smallest span among the ones that contain it.
+struct FunctionLocator<'a, 'tcx> {
+    tcx: TyCtxt<'tcx>,
+    options: &'a RustLifeOptions,
+    position: &'a SourcePosition,
+    /// The innermost function that was found so far, together with the length of its span.
+    found: Option<(LocalDefId, u32)>,
+}
+
+impl<'a, 'tcx> FunctionLocator<'a, 'tcx> {
+    /// Checks if the span contains the position that is searched for.
+    fn contains_position(&self, span: Span) -> bool {
+        let source_map = self.tcx.sess.source_map();
+        let lo = source_map.lookup_char_pos(span.lo());
+        let hi = source_map.lookup_char_pos(span.hi());
+        let in_file = match lo.file.name {
+            FileName::Real(ref real_file_name) => real_file_name.local_path().map_or(false, |path|
+                self.position.is_in_file(path, &self.options.invocation_dir)
+            ),
+            _ => false,
+        };
+        // lines and columns of the position are counted from 1, the ones of rustc's columns from 0.
+        let line_col = |loc: &Loc| match self.position.column {
+            Some(_) => (loc.line, loc.col.0 + 1),
+            None => (loc.line, 0),
+        };
+        let position = (self.position.line, self.position.column.unwrap_or(0));
+        in_file && line_col(&lo) <= position && position <= line_col(&hi)
+    }
+}
+
+impl<'a, 'tcx> intravisit::Visitor<'tcx> for FunctionLocator<'a, 'tcx> {
+    type NestedFilter = nested_filter::All;
+
+    fn nested_visit_map(&mut self) -> Self::Map {
+        self.tcx.hir()
+    }
+
+    fn visit_fn(&mut self, fk: intravisit::FnKind<'tcx>, fd: &'tcx hir::FnDecl<'tcx>,
+                b: hir::BodyId, s: Span, def_id: LocalDefId) {
+        intravisit::walk_fn(self, fk, fd, b, def_id);
+
+        if !self.contains_position(s) {
+            return;
+        }
+        // the innermost function is found, e.g. the closure if the position is inside of one.
+        let span_len = s.hi().0 - s.lo().0;
+        if self.found.map_or(true, |(_, found_len)| span_len < found_len) {
+            self.found = Some((def_id, span_len));
+        }
+    }
+}
+
+struct InfoPrinter<'a, 'tcx> {
+    pub tcx: TyCtxt<'tcx>,
+    pub options: &'a RustLifeOptions,
+    /// The function that contains the position that was given in the options, if any. If a
+    /// position was given, only this function is analyzed.
+    pub function_at_position: Option<LocalDefId>,
+    /// Gives the locations where all output files shall be written to.
+    pub output_layout: output::OutputLayout,
+    /// The manifest that collects the entries for all functions that were handled, it will be
+    /// written once the entire crate is processed.
+    pub manifest: output::Manifest,
 }
 
 impl<'a, 'tcx> intravisit::Visitor<'tcx> for InfoPrinter<'a, 'tcx> {
-    fn nested_visit_map<'this>(&'this mut self) -> intravisit::NestedVisitorMap<'this, 'tcx> {
-        let map = &self.tcx.hir();
-        intravisit::NestedVisitorMap::All(map)
+    type NestedFilter = nested_filter::All;
+
+    fn nested_visit_map(&mut self) -> Self::Map {
+        self.tcx.hir()
     }
 
-    fn visit_fn(&mut self, fk: intravisit::FnKind<'tcx>, fd: &'tcx hir::FnDecl,
-                b: hir::BodyId, s: syntax_pos::Span, hir_id: hir::HirId) {
+    fn visit_fn(&mut self, fk: intravisit::FnKind<'tcx>, fd: &'tcx hir::FnDecl<'tcx>,
+                b: hir::BodyId, _s: Span, def_id: LocalDefId) {
         // call walk_fn with all received parameters. This is what the default would do, and
         // required to also process all content of the function (and thereby eventually also handle
         // inner (nested) functions.
-        intravisit::walk_fn(self, fk, fd, b, s, hir_id);
+        intravisit::walk_fn(self, fk, fd, b, def_id);
 
         let name = match fk {
-            intravisit::FnKind::ItemFn(name, ..) => name,
-            intravisit::FnKind::Method(name, ..) => name,
-            _ => return, // skip anything else (right now, this seems to include only closures), since we do not know to handle it. (Dealing with closures is deferred for now.)
+            intravisit::FnKind::ItemFn(name, ..) => name.to_string(),
+            intravisit::FnKind::Method(name, ..) => name.to_string(),
+            // closures have no name, use their def path instead. (E.g. `main::{closure#0}`)
+            intravisit::FnKind::Closure => self.tcx.def_path_str(def_id),
         };
 
         trace!("[visit_fn] enter name={:?}", name);
 
-        match env::var_os("PRUSTI_DUMP_PROC").and_then(|value| value.into_string().ok()) {
-            Some(value) => {
-                if name.as_str() != value {
-                    return;
-                }
-            },
-            _ => {},
-        };
-
-        let def_id = self.tcx.hir().local_def_id_from_hir_id(hir_id);
-        // This trial was disabled (and the old version that comes later in the code was re-enabled) too keep the old version working in a fully consistent way for now.
-        //let mir = self.tcx.mir_built(def_id).borrow().clone();
+        if self.options.position.is_some() && self.function_at_position != Some(def_id) {
+            return;
+        }
+        if !self.options.is_function_selected(&self.manifest.crate_name,
+                                              &self.tcx.def_path_str(def_id)) {
+            debug!("Skipping function {:?}, it is not selected.", name);
+            return;
+        }
 
-        self.tcx.mir_borrowck(def_id);
+        // The body and the facts were stored when the function was borrow checked during the
+        // analysis of the crate.
+        let body_with_facts = match borrowck_facts::take_body_with_facts(self.tcx, def_id) {
+            Some(body_with_facts) => body_with_facts,
+            None => {
+                info!("Function {:?} was not borrow checked, it is skipped.", name);
+                return;
+            }
+        };
 
-        // Read Polonius facts.
+        // Get the Polonius facts.
         let def_path = self.tcx.hir().def_path(def_id);
-        let dir_path = PathBuf::from("nll-facts").join(def_path.to_filename_friendly_no_crate());
-        debug!("Reading facts from: {:?}", dir_path);
         let mut facts_loader = facts::FactLoader::new();
-        facts_loader.load_all_facts(&dir_path);
-
-        // Read relations between region IDs and local variables.
-        let renumber_path = PathBuf::from(format!(
-            "log/mir/rustc.{}.-------.renumber.0.mir",
-            def_path.to_filename_friendly_no_crate()));
-        debug!("Renumber path: {:?}", renumber_path);
-		let variable_regions = regions::load_variable_regions(&renumber_path).unwrap();
+        if self.options.facts_from_files {
+            let dir_path = self.options.facts_dir().join(def_path.to_filename_friendly_no_crate());
+            debug!("Reading facts from: {:?}", dir_path);
+            if let Err(err) = facts_loader.load_all_facts(&dir_path) {
+                // the facts of the function are incomplete, the other functions may still be fine.
+                self.tcx.dcx().warn(format!("cannot load the facts of function `{}`, it is \
+                                             skipped: {}", name, err));
+                return;
+            }
+        } else {
+            facts_loader.load_facts_from_compiler(
+                body_with_facts.input_facts.as_ref().expect("The borrow checker provided no facts"),
+                body_with_facts.location_table.as_ref().expect("The borrow checker provided no location table"));
+        }
 
         let all_facts = facts_loader.facts;
-        let output = Output::compute(&all_facts, Algorithm::Naive, true);
+        let output = Output::compute(&all_facts, self.options.polonius_algorithm, true);
 
-        println!("errors: {:?}", output.errors);
+        debug!("subset_errors: {:?}", output.subset_errors);
 
-        // This was disabled before (instead, an "older" version of mir was read from tcx before), but was re-enabled now to keep the old code work in a consistent way for now.
-        let mir = self.tcx.mir_validated(def_id).borrow();
+        debug!("errors: {:?}", output.errors);
+
+        // This is the body that the borrow checker used, i.e. the one where all regions are
+        // renumbered to the regions that are used in the facts.
+        let mir = &body_with_facts.body;
 
         let interner = facts_loader.interner;
 
-        let region_to_local_map = regions::load_region_to_local_map(&renumber_path).expect("Error reading mir dump file!");
+        let region_to_local_map = regions::compute_region_to_local_map(self.tcx, mir);
 
         debug!("region_to_local_map: {:?}", region_to_local_map);
 
+        // The regions of a closure that come from its parent are linked to the variables of the
+        // parent that they belong to, e.g. to the captured variables. The parent is visited after
+        // the closure, so its body is still available.
+        let captured_names_for_regions = match fk {
+            intravisit::FnKind::Closure => self.compute_captured_names_for_regions(def_id, mir),
+            _ => HashMap::new(),
+        };
+
+        debug!("captured_names_for_regions: {:?}", captured_names_for_regions);
+
+        let universal_regions = regions::compute_universal_regions(self.tcx, def_id, mir, &all_facts);
+
+        debug!("universal_regions: {:?}", universal_regions);
+
 
         let mut mir_info_printer = MirInfoPrinter {
             tcx: self.tcx,
+            options: self.options,
+            output_layout: &self.output_layout,
+            crate_name: self.manifest.crate_name.clone(),
+            def_id,
             mir: mir,
             borrowck_in_facts: all_facts,
             borrowck_out_facts: output,
             interner: interner,
-			variable_regions: variable_regions,
             region_to_local_map,
+            captured_names_for_regions,
+            captures_for_regions: HashMap::new(),
+            universal_regions,
             def_path: def_path,
         };
-        mir_info_printer.print_info();
+        mir_info_printer.captures_for_regions = mir_info_printer.compute_captures_for_regions();
+        debug!("captures_for_regions: {:?}", mir_info_printer.captures_for_regions);
+        let function_entry = mir_info_printer.print_info();
+        self.manifest.functions.push(function_entry);
 
         debug!("[visit_fn] ----------> Done with handling function {:?} <----------", name);
 
@@ -131,18 +268,90 @@ impl<'a, 'tcx> intravisit::Visitor<'tcx> for InfoPrinter<'a, 'tcx> {
     }
 }
 
+impl<'a, 'tcx> InfoPrinter<'a, 'tcx> {
+    /// Gives the names of the variables of the parent of a closure (i.e. of the function or closure
+    /// that defines it) that the regions of the closure are linked to, see
+    /// regions::link_closure_regions(). The regions of the closure are only included if the region
+    /// of the parent is introduced by a variable with a name.
+    fn compute_captured_names_for_regions(&self, closure_def_id: LocalDefId,
+                                          closure_body: &mir::Body<'tcx>)
+            -> HashMap<Region, String> {
+        let tcx = self.tcx;
+        let parent_def_id = tcx.local_parent(closure_def_id);
+        let captured_names = borrowck_facts::with_body_with_facts(tcx, parent_def_id, |parent| {
+            let parent_region_to_local_map =
+                regions::compute_region_to_local_map(tcx, &parent.body);
+            regions::link_closure_regions(tcx, closure_def_id.to_def_id(), closure_body,
+                                          &parent.body)
+                .into_iter()
+                .filter_map(|(closure_region, parent_region)| {
+                    parent_region_to_local_map.get(&parent_region)
+                        .and_then(|&local| regions::find_name_of_local(&parent.body, local))
+                        .map(|name| (closure_region, name))
+                })
+                .collect()
+        });
+        captured_names.unwrap_or_else(|| {
+            debug!("The body of the parent {:?} of the closure {:?} is not available.",
+                   parent_def_id, closure_def_id);
+            HashMap::new()
+        })
+    }
+}
+
+/// Describes how a variable is captured by a closure, for the explanations of errors.
+fn capture_mode_description(capture_kind: ty::UpvarCapture) -> String {
+    match capture_kind {
+        ty::UpvarCapture::ByValue => "by value",
+        ty::UpvarCapture::ByRef(ty::BorrowKind::ImmBorrow) => "by reference",
+        ty::UpvarCapture::ByRef(ty::BorrowKind::UniqueImmBorrow) => "by unique reference",
+        ty::UpvarCapture::ByRef(ty::BorrowKind::MutBorrow) => "by mutable reference",
+    }.to_string()
+}
+
+/// Tells how well the code of an error of rustc matches the code of a classified error (see
+/// MirInfoPrinter::classify_error()): 0 if both are the same, 2 if they differ, and 1 if either
+/// of them is unknown.
+fn code_match_rank(error_code: Option<RustcErrorCode>, rustc_code: Option<&str>) -> usize {
+    match (error_code, rustc_code) {
+        (Some(code), Some(rustc_code)) if code.as_str() == rustc_code => 0,
+        (Some(_), Some(_)) => 2,
+        _ => 1,
+    }
+}
+
+/// Matches the errors of Polonius with the errors of rustc one-to-one, given the candidate pairs
+/// of the index of an error and the index of an error of rustc, each with its rank (smaller ranks
+/// are better matches). The pairs are matched greedily in the order of their ranks, i.e. a pair is
+/// matched unless either of its errors is matched already.
+/// Returns the index of the matching error of rustc for every error that has one, keyed by the
+/// index of the error. (Hence the errors without an entry are the ones that only Polonius reports,
+/// and the errors of rustc that are not among the values are the ones that only rustc reports.)
+fn match_greedily<R: Ord>(mut candidates: Vec<(R, usize, usize)>) -> HashMap<usize, usize> {
+    candidates.sort();
+    let mut matches: HashMap<usize, usize> = HashMap::new();
+    for (_, error_index, rustc_index) in candidates {
+        if !matches.contains_key(&error_index) &&
+                !matches.values().any(|&matched_index| matched_index == rustc_index) {
+            matches.insert(error_index, rustc_index);
+        }
+    }
+    matches
+}
 
 /// This struct holds the functions and data that is needed to find a path in an outlives graph
 /// that shall be sufficient to describe and explain a given error (that was detected by the (naive)
 /// Poloinus borrow checker) and should be helpful to understand the causes for the error.
-/// After initializing all needed fields (best done by using the provided constructor), call
-/// compute_error_path() to run the actual path computation and to get back the resulting path.
+/// An error is identified by the point where it occurs and by one single loan that is invalidated
+/// at this point. (Polonius may report several loans for the same point, each of them is considered
+/// to be a distinct error and needs a separate ErrorPathFinder.)
+/// After initializing all needed fields (best done by using the provided constructor), the path is
+/// searched (see MirInfoPrinter::find_error_paths()) from the region that find_error_region() gives
+/// backwards to a region that fulfills issues_error_loan().
 struct ErrorPathFinder<'epf> {
     all_facts: &'epf facts::AllInputFacts,
     output: &'epf facts::AllOutputFacts,
-    error_fact: (PointIndex, Vec<Loan>),
-    outlives: &'epf Vec<(Region, Region, PointIndex)>,
-//    start_points_of_error_loan: Vec<PointIndex>,
+    error_point: PointIndex,
     error_loan: Loan,
 }
 
@@ -150,59 +359,39 @@ impl <'epf> ErrorPathFinder<'epf> {
     /// The constructor to create a new instance and inserting all needed information into it.
     /// When this constructor was executed (with sensible arguments), the struct instance is ready
     /// to run the path computation.
+    /// The error that shall be explained is given by the point error_point and the loan
+    /// error_loan, both should be taken from an entry of the errors of the Polonius output.
     fn new(all_facts: &'epf facts::AllInputFacts, output: &'epf facts::AllOutputFacts,
-           error_fact: (PointIndex, Vec<Loan>), outlives: &'epf Vec<(Region, Region, PointIndex)>) -> Self {
+           error_point: PointIndex, error_loan: Loan) -> Self {
         ErrorPathFinder {
             all_facts,
             output,
-            error_fact,
-            outlives,
-//            start_points_of_error_loan: Vec::default(),
-            error_loan: Loan::from(0),
-        }
-    }
-
-    /// The method that does run the entire path computation, using the information that is provided
-    /// by the fields of the struct instance it is called on. Best call this after initiation a
-    /// struct instance with the provided constructor.
-    /// Note that this method will change some of the fields of the struct, and it is not intended
-    /// to run this method more then once on the same struct instance. (This might work, but it was
-    /// never tested and no guarantees are provided.) I.e. it is not guaranteed that this method
-    /// is idempotent with respect to the struct and it's result.
-    /// On completion, the method will return the path as a vector of regions (facts::Region).
-    /// This gives a simple, portable and unique representation of the found path. However, please
-    /// note that the path is given in backwards direction. I.e. the first element of the vector
-    /// it the last of the path in the outlives relation (graph).
-    /// When no such path is found an empty vector (Vec::default()) is returned. In this case the
-    /// this method will print also a warning to the log. (Since this did never happen while
-    /// testing as of now.)
-    /// Sometimes also the search for the starting region fails. In this case, also an empty vector
-    /// (Vec::default()) is returned, but this does not cause a warning. (As this seems to happen
-    /// under some circumstances, especially when multiple errors are found in the input program.)
-    /// Especially, for some programs Polunius finds several points for an error. In this case one
-    /// should try all as inupt for the path search, as sometimes not all do lead to a successful
-    /// search. (For some no starting region is found.)
-    fn compute_error_path(&mut self) -> Vec<Region> {
-        trace!("[compute_error_path] enter");
-
-        let regions_life_at_error: Vec<Region> = self.all_facts.region_live_at.iter().filter(|&(_r, p)|
-            *p == self.error_fact.0
-        ).map(|&(r, _p)| r).collect();
-
-        debug!("regions_life_at_error: {:?}", regions_life_at_error);
-
-        //NOTE It might be possible to simplify this, making the next step superfluous, as we already get a loan form the error in error_fact.
+            error_point,
+            error_loan,
+        }
+    }
 
-        let loans_invalidated_by_error: Vec<Loan> = self.all_facts.invalidates.iter().filter(|&(p, _l)|
-            *p == self.error_fact.0
-        ).map(|&(_p, l)| l).collect();
+    /// Finds the region where the search for the path starts, i.e. a region that contains the
+    /// error loan at the error point and is live there. (The path is searched backwards from it to
+    /// the region of the loan, see issues_error_loan().)
+    /// Returns None if no such region is found. This is not reported as a warning, as it seems to
+    /// happen under some circumstances, especially when multiple errors are found in the input
+    /// program. (Polonius may find several points for an error, and for some of them no starting
+    /// region is found.)
+    fn find_error_region(&self) -> Option<Region> {
+        trace!("[find_error_region] enter");
+
+        let regions_life_at_error: Vec<Region> = self.output.origin_live_on_entry
+            .get(&self.error_point)
+            .cloned()
+            .unwrap_or_default();
 
-        debug!("loans_invalidated_by_error: {:?}", loans_invalidated_by_error);
+        debug!("regions_life_at_error: {:?}", regions_life_at_error);
 
-        let mut requires = self.all_facts.borrow_region.clone();
+        let mut requires = self.all_facts.loan_issued_at.clone();
 
         requires.extend(
-            self.output.restricts.iter().flat_map(
+            self.output.origin_contains_loan_at.iter().flat_map(
                 |(&point, region_map)|
                     region_map.iter().flat_map(
                         move |(&region, loans)|
@@ -211,215 +400,1385 @@ impl <'epf> ErrorPathFinder<'epf> {
             )
         );
 
-        debug!("requires, after adding elements from output.restricts : {:?}", requires);
+        debug!("requires, after adding elements from output.origin_contains_loan_at : {:?}", requires);
 
-        let error_region_loan_opt = requires.iter().filter(|&(r, l, p)|
-            *p == self.error_fact.0 &&
-                loans_invalidated_by_error.contains(l) &&
+        let error_region = requires.iter().filter(|&(r, l, p)|
+            *p == self.error_point &&
+                *l == self.error_loan &&
                 regions_life_at_error.contains(r)
-        ).map(|&(r, l, _)| (r, l)).next();
-        let (error_region, error_loan_var) = match error_region_loan_opt {
-            Some(error_descr) => error_descr,
-            None => return Vec::default(),
-        };
-        self.error_loan = error_loan_var;
+        ).map(|&(r, _, _)| r).next();
 
-        debug!("error_point: {:?}", self.error_fact.0);
+        debug!("error_point: {:?}", self.error_point);
         debug!("error_region: {:?}", error_region);
         debug!("error_loan: {:?}", self.error_loan);
-        debug!("all_facts.region_live_at: {:?}", self.all_facts.region_live_at);
+        debug!("output.origin_live_on_entry: {:?}", self.output.origin_live_on_entry);
         debug!("all_facts.cfg_edge: {:?}", self.all_facts.cfg_edge);
-        debug!("all_facts.borrow_region: {:?}", self.all_facts.borrow_region);
+        debug!("all_facts.loan_issued_at: {:?}", self.all_facts.loan_issued_at);
 
-        assert!(self.error_fact.1.contains(&self.error_loan));
+        trace!("[find_error_region] exit");
+        error_region
+    }
 
-        debug!("Start computing path to error:");
+    /// Finds the region where the search for the path starts for an error that is only found
+    /// location insensitively (see MirInfoPrinter::compute_location_insensitive_errors()). Such an
+    /// error has no region that contains the loan at the error point (according to the location
+    /// sensitive output), instead a region that is live at the error point and is reachable from
+    /// the region of the loan in the outlives relation (ignoring the points of the constraints) is
+    /// taken, the one that is the fewest edges away.
+    /// Returns None if no such region is found.
+    fn find_location_insensitive_error_region(&self) -> Option<Region> {
+        trace!("[find_location_insensitive_error_region] enter");
+        let regions_live_at_error: Vec<Region> = self.output.origin_live_on_entry
+            .get(&self.error_point)
+            .cloned()
+            .unwrap_or_default();
+        let mut reached: Vec<Region> = self.all_facts.loan_issued_at.iter()
+            .filter(|&&(_, loan, _)| loan == self.error_loan)
+            .map(|&(region, _, _)| region)
+            .collect();
+        let mut index = 0;
+        let mut error_region = None;
+        // breadth-first search forward through the outlives relation, the reached regions are
+        // kept in the order in which they are reached.
+        while index < reached.len() {
+            let region = reached[index];
+            if regions_live_at_error.contains(&region) {
+                error_region = Some(region);
+                break;
+            }
+            for &(region1, region2, _) in self.all_facts.subset_base.iter() {
+                if region1 == region && !reached.contains(&region2) {
+                    reached.push(region2);
+                }
+            }
+            index += 1;
+        }
+        debug!("location insensitive error_region: {:?}", error_region);
+        trace!("[find_location_insensitive_error_region] exit");
+        error_region
+    }
 
-        let mut path_to_error: Vec<Region> = Vec::new();
-        let res = self.path_to_error_backwards(error_region,&mut path_to_error);
+    /// The termination criterion of the search for the path: a region that does include the error
+    /// loan (may also be called error borrow) because it is issued for it is considered to be far
+    /// enough.
+    fn issues_error_loan(&self, region: Region) -> bool {
+        self.all_facts.loan_issued_at.iter().any(|&(r, l, _)| r == region && l == self.error_loan)
+    }
+}
 
-        debug!("path_to_error after done with iteration: {:?}", path_to_error);
+/// finds all regions in the outlives relation that are directly before the region given as start.
+fn find_prev_regions(outlives: &[(Region, Region, PointIndex)], start: Region) -> Vec<Region> {
+    outlives.iter().filter(|&(_, r2, _)|
+        *r2 == start
+    ).map(|&(r1, _, _)| r1).collect()
+}
+
+/// This function does implement the (recursive) traversal of the graph described by the outlives
+/// relation, and thereby tries to find a path from the Region given as `start` to a region that
+/// fulfills the termination criterion is_end. (E.g. a region that includes the error loan, or a
+/// given universal region.)
+/// The search is (for now) implemented in a depth-first traversal, and the first path that
+/// leads to a region that fulfills the criterion is taken. This graph is then returned as
+/// the out-parameter cur_path.
+/// In addition, the function returns true if it did succeed in finding a path that fulfills the
+/// termination criterion. If non was fround, false is returned and the content of cur_path is
+/// not altered. (The behaviour regarding cur_path in the error case might change in the future,
+/// e.g. for the sake of performance.)
+fn find_path_backwards<F: Fn(Region) -> bool>(outlives: &[(Region, Region, PointIndex)],
+                                              start: Region, is_end: &F,
+                                              cur_path: &mut Vec<Region>) -> bool {
+    debug!("cur_region (start): {:?}", start);
+
+    // add start to the path, as it will now become part of it.
+    cur_path.push(start);
+
+    if is_end(start) {
+        // therefore, stop the recursion here, as we consider this to be gone far enough.
+        // Also, this path is considered to lead to success, so return true
+        debug!("Success path found by find_path_backwards, ending at region {:?}", start);
+        return true
+    }
 
-        trace!("[compute_error_path] exit");
+    let mut prev_regions = find_prev_regions(outlives, start);
+    prev_regions.dedup();
+    debug!("prev_regions: {:?}", prev_regions);
 
-        if res {
-            path_to_error
+    for pr in prev_regions {
+        if cur_path.contains(&pr) {
+            // this element already is part of the path, so there would be circle by adding it again, therefore stop here.
+            continue
         } else {
-            warn!("No path to explain the error was found for the start point {:?} and error loan \
-                    {:?}!", self.error_fact.0, self.error_loan);
-            Vec::default()
-        }
-    }
-
-    /// finds all regions in the outlives (available as field of the struct) that are directly
-    /// before the region given as start.
-    fn find_prev_regions(&self, start: Region)
-                         -> Vec<Region> {
-        self.outlives.iter().filter(|&(_, r2, _)|
-            *r2 == start
-        ).map(|&(r1, _, _)| r1).collect()
-    }
-
-    /// This function finds all loans that belong to a certain region as given by the
-    /// all_facts.borrow_region input. (Is available in self)
-    /// Note that this does not give all loans that might be "live" for this region, or relevant for
-    /// this region. This would be given by the (computed) requires relation. Instead, this only
-    /// includes the loans that were considered to belong to a region when they were provided as
-    /// input fact (borrow_region) to the borrow checker.
-    fn loan_of_reagion(&self, reg: Region) -> Vec<Loan> {
-        self.all_facts.borrow_region.iter().filter(|&(r, _, _)|
-            *r == reg
-        ).map(|&(_, l, _)| l).collect()
-    }
-
-    /// This method does implement the (recursive) traversal of the graph described by the outlives
-    /// relation (given as self.outlives), and thereby tries to find a path from the Region given as
-    /// `start` to a region that includes the error loan/borrow.
-    /// The search is (for now) implemented in a depth-first traversal, and the first path that
-    /// leads to a region that fulfills the criterion is taken. This graph is then returned as
-    /// the out-parameter cur_path.
-    /// In addition, the method returns true if it did succeed in finding a path that fulfills the
-    /// termination criterion. If non was fround, false is returned and the content of cur_path is
-    /// not altered. (The behaviour regarding cur_path in the error case might change in the future,
-    /// e.g. for the sake of performance.)
-    fn path_to_error_backwards(&self, start: Region, mut cur_path: &mut Vec<Region>)
-                               -> bool {
-        debug!("cur_region (start): {:?}", start);
-
-        // add start to the path, as it will now become part of it.
-        cur_path.push(start);
-
-        let input_loans_of_cur_region =  self.loan_of_reagion(start);
-        debug!("input_loans_of_cur_region: {:?}", input_loans_of_cur_region);
-
-        if self.all_facts.borrow_region.iter().filter(|&(r, l, _)|
-                    *r == start && *l == self.error_loan
-                ).count() > 0 {
-            // the start region does include the error loan. (May also be called error borrow.)
-            // therefore, stop the recursion here, as we consider this to be gone far enough.
-            // Also, this path is considered to lead to success, so return true
-            debug!("Success path found by path_to_error_backwards, ending at region {:?}", start);
-            return true
-        }
-
-        let mut prev_regions = self.find_prev_regions(start);
-        prev_regions.dedup();
-        debug!("prev_regions: {:?}", prev_regions);
-
-        for pr in prev_regions {
-            if cur_path.contains(&pr) {
-                // this element already is part of the path, so there would be circle by adding it again, therefore stop here.
-                continue
+            let mut pr_path = cur_path.clone();
+            if find_path_backwards(outlives, pr, is_end, &mut pr_path) {
+                cur_path.clear();
+                cur_path.append(&mut pr_path);
+                return true
             } else {
-                let mut pr_path = cur_path.clone();
-                if self.path_to_error_backwards(pr, &mut pr_path) {
-                    cur_path.clear();
-                    cur_path.append(&mut pr_path);
-                    return true
-                } else {
-                    continue
-                }
+                continue
             }
         }
-        // There are no more previous regions to inspect, and apparently none did lead to a path that leads to "success", so this is a dead end, return false.
-        false
     }
+    // There are no more previous regions to inspect, and apparently none did lead to a path that leads to "success", so this is a dead end, return false.
+    false
+}
+
+/// An error that Polonius reported for a function. Together with the point where it occurs, it
+/// identifies an error that is explained by a separate EnrichedErrorGraph.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum BorrowckError {
+    /// The loan is invalidated while it is still live. (E.g. E0499, E0502, E0505, but also E0515 or
+    /// E0597, where the loan has to outlive a universal region, since the data is dropped while the
+    /// universal region, which is live in the whole function, still contains the loan.)
+    LoanInvalidated(Loan),
+    /// The first (universal) region has to outlive the second one (i.e. be a subset of it, as
+    /// Polonius calls it), which is not known from the signature of the function. (E.g. E0621)
+    SubsetRequired(Region, Region),
+    /// The move path is used while it may have been moved out (E0382). Such an error is not
+    /// explained by a path in the outlives relation, but by a path in the control flow graph, see
+    /// move_errors::MoveErrorPathFinder.
+    UseOfMoved(MovePath),
 }
 
-struct MirInfoPrinter<'a, 'tcx: 'a> {
-    pub tcx: TyCtxt<'a, 'tcx, 'tcx>,
-    pub mir: cell::Ref<'a, mir::Mir<'tcx>>,
-//    pub mir: mir::Mir<'tcx>,
+struct MirInfoPrinter<'a, 'tcx> {
+    pub tcx: TyCtxt<'tcx>,
+    pub options: &'a RustLifeOptions,
+    pub output_layout: &'a output::OutputLayout,
+    pub crate_name: String,
+    pub def_id: LocalDefId,
+    pub mir: &'a mir::Body<'tcx>,
     pub borrowck_in_facts: facts::AllInputFacts,
     pub borrowck_out_facts: facts::AllOutputFacts,
     pub interner: facts::Interner,
-	pub variable_regions: HashMap<mir::Local, Region>,
     /// This gives the mapping from regions to the locals that introduced them.
-    /// This information can be read form a MIR dump by the method regions::load_region_to_local_map
+    /// This information can be computed from the MIR by regions::compute_region_to_local_map
     pub region_to_local_map: HashMap<Region, mir::Local>,
-    pub def_path: rustc::hir::map::DefPath,
+    /// For closures, this gives the names of the variables of the enclosing function (or closure)
+    /// that the regions of the closure are linked to, e.g. the names of captured variables. It is
+    /// empty for all other functions. Can be computed by
+    /// InfoPrinter::compute_captured_names_for_regions.
+    pub captured_names_for_regions: HashMap<Region, String>,
+    /// This maps the regions of the temporaries that hold the captured variables of the closures
+    /// that are created in the function to the information about the capture.
+    /// This information can be computed from the MIR by compute_captures_for_regions()
+    pub captures_for_regions: HashMap<Region, CaptureInfo>,
+    /// This gives the universal regions of the function (i.e. the ones of its signature) that were
+    /// identified, together with their description.
+    /// This information can be computed by regions::compute_universal_regions
+    pub universal_regions: HashMap<Region, regions::UniversalRegion>,
+    pub def_path: hir::definitions::DefPath,
 }
 
 
 impl<'a, 'tcx> MirInfoPrinter<'a, 'tcx> {
-    pub fn print_info(&mut self) -> Result<(), io::Error> {
-        self.print_error();
+    /// Explains all errors of the function and writes the resulting files, see print_error().
+    /// If further algorithms were requested for comparison, their errors are compared with the ones
+    /// that are explained, see write_algorithm_comparison().
+    /// Returns the entry of the function for the manifest.
+    pub fn print_info(&mut self) -> output::FunctionEntry {
+        let algorithm_comparison_file = self.write_algorithm_comparison();
+        let (errors, diagnostics) = self.print_error();
+        for diagnostic in diagnostics.iter() {
+            if let Err(err) = diagnostic.emit() {
+                warn!("Unable to emit the diagnostic `{}`: {}", diagnostic.message, err);
+            }
+        }
+        output::FunctionEntry {
+            def_path: self.tcx.def_path_str(self.def_id),
+            function_name: self.def_path.to_filename_friendly_no_crate(),
+            algorithm_comparison_file,
+            errors,
+        }
+    }
+
+    /// Runs the algorithms of Polonius that were given for comparison (by `--rust-life-compare`)
+    /// on the facts of the function, and writes the comparison of their errors with the ones of the
+    /// algorithm that was used for the explanations to the directory of the function. Every
+    /// algorithm that disagrees is reported to the log as well.
+    /// Returns the path of the written file (relative to the root of the output directory), None if
+    /// no comparison was requested or the file cannot be written.
+    fn write_algorithm_comparison(&self) -> Option<PathBuf> {
+        if self.options.compare_algorithms.is_empty() {
+            return None;
+        }
+        let function_name = self.def_path.to_filename_friendly_no_crate();
+        let comparison = algorithm_comparison::compare_algorithms(
+            &self.borrowck_in_facts, self.options.polonius_algorithm, &self.borrowck_out_facts,
+            &self.options.compare_algorithms);
+        for disagreement in comparison.disagreements.iter() {
+            warn!("In `{}`, {} disagrees with {}: {} error(s) are only found by {}, {} error(s) \
+                   only by {}.", function_name, disagreement.algorithm,
+                  comparison.reference_algorithm, disagreement.missing.len(),
+                  comparison.reference_algorithm, disagreement.additional.len(),
+                  disagreement.algorithm);
+        }
+
+        let function_dir = self.output_layout.function_dir(&self.crate_name, &function_name);
+        let comparison_path = function_dir.join("algorithm_comparison.json");
+        let result = self.output_layout.create_dir(&function_dir)
+            .and_then(|()| File::create(self.output_layout.resolve(&comparison_path)))
+            .and_then(|out_file| serde_json::to_writer_pretty(out_file, &comparison)
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, err)));
+        match result {
+            Ok(()) => Some(comparison_path),
+            Err(err) => {
+                warn!("Unable to write the algorithm comparison {:?}: {}", comparison_path, err);
+                None
+            },
+        }
+    }
+
+    /// Computes and writes the explanations for all errors that Polonius found in the function.
+    /// Each distinct error, i.e. each pair of an error point and a loan that is invalidated at this
+    /// point, or of two universal regions where the first one must outlive the second one (see
+    /// BorrowckError), is explained by a separate EnrichedErrorGraph. The graph is written both as dot
+    /// graph and as JSON dump to the directory that the output layout gives for the error.
+    /// The errors are identified by their index in the result of distinct_errors().
+    /// If a position was given in the options, only the error that is closest to it is explained,
+    /// it keeps the index that it has among all errors of the function.
+    /// Uses of moved values are explained by a MoveErrorGraph instead, see
+    /// compute_move_error_graph(), which is written to the same directory.
+    /// The explanations are also attached as notes to the errors that rustc reports for the
+    /// function (see attach_explanations_to_rustc_errors()), unless they are emitted as JSON
+    /// diagnostics.
+    /// Every error is matched against the errors that rustc reports for the function (see
+    /// match_rustc_errors()). Errors that rustc does not report are marked as
+    /// ErrorStatus::PoloniusOnly, and their explanations are neither attached to an error of rustc
+    /// nor emitted as JSON diagnostics.
+    /// Returns the manifest entries for all explained errors, including the ones for which no
+    /// explanation was found. (For these, no files are written.) Unless a position was given, they
+    /// are followed by entries for the errors that only rustc reports.
+    /// Also returns the JSON diagnostics for the explained errors if they were requested, for
+    /// emitting them once the function is done.
+    fn print_error(&mut self) -> (Vec<output::ErrorEntry>, Vec<Diagnostic>) {
+        let function_name = self.def_path.to_filename_friendly_no_crate();
+        let mut error_entries = Vec::new();
+        let mut explanation_notes = Vec::new();
+        let mut diagnostics = Vec::new();
+        let rustc_errors = borrowck_errors::summarize_held_errors(self.def_id);
+
+        let all_errors = self.distinct_errors();
+        let rustc_matches = self.match_rustc_errors(&rustc_errors, &all_errors);
+        let mut errors: Vec<(usize, (PointIndex, BorrowckError))> =
+            all_errors.iter().cloned().enumerate().collect();
+        if let Some(ref position) = self.options.position {
+            errors = errors.into_iter()
+                .min_by_key(|&(_, (err_point, _))| self.distance_to_position(err_point, position))
+                .into_iter()
+                .collect();
+        }
+        let (move_errors, errors): (Vec<_>, Vec<_>) = errors.into_iter()
+            .partition(|&(_, (_, error))| matches!(error, BorrowckError::UseOfMoved(_)));
+
+        for (error_index, (err_point, error, error_graph_opt)) in
+                self.compute_error_graphs(errors) {
+            let mut error_entry = output::ErrorEntry {
+                index: error_index,
+                status: output::ErrorStatus::PoloniusOnly,
+                rustc_code: None,
+                rustc_message: None,
+                loan: match error {
+                    BorrowckError::LoanInvalidated(loan) => Some(loan),
+                    _ => None,
+                },
+                required_subset: match error {
+                    BorrowckError::SubsetRequired(region1, region2) => Some((region1, region2)),
+                    _ => None,
+                },
+                moved_path: None,
+                line: self.get_line_for_point(err_point),
+                json_file: None,
+                dot_file: None,
+                explanation_file: None,
+            };
+            let rustc_index = self.mark_error_status(&mut error_entry, &rustc_errors,
+                                                     &rustc_matches, err_point);
+
+            if let Some(error_graph) = error_graph_opt {
+                let notes = self.write_error_graph(&mut error_entry, &error_graph, err_point, error,
+                                                   &mut diagnostics);
+                if let Some(rustc_index) = rustc_index {
+                    explanation_notes.push((rustc_index, notes));
+                }
+            }
+
+            error_entries.push(error_entry);
+        }
+
+        for (error_index, (err_point, error)) in move_errors {
+            let moved_path = match error {
+                BorrowckError::UseOfMoved(moved_path) => moved_path,
+                _ => unreachable!(),
+            };
+            let mut error_entry = output::ErrorEntry {
+                index: error_index,
+                status: output::ErrorStatus::PoloniusOnly,
+                rustc_code: None,
+                rustc_message: None,
+                loan: None,
+                required_subset: None,
+                moved_path: Some(moved_path),
+                line: self.get_line_for_point(err_point),
+                json_file: None,
+                dot_file: None,
+                explanation_file: None,
+            };
+            let rustc_index = self.mark_error_status(&mut error_entry, &rustc_errors,
+                                                     &rustc_matches, err_point);
+
+            if let Some(move_error_graph) = self.compute_move_error_graph(err_point, moved_path) {
+                let error_dir = self.output_layout.error_dir(&self.crate_name, &function_name,
+                                                             error_index);
+                match self.output_layout.create_dir(&error_dir) {
+                    Ok(()) => self.write_move_error_graph(&mut error_entry, &move_error_graph,
+                                                          &error_dir),
+                    Err(err) => warn!("Unable to create the directory {:?} for the error at {:?}, \
+                                       its files are not written: {}", error_dir, err_point, err),
+                }
+
+                if let Some(rustc_index) = rustc_index {
+                    explanation_notes.push((rustc_index, move_error_graph.explanation_notes()));
+                }
+
+                if self.options.json_diagnostics &&
+                        error_entry.status != output::ErrorStatus::PoloniusOnly {
+                    diagnostics.push(self.create_move_error_diagnostic(&move_error_graph));
+                }
+            } else {
+                info!("No explanation found for the error at {:?} for {:?}.", err_point, error);
+            }
+
+            error_entries.push(error_entry);
+        }
+
+        if self.options.position.is_none() {
+            let rustc_only_errors: Vec<(usize, &borrowck_errors::HeldErrorSummary)> =
+                rustc_errors.iter().enumerate()
+                    .filter(|&(rustc_index, _)|
+                        !rustc_matches.values().any(|&matched_index| matched_index == rustc_index)
+                    )
+                    .collect();
+            let insensitive_errors = if self.options.explain_rustc_only &&
+                    !rustc_only_errors.is_empty() {
+                self.compute_location_insensitive_errors()
+            } else {
+                Vec::new()
+            };
+            for (error_index, (rustc_index, rustc_error)) in
+                    (all_errors.len()..).zip(rustc_only_errors) {
+                info!("The error `{}` of rustc is not reported by Polonius.", rustc_error.message);
+                let mut error_entry = output::ErrorEntry {
+                    index: error_index,
+                    status: output::ErrorStatus::RustcOnly,
+                    rustc_code: rustc_error.code.clone(),
+                    rustc_message: Some(rustc_error.message.clone()),
+                    loan: None,
+                    required_subset: None,
+                    moved_path: None,
+                    line: rustc_error.span.primary_span()
+                        .map_or((0, String::new()), |span| self.get_line_for_span(span)),
+                    json_file: None,
+                    dot_file: None,
+                    explanation_file: None,
+                };
+
+                // the error is explained by the location insensitive error on its line, if any.
+                let insensitive_error = insensitive_errors.iter()
+                    .filter_map(|&(err_point, loan)|
+                        self.match_distance(&rustc_error.span, &self.get_loc_for_point(err_point))
+                            .map(|distance| (distance, (err_point, loan)))
+                    )
+                    .min_by_key(|&(distance, _)| distance)
+                    .map(|(_, insensitive_error)| insensitive_error);
+                if let Some((err_point, loan)) = insensitive_error {
+                    let error = BorrowckError::LoanInvalidated(loan);
+                    error_entry.loan = Some(loan);
+                    let error_graphs =
+                        self.compute_error_graphs(vec![(error_index, (err_point, error))]);
+                    for (_, (_, _, error_graph_opt)) in error_graphs {
+                        if let Some(error_graph) = error_graph_opt {
+                            explanation_notes.push((rustc_index, self.write_error_graph(
+                                &mut error_entry, &error_graph, err_point, error,
+                                &mut diagnostics)));
+                        }
+                    }
+                }
+                error_entries.push(error_entry);
+            }
+        }
+
+        if !self.options.json_diagnostics {
+            self.attach_explanations_to_rustc_errors(&explanation_notes);
+        }
+
+        (error_entries, diagnostics)
+    }
+
+    /// Writes the files for a use of a moved value that is explained by the MoveErrorGraph to the
+    /// directory of the error (which must exist), i.e. the graph as dot graph and as JSON dump, and
+    /// the explanation in prose (if requested), and sets their paths in the entry of the error.
+    /// Files that cannot be written are only reported to the log, their paths are not set.
+    fn write_move_error_graph(&self, error_entry: &mut output::ErrorEntry,
+                              move_error_graph: &MoveErrorGraph, error_dir: &Path) {
+        let move_error_graph_path = error_dir.join("move_error_graph.dot");
+        match self.print_move_error_graph(move_error_graph,
+                                          &self.output_layout.resolve(&move_error_graph_path)) {
+            Ok(()) => error_entry.dot_file = Some(move_error_graph_path),
+            Err(err) => warn!("Unable to write the move error graph {:?}: {}",
+                              move_error_graph_path, err),
+        }
+
+        let move_error_graph_path_json = error_dir.join("move_error_graph.json");
+        let result = File::create(self.output_layout.resolve(&move_error_graph_path_json))
+            .and_then(|out_file| serde_json::to_writer_pretty(out_file, move_error_graph)
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, err)));
+        match result {
+            Ok(()) => error_entry.json_file = Some(move_error_graph_path_json),
+            Err(err) => warn!("Unable to write the move error graph {:?}: {}",
+                              move_error_graph_path_json, err),
+        }
+
+        error_entry.explanation_file = self.write_explanation(
+            &explain::explain_move_error_graph(move_error_graph), error_dir);
+    }
+
+    /// Writes the files for an error that is explained by the (improved) error graph to the
+    /// directory of the error, i.e. the graph as dot graph and as JSON dump, and the explanation in
+    /// prose (if requested), and sets their paths in the entry of the error. If the directory
+    /// cannot be created, this is only reported to the log and no files are written. Adds the
+    /// graph as JSON diagnostic to the given ones as well if requested, unless the error is only
+    /// reported by Polonius.
+    /// Returns the notes that explain the error, for attaching them to the error of rustc.
+    fn write_error_graph(&self, error_entry: &mut output::ErrorEntry,
+                         error_graph: &EnrichedErrorGraph, err_point: PointIndex,
+                         error: BorrowckError, diagnostics: &mut Vec<Diagnostic>) -> Vec<String> {
+        let function_name = self.def_path.to_filename_friendly_no_crate();
+        let error_dir = self.output_layout.error_dir(&self.crate_name, &function_name,
+                                                     error_entry.index);
+        match self.output_layout.create_dir(&error_dir) {
+            Ok(()) => self.write_error_graph_files(error_entry, error_graph, &error_dir),
+            Err(err) => warn!("Unable to create the directory {:?} for the error at {:?}, its \
+                               files are not written: {}", error_dir, err_point, err),
+        }
+
+        if self.options.json_diagnostics &&
+                error_entry.status != output::ErrorStatus::PoloniusOnly {
+            diagnostics.push(self.create_error_diagnostic(error_graph, err_point, error));
+        }
+
+        self.create_explanation_notes(error_graph, err_point, error)
+    }
+
+    /// Writes the files of an error that is explained by the (improved) error graph to the
+    /// directory of the error (which must exist), and sets their paths in the entry of the error,
+    /// see write_error_graph(). Files that cannot be written are only reported to the log, their
+    /// paths are not set.
+    fn write_error_graph_files(&self, error_entry: &mut output::ErrorEntry,
+                               error_graph: &EnrichedErrorGraph, error_dir: &Path) {
+        // These lines can be uncommented for debugging purposes. (Note that the unimproved
+        // graph is not available any more at this point.)
+//        let error_graph_path = error_dir.join("error_graph.dot");
+//
+//        self.print_outlive_error_graph(&error_graph, &self.output_layout.resolve(&error_graph_path));
+
+        let error_graph_path_improved = error_dir.join("error_graph_improved.dot");
+        match self.print_outlive_error_graph(
+                error_graph, &self.output_layout.resolve(&error_graph_path_improved)) {
+            Ok(()) => error_entry.dot_file = Some(error_graph_path_improved),
+            Err(err) => warn!("Unable to write the error graph {:?}: {}",
+                              error_graph_path_improved, err),
+        }
+
+        let error_graph_path_json = error_dir.join("error_graph.json");
+        match self.dump_outlive_error_graph_as_json(
+                error_graph, &self.output_layout.resolve(&error_graph_path_json)) {
+            Ok(()) => error_entry.json_file = Some(error_graph_path_json),
+            Err(err) => warn!("Unable to write the error graph {:?}: {}",
+                              error_graph_path_json, err),
+        }
+        error_entry.explanation_file = self.write_explanation(
+            &explain::explain_error_graph(error_graph), error_dir);
+    }
+
+    /// Gives the errors that the location insensitive algorithm of Polonius finds for the function,
+    /// but the algorithm that is used for the explanations does not. These are (typically) the
+    /// errors that the borrow checker of rustc (NLL) reports, but Polonius accepts, since a
+    /// constraint only holds on some paths through the function (e.g. for a conditional return of
+    /// a borrow). The result is sorted by the points of the errors.
+    fn compute_location_insensitive_errors(&self) -> Vec<(PointIndex, Loan)> {
+        let insensitive_output = Output::compute(&self.borrowck_in_facts,
+                                                 Algorithm::LocationInsensitive, false);
+        let mut insensitive_errors: Vec<(PointIndex, Loan)> = insensitive_output.errors.iter()
+            .flat_map(|(&point, loans)| loans.iter().map(move |&loan| (point, loan)))
+            .filter(|&(point, loan)| !self.is_polonius_error(point, loan))
+            .collect();
+        insensitive_errors.sort();
+        insensitive_errors.dedup();
+        debug!("location insensitive errors: {:?}", insensitive_errors);
+        insensitive_errors
+    }
+
+    /// Checks if the loan is invalidated at the point according to the output of Polonius that is
+    /// explained, i.e. if the error is not only found location insensitively.
+    fn is_polonius_error(&self, point: PointIndex, loan: Loan) -> bool {
+        self.borrowck_out_facts.errors.get(&point).map_or(false, |loans| loans.contains(&loan))
+    }
+
+    /// Sets the status of the entry of the error at the point, and the code and message of the
+    /// matching error of rustc, if there is one. (See match_rustc_errors(), whose result must be
+    /// given.) Returns the index of the matching error of rustc.
+    fn mark_error_status(&self, error_entry: &mut output::ErrorEntry,
+                         rustc_errors: &[borrowck_errors::HeldErrorSummary],
+                         rustc_matches: &HashMap<usize, usize>, err_point: PointIndex)
+            -> Option<usize> {
+        match rustc_matches.get(&error_entry.index) {
+            Some(&rustc_index) => {
+                let rustc_error = &rustc_errors[rustc_index];
+                error_entry.status = output::ErrorStatus::Explained;
+                error_entry.rustc_code = rustc_error.code.clone();
+                error_entry.rustc_message = Some(rustc_error.message.clone());
+                Some(rustc_index)
+            },
+            None => {
+                info!("The error at {:?} is not reported by rustc, its explanation is not shown.",
+                      err_point);
+                error_entry.status = output::ErrorStatus::PoloniusOnly;
+                None
+            },
+        }
+    }
+
+    /// Matches the errors of Polonius (given as by distinct_errors()) with the errors of rustc,
+    /// such that every error is matched with at most one error of the other. An error of rustc can
+    /// only match an error whose point is on the same line as one of its spans (see
+    /// match_distance()). Among these, the pairs where the code of the error of rustc is the one of
+    /// the classified error (see classify_error()) are preferred over the ones where either code is
+    /// unknown, and these over the ones where the codes differ. Then the pairs with the smaller
+    /// match distance are preferred. The pairs are matched greedily in this order.
+    /// Returns the index of the matching error of rustc for every error that has one, keyed by the
+    /// index of the error.
+    fn match_rustc_errors(&self, rustc_errors: &[borrowck_errors::HeldErrorSummary],
+                          errors: &[(PointIndex, BorrowckError)]) -> HashMap<usize, usize> {
+        let mut candidates = Vec::new();
+        for (error_index, &(err_point, error)) in errors.iter().enumerate() {
+            let point_loc = self.get_loc_for_point(err_point);
+            let error_code = self.classify_error(err_point, error);
+            for (rustc_index, rustc_error) in rustc_errors.iter().enumerate() {
+                if let Some(distance) = self.match_distance(&rustc_error.span, &point_loc) {
+                    let code_rank = code_match_rank(error_code, rustc_error.code.as_deref());
+                    candidates.push(((code_rank, distance), error_index, rustc_index));
+                }
+            }
+        }
+
+        let matches = match_greedily(candidates);
+        debug!("errors of rustc matching the errors of Polonius: {:?}", matches);
+        matches
+    }
+
+    /// Tells how well an error of rustc (given by its spans) matches a point (given by its
+    /// location in the source code): None if no span of the error is on the same line as the
+    /// point, otherwise the index of the best span (the primary spans come first, hence the index
+    /// tells how well a point on the same line matches the error) and the distance of its column
+    /// to the one of the point. Smaller values are better matches.
+    fn match_distance(&self, multi_span: &MultiSpan, point_loc: &Loc) -> Option<(usize, usize)> {
+        let source_map = self.tcx.sess.source_map();
+        let error_spans: Vec<Span> = multi_span.primary_spans().iter().cloned()
+            .chain(multi_span.span_labels().iter().filter(|label| !label.is_primary)
+                .map(|label| label.span))
+            .collect();
+        error_spans.iter().enumerate().filter_map(|(span_index, span)| {
+            let span_loc = source_map.lookup_char_pos(span.lo());
+            if span_loc.file.name == point_loc.file.name && span_loc.line == point_loc.line {
+                let column_distance = cmp::max(span_loc.col.0, point_loc.col.0) -
+                    cmp::min(span_loc.col.0, point_loc.col.0);
+                Some((span_index, column_distance))
+            } else {
+                None
+            }
+        }).min()
+    }
+
+    /// Gives the location in the source code of the start of the span of the point.
+    fn get_loc_for_point(&self, pt: PointIndex) -> Loc {
+        self.tcx.sess.source_map().lookup_char_pos(self.get_span_for_point(pt).lo())
+    }
+
+    /// Writes the explanation of an error in prose to the directory of the error (given relative
+    /// to the root of the output directory), in the format that was given in the options. Returns
+    /// the path of the written file (relative to the root), None if no format was given or the
+    /// file cannot be written. (The latter is only reported to the log.)
+    fn write_explanation(&self, explanation: &explain::Explanation, error_dir: &Path)
+            -> Option<PathBuf> {
+        let format = self.options.explanation_format?;
+        let explanation_path = error_dir.join(format!("explanation.{}", format.file_extension()));
+        match fs::write(self.output_layout.resolve(&explanation_path), explanation.render(format)) {
+            Ok(()) => Some(explanation_path),
+            Err(err) => {
+                warn!("Unable to write the explanation {:?}: {}", explanation_path, err);
+                None
+            },
+        }
+    }
+
+    /// Computes the MoveErrorGraph that explains the use of the move path at the error point,
+    /// based on the path in the control flow graph that the MoveErrorPathFinder finds from the
+    /// move to the use. None if no such path is found.
+    fn compute_move_error_graph(&self, error_point: PointIndex, moved_path: MovePath)
+            -> Option<MoveErrorGraph> {
+        let path_finder = MoveErrorPathFinder::new(&self.borrowck_in_facts, error_point,
+                                                   moved_path);
+        let path = path_finder.compute_move_path()?;
+        let move_point = *path.first()?;
+
+        let variable_name = path_finder.root_variable()
+            .and_then(|variable| {
+                let local_index: usize = variable.into();
+                regions::find_name_of_local(self.mir, mir::Local::from_usize(local_index))
+            })
+            .unwrap_or_else(|| format!("{:?}", moved_path));
+
+        let mut lines_on_path: Vec<(usize, String)> = Vec::new();
+        for &point in path.iter() {
+            let line = self.get_line_for_point(point);
+            if lines_on_path.last().map_or(true, |&(line_number, _)| line_number != line.0) {
+                lines_on_path.push(line);
+            }
+        }
+        let move_line = self.get_line_for_point(move_point);
+        let use_line = self.get_line_for_point(error_point);
+        // the path goes back to an earlier line only if it follows the back edge of a loop.
+        let in_loop = lines_on_path.windows(2).any(|lines| lines[1].0 < lines[0].0);
+
+        Some(MoveErrorGraph {
+            function_name: self.def_path.to_filename_friendly_no_crate(),
+            error_code: RustcErrorCode::E0382,
+            moved_path,
+            variable_name,
+            move_line,
+            move_kind: self.describe_move(move_point),
+            use_line,
+            lines_on_path,
+            in_loop,
+            move_span: self.get_span_for_point(move_point),
+            use_span: self.get_span_for_point(error_point),
+        })
+    }
+
+    /// Describes how the value is moved at the given point, i.e. "moved into the call of `f`" if
+    /// the point is a call of the function f (which takes the moved value as argument), and just
+    /// "moved" otherwise.
+    fn describe_move(&self, move_point: PointIndex) -> String {
+        let location = self.interner.get_point(move_point).location;
+        let block_data = &self.mir[location.block];
+        if location.statement_index != block_data.statements.len() {
+            return "moved".to_string();
+        }
+        match block_data.terminator().kind {
+            mir::TerminatorKind::Call { ref func, .. } => match func.const_fn_def() {
+                Some((def_id, _)) =>
+                    format!("moved into the call of `{}`", self.tcx.def_path_str(def_id)),
+                None => "moved into a function call".to_string(),
+            },
+            _ => "moved".to_string(),
+        }
+    }
+
+    /// Writes the graph of a move error in dot format to the given file: The lines on the path from
+    /// the move to the use are the nodes, linked in the order of the path. The line of the move and
+    /// the one of the use are highlighted.
+    fn print_move_error_graph(&self, move_error_graph: &MoveErrorGraph, graph_out_path: &Path)
+            -> io::Result<()> {
+        let mut graph_file = File::create(graph_out_path)?;
+
+        writeln!(graph_file, "digraph G {{")?;
+        for (index, (line_number, line_str)) in move_error_graph.lines_on_path.iter().enumerate() {
+            let label = if index == 0 {
+                format!("{} at line {}", move_error_graph.move_kind, line_number)
+            } else if index == move_error_graph.lines_on_path.len() - 1 {
+                format!("used at line {}", line_number)
+            } else {
+                format!("line {}", line_number)
+            };
+            let color = if index == 0 || index == move_error_graph.lines_on_path.len() - 1 {
+                "red"
+            } else {
+                "black"
+            };
+            writeln!(graph_file, "node{} [shape=box, color={}, label=<<table border=\"0\">\
+                     <tr><td>{}</td></tr><tr><td>{}: {}</td></tr></table>>];", index, color,
+                     label.replace("&", "&amp;").replace("<", "&lt;").replace(">", "&gt;"),
+                     line_number,
+                     line_str.trim().replace("&", "&amp;").replace("<", "&lt;")
+                         .replace(">", "&gt;"))?;
+            if index > 0 {
+                writeln!(graph_file, "node{} -> node{};", index - 1, index)?;
+            }
+        }
+        writeln!(graph_file, "}}")?;
         Ok(())
     }
 
-    fn print_error(&mut self) {
-        let mut path_to_explain_last_error: Vec<Region> = Vec::default();
+    /// Creates a diagnostic in the JSON format of rustc that explains a move error. It is a note
+    /// with the code of the error of rustc that it explains. The primary span is the
+    /// use of the moved value, the move is added as labelled secondary span, and every line on the
+    /// path in between as a note.
+    fn create_move_error_diagnostic(&self, move_error_graph: &MoveErrorGraph) -> Diagnostic {
+        let source_map = self.tcx.sess.source_map();
+        let mut diagnostic = Diagnostic::new(
+            "note",
+            format!("explanation of the use of moved value: `{}`", move_error_graph.variable_name),
+            Some(move_error_graph.error_code.as_str()));
+        diagnostic.spans.extend(DiagnosticSpan::from_span(
+            source_map, move_error_graph.use_span, true,
+            Some("the value is used here after the move".to_string())));
+        diagnostic.spans.extend(DiagnosticSpan::from_span(
+            source_map, move_error_graph.move_span, false,
+            Some(format!("the value is {} here", move_error_graph.move_kind))));
+        for note in move_error_graph.explanation_notes() {
+            diagnostic.add_child("note", note, None);
+        }
+        diagnostic.render();
+        diagnostic
+    }
+
+    /// Gives the notes that explain an error, one for every constraint on the path of the
+    /// (improved) graph of the error, in the order of the path. Every note reads like "`x` may
+    /// point to the data of `y` because of line N". They are followed by a note for every capture
+    /// of a variable by a closure that is on the path, see CaptureInfo::describe(), by a note that
+    /// tells which later use keeps the borrow live (see LivenessTrace::describe()), and by a note
+    /// that explains the error in terms of the signature of the function, if this is possible (see
+    /// create_signature_note()).
+    fn create_explanation_notes(&self, error_graph: &EnrichedErrorGraph, error_point: PointIndex,
+                                error: BorrowckError) -> Vec<String> {
+        let mut capture_notes: Vec<String> = Vec::new();
+        for region in error_graph.regions_in_path_order() {
+            if let Some(capture_info) = error_graph.captures_for_regions.get(&region) {
+                let capture_note = capture_info.describe();
+                if !capture_notes.contains(&capture_note) {
+                    capture_notes.push(capture_note);
+                }
+            }
+        }
+        error_graph.edges_in_path_order().iter().map(|&(region1, region2)| {
+            let note = format!("{} may point to the data of {}",
+                               error_graph.describe_region(region2),
+                               error_graph.describe_region(region1));
+            match error_graph.lines_for_edges.get(&(region1, region2)) {
+                Some(&(line_number, _)) if line_number != usize::MAX =>
+                    format!("{} because of line {}", note, line_number),
+                _ => note,
+            }
+        }).chain(capture_notes)
+            .chain(error_graph.liveness_trace.iter().map(LivenessTrace::describe))
+            .chain(self.create_signature_note(error_graph, error_point, error))
+            .chain(error_graph.describe_constraint_cut())
+            .chain(error_graph.describe_polonius_acceptance())
+            .collect()
+    }
+
+    /// Gives a note that explains an error in terms of the signature of the function, if the path
+    /// of its graph ends at a universal region (i.e. at a lifetime of the signature, or 'static):
+    /// For an invalidated loan, it tells that the lifetime lasts until after the function returns,
+    /// which a borrow of data that is invalidated inside the function cannot satisfy. For a required
+    /// subset, it tells that the signature does not declare the relation of the two lifetimes.
+    fn create_signature_note(&self, error_graph: &EnrichedErrorGraph, error_point: PointIndex,
+                             error: BorrowckError) -> Option<String> {
+        match error {
+            BorrowckError::LoanInvalidated(error_loan) => {
+                let exit_region = *error_graph.regions_in_path_order().last()?;
+                let universal_region = self.universal_regions.get(&exit_region)?;
+                let loan_line = self.get_line_for_point(self.find_loan_point(error_loan)?).0;
+                let error_line = self.get_line_for_point(error_point).0;
+                Some(format!("{} is part of the signature of the function, so it lasts until after \
+                              the function returns. The borrow at line {} has to be valid for it, \
+                              but the borrowed data is invalidated at line {}, inside the function",
+                             universal_region.description, loan_line, error_line))
+            },
+            BorrowckError::SubsetRequired(region1, region2) => {
+                let universal_region1 = self.universal_regions.get(&region1)?;
+                let universal_region2 = self.universal_regions.get(&region2)?;
+                let note = format!("the signature of the function does not require that {} \
+                                    outlives {}", universal_region1.description,
+                                   universal_region2.description);
+                match (&universal_region1.name, &universal_region2.name) {
+                    (Some(name1), Some(name2)) =>
+                        Some(format!("{}, consider adding the bound `{}: {}`", note, name1, name2)),
+                    _ => Some(note),
+                }
+            },
+            BorrowckError::UseOfMoved(_) => None,
+        }
+    }
 
-        for (point, loans) in self.borrowck_out_facts.errors.iter() {
-            let err_point_ind = point;
-            let err_loans = loans;
+    /// Gives the code that rustc uses for the error, see error_codes::classify_loan_error(). Uses
+    /// of moved values are always E0382, required subsets have no code.
+    fn classify_error(&self, error_point: PointIndex, error: BorrowckError)
+            -> Option<RustcErrorCode> {
+        match error {
+            BorrowckError::LoanInvalidated(loan) => {
+                let loan_location = self.interner.get_point(self.find_loan_point(loan)?).location;
+                let error_location = self.interner.get_point(error_point).location;
+                let error_code = error_codes::classify_loan_error(self.mir, loan_location,
+                                                                  error_location);
+                debug!("error code of {:?} at {:?}: {:?}", loan, error_point, error_code);
+                error_code
+            },
+            BorrowckError::SubsetRequired(..) => None,
+            BorrowckError::UseOfMoved(_) => Some(RustcErrorCode::E0382),
+        }
+    }
 
-            debug!("-------------------------------------------------------------------------------------------------------------");
-            debug!("Start searching the path to the error, new version that searches (default) outlives (from borrowck_in_facts):");
-            let mut error_path_finder = ErrorPathFinder::new(&self.borrowck_in_facts,
-                                                             &self.borrowck_out_facts,
-                                                             (*err_point_ind, err_loans.clone()),
-                                                             &self.borrowck_in_facts.outlives);
-            let new_path = error_path_finder.compute_error_path();
-            if ! new_path.is_empty() {
-                // if the newly found path is non-empty, take it. This prevents that a path that was
-                // found before is overwritten by an emtpy (error result)
-                path_to_explain_last_error = new_path;
+    /// Gives the name of the variable that the loan borrows (from), if the borrow is found at the
+    /// point where the loan is created and the variable has a name.
+    fn find_borrowed_name(&self, loan: Loan) -> Option<String> {
+        let loan_location = self.interner.get_point(self.find_loan_point(loan)?).location;
+        let (_, borrowed_place) = error_codes::find_borrow(self.mir, loan_location)?;
+        regions::find_name_of_local(self.mir, borrowed_place.local)
+    }
+
+    /// Gives the point where the loan is created, as given by the loan_issued_at input facts.
+    fn find_loan_point(&self, loan: Loan) -> Option<PointIndex> {
+        self.borrowck_in_facts.loan_issued_at.iter()
+            .find(|&&(_, l, _)| l == loan)
+            .map(|&(_, _, point)| point)
+    }
+
+    /// Computes the LivenessTrace that tells why the invalidated loan is still live at the error
+    /// point, see LivenessTraceFinder. Returns it together with the point of the use (or drop) that
+    /// keeps the loan live, None if no such use is found.
+    fn compute_liveness_trace(&self, error_point: PointIndex, loan: Loan)
+            -> Option<(PointIndex, LivenessTrace)> {
+        let trace_finder = LivenessTraceFinder::new(&self.borrowck_in_facts,
+                                                    &self.borrowck_out_facts,
+                                                    error_point,
+                                                    loan);
+        let live_use = trace_finder.compute_live_use()?;
+        let use_point = *live_use.points.last()?;
+
+        let local_index: usize = live_use.variable.into();
+        let variable_name = regions::find_name_of_local(self.mir,
+                                                        mir::Local::from_usize(local_index))
+            .map_or_else(|| "a temporary".to_string(), |name| format!("`{}`", name));
+        // the trace may pass through the expansion of a macro (e.g. the formatting of println!),
+        // whose spans lie in a source file that is not available, hence the lines of the call
+        // site are taken.
+        let line_for_point = |point| {
+            self.get_line_for_span(self.get_span_for_point(point).source_callsite())
+        };
+        let mut lines_on_path: Vec<(usize, String)> = Vec::new();
+        for &point in live_use.points.iter() {
+            let line = line_for_point(point);
+            if lines_on_path.last().map_or(true, |&(line_number, _)| line_number != line.0) {
+                lines_on_path.push(line);
             }
         }
+        let liveness_trace = LivenessTrace {
+            region: live_use.region,
+            variable_name,
+            is_drop: live_use.is_drop,
+            error_line: line_for_point(error_point),
+            use_line: line_for_point(use_point),
+            lines_on_path,
+        };
+        Some((use_point, liveness_trace))
+    }
+
+    /// Creates the ErrorSite for a point, with the given label.
+    fn create_error_site(&self, point: PointIndex, label: String) -> ErrorSite {
+        let source_map = self.tcx.sess.source_map();
+        let span = self.get_span_for_point(point);
+        ErrorSite {
+            point,
+            line: self.get_line_for_point(point),
+            snippet: source_map.span_to_snippet(span).unwrap_or_default(),
+            span: DiagnosticSpan::from_span(source_map, span, false, Some(label.clone())),
+            label,
+        }
+    }
 
-        if ! path_to_explain_last_error.is_empty() {
-            let mut graph_to_explain_last_error: FxHashMap<(Region, Region),
+    /// Creates the three ErrorSites of an invalidated loan (see EnrichedErrorGraph::borrow_point),
+    /// all of them are None for other errors. The later use is given by the use that keeps the
+    /// loan live, as computed by compute_liveness_trace().
+    fn create_error_sites(&self, error_point: PointIndex, error: BorrowckError,
+                          later_use: Option<&(PointIndex, LivenessTrace)>)
+            -> (Option<ErrorSite>, Option<ErrorSite>, Option<ErrorSite>) {
+        let loan = match error {
+            BorrowckError::LoanInvalidated(loan) => loan,
+            _ => return (None, None, None),
+        };
+        let borrowed_name = self.find_borrowed_name(loan);
+        let borrow_point = self.find_loan_point(loan).map(|loan_point| {
+            let label = match borrowed_name {
+                Some(ref name) => format!("`{}` is borrowed here", name),
+                None => "the borrow is created here".to_string(),
+            };
+            self.create_error_site(loan_point, label)
+        });
+        let invalidation_label = match borrowed_name {
+            Some(ref name) => format!("`{}` is invalidated here, while it is still borrowed", name),
+            None => "the borrow is invalidated here, while it is still live".to_string(),
+        };
+        let invalidation_point = Some(self.create_error_site(error_point, invalidation_label));
+        let later_use_point = later_use.map(|&(use_point, ref liveness_trace)| {
+            let label = if liveness_trace.is_drop {
+                format!("the borrow is later used when {} is dropped",
+                        liveness_trace.variable_name)
+            } else {
+                format!("the borrow is later used by {}", liveness_trace.variable_name)
+            };
+            self.create_error_site(use_point, label)
+        });
+        (borrow_point, invalidation_point, later_use_point)
+    }
+
+    /// Adds the notes that explain the errors of the function to the errors that the borrow checker
+    /// of rustc reported for the function. The notes are given together with the index of the
+    /// error of rustc (in the order of borrowck_errors::summarize_held_errors()) that matches the
+    /// explained error, see match_rustc_errors(). Since the matching is one-to-one, every rustc
+    /// error gets the notes of at most one error.
+    fn attach_explanations_to_rustc_errors(&self, explanation_notes: &[(usize, Vec<String>)]) {
+        borrowck_errors::add_notes_to_held_errors(self.def_id, |rustc_index| {
+            explanation_notes.iter()
+                .find(|&&(notes_index, _)| notes_index == rustc_index)
+                .map_or_else(Vec::new, |(_, notes)| notes.clone())
+        });
+    }
+
+    /// Gives all distinct errors that Polonius reported for the function. These are first all pairs
+    /// of an error point and a loan that is invalidated at this point, then all pairs of universal
+    /// regions where the first one has to outlive the second one (Polonius reports the latter at
+    /// every point where the requirement holds, only the first of these points is kept), and last
+    /// all pairs of a point and a move path that is used there while it may be moved out. All are
+    /// sorted in ascending order of their points, hence the order of the result (and thereby the
+    /// index of every error) is deterministic.
+    fn distinct_errors(&self) -> Vec<(PointIndex, BorrowckError)> {
+        let mut errors: Vec<(PointIndex, BorrowckError)> = self.borrowck_out_facts.errors.iter().flat_map(
+            |(&point, loans)| loans.iter().map(move |&loan| (point, BorrowckError::LoanInvalidated(loan)))
+        ).collect();
+        errors.sort();
+        errors.dedup();
+
+        let mut subset_errors: Vec<(PointIndex, BorrowckError)> = Vec::new();
+        let mut all_subset_errors: Vec<(PointIndex, (Region, Region))> =
+            self.borrowck_out_facts.subset_errors.iter().flat_map(
+                |(&point, subsets)| subsets.iter().map(move |&subset| (point, subset))
+            ).collect();
+        all_subset_errors.sort();
+        for (point, (region1, region2)) in all_subset_errors {
+            let error = BorrowckError::SubsetRequired(region1, region2);
+            if subset_errors.iter().all(|&(_, other_error)| other_error != error) {
+                subset_errors.push((point, error));
+            }
+        }
+
+        errors.extend(subset_errors);
+
+        let mut move_errors: Vec<(PointIndex, BorrowckError)> =
+            self.borrowck_out_facts.move_errors.iter().flat_map(
+                |(&point, paths)| paths.iter().map(move |&path| (point, BorrowckError::UseOfMoved(path)))
+            ).collect();
+        move_errors.sort();
+        move_errors.dedup();
+        errors.extend(move_errors);
+        errors
+    }
+
+    /// Gives the distance of the source code of a point to the given position, as pair of the
+    /// distance in lines and the distance in columns. (The latter is 0 if the position has no
+    /// column.) Comparing these pairs gives the point that is closest to the position.
+    fn distance_to_position(&self, pt: PointIndex, position: &SourcePosition) -> (usize, usize) {
+        let point_loc = self.tcx.sess.source_map().lookup_char_pos(self.get_span_for_point(pt).lo());
+        let line_distance = cmp::max(point_loc.line, position.line) -
+            cmp::min(point_loc.line, position.line);
+        // lines and columns of the position are counted from 1, the ones of rustc's columns from 0.
+        let column_distance = match position.column {
+            Some(column) => {
+                let point_column = point_loc.col.0 + 1;
+                cmp::max(point_column, column) - cmp::min(point_column, column)
+            },
+            None => 0,
+        };
+        (line_distance, column_distance)
+    }
+
+    /// Computes an (already improved) EnrichedErrorGraph for each of the given errors. They must be
+    /// given together with their index, as given by distinct_errors().
+    /// The result contains one entry for every error, giving its index, the point and the kind of
+    /// the error and the graph that explains it. If no path is found for an error (by the
+    /// ErrorPathFinder for invalidated loans, or between the two universal regions for subset
+    /// errors), the graph is None. (This is also reported to the log.)
+    fn compute_error_graphs(&self, errors: Vec<(usize, (PointIndex, BorrowckError))>)
+            -> Vec<(usize, (PointIndex, BorrowckError, Option<EnrichedErrorGraph<'tcx>>))> {
+        let mut error_graphs = Vec::new();
+
+        for (error_index, (err_point, error)) in errors {
+            debug!("-------------------------------------------------------------------------------------------------------------");
+            debug!("Start searching the path to the error at {:?} for {:?}, new version that \
+                    searches (default) outlives (from borrowck_in_facts):", err_point, error);
+            let location_sensitive_path = match error {
+                BorrowckError::LoanInvalidated(err_loan)
+                        if self.options.path_search == PathSearch::LocationSensitive =>
+                    self.find_location_sensitive_path(err_point, err_loan),
+                _ => None,
+            };
+            let mut ranked_paths = match location_sensitive_path {
+                Some((ref path, _)) => vec![(path.len() - 1, path.clone())],
+                None => self.find_error_paths(err_point, error),
+            };
+            if ranked_paths.is_empty() {
+                info!("No explanation found for the error at {:?} for {:?}.", err_point, error);
+                error_graphs.push((error_index, (err_point, error, None)));
+                continue;
+            }
+            let (path_cost, mut path_to_explain_error) = ranked_paths.remove(0);
+
+            let mut graph_to_explain_error: FxHashMap<(Region, Region),
                 Vec<PointIndex>> = FxHashMap::default();
-            let mut prev_region = path_to_explain_last_error.pop().unwrap();
-            path_to_explain_last_error.iter().rev().for_each(|&r| {
-                let mut points_of_edge: Vec<_> =
-                    self.borrowck_in_facts.outlives.iter().filter(|&(r1, r2, _)|
+            let mut path_edges: Vec<(Region, Region)> = Vec::new();
+            let mut prev_region = path_to_explain_error.pop().unwrap();
+            path_to_explain_error.iter().rev().for_each(|&r| {
+                let mut points_of_edge: Vec<_> = match location_sensitive_path {
+                    // only the constraint that causes the step on the path is relevant.
+                    Some((_, ref edge_points)) => edge_points[&(prev_region, r)].clone(),
+                    None => self.borrowck_in_facts.subset_base.iter().filter(|&(r1, r2, _)|
                         *r1 == prev_region && *r2 == r
-                    ).map(|&(_, _, p)| p).collect();
+                    ).map(|&(_, _, p)| p).collect(),
+                };
                 points_of_edge.dedup();
-                graph_to_explain_last_error.insert((prev_region, r), points_of_edge);
+                graph_to_explain_error.insert((prev_region, r), points_of_edge);
+                path_edges.push((prev_region, r));
                 prev_region = r;
             }
             );
 
-            debug!("borrowck_in_facts.outlives: {:?}", self.borrowck_in_facts.outlives);
+            debug!("borrowck_in_facts.subset_base: {:?}", self.borrowck_in_facts.subset_base);
 
-            debug!("graph_to_explain_last_error: {:?}", graph_to_explain_last_error);
+            debug!("graph_to_explain_error: {:?}", graph_to_explain_error);
 
             debug!("region_to_local_map: {:?}", self.region_to_local_map);
 
-            let mut enriched_graph_to_explain_last_error =
-                self.create_enriched_graph(&graph_to_explain_last_error, &self.borrowck_in_facts.borrow_region);
+            let mut enriched_graph_to_explain_error =
+                self.create_enriched_graph(&graph_to_explain_error,
+                                           &self.borrowck_in_facts.loan_issued_at,
+                                           err_point,
+                                           error);
+
+            if self.options.constraint_cut {
+                let constraint_cut = self.compute_constraint_cut(err_point, error, &path_edges);
+                if let Some(ref constraint_cut) = constraint_cut {
+                    let mut cut_lines: Vec<(usize, String)> = constraint_cut.edges.iter()
+                        .filter_map(|edge|
+                            enriched_graph_to_explain_error.lines_for_edges.get(edge).cloned()
+                        )
+                        .filter(|&(line_number, _)| line_number != usize::MAX)
+                        .collect();
+                    cut_lines.sort();
+                    cut_lines.dedup();
+                    enriched_graph_to_explain_error.cut_lines = cut_lines;
+                }
+                enriched_graph_to_explain_error.constraint_cut = constraint_cut;
+            }
+
+            enriched_graph_to_explain_error.path_cost = path_cost;
+            enriched_graph_to_explain_error.alternative_paths = ranked_paths.into_iter()
+                .enumerate()
+                .map(|(index, (cost, path))| self.create_alternative_path(index + 2, cost, path))
+                .collect();
 
-            // These lines can be uncommented for debugging purposes
-//            let error_graph_path = PathBuf::from("nll-facts")
-//                .join(self.def_path.to_filename_friendly_no_crate())
-//                .join("error_graph.dot");
-//
-//            self.print_outlive_error_graph(&enriched_graph_to_explain_last_error, &error_graph_path);
+            enriched_graph_to_explain_error.improve_graph();
 
-            let error_graph_path_improved = PathBuf::from("nll-facts")
-                .join(self.def_path.to_filename_friendly_no_crate())
-                .join("error_graph_improved.dot");
+            error_graphs.push((error_index,
+                               (err_point, error, Some(enriched_graph_to_explain_error))));
+        }
 
-            enriched_graph_to_explain_last_error.improve_graph();
+        error_graphs
+    }
 
-            self.print_outlive_error_graph(&enriched_graph_to_explain_last_error, &error_graph_path_improved);
+    /// Searches the paths in the outlives relation that explain an error, using the strategy that
+    /// was given in the options (see PathSearch). The path of an invalidated loan leads from the
+    /// region of the loan to a region that contains the loan and is live at the error point (see
+    /// ErrorPathFinder), the path of a required subset leads from the region that must outlive
+    /// the other one to the other one. (Both are searched backwards.)
+    /// Returns the path that explains the error first, followed by the number of alternative paths
+    /// that was requested in the options, if there are that many. They are ranked by their cost
+    /// (see find_shortest_paths_backwards()), the cost of every path is given with it. The result
+    /// is empty if no path is found.
+    fn find_error_paths(&self, error_point: PointIndex, error: BorrowckError)
+            -> Vec<(usize, Vec<Region>)> {
+        let outlives = &self.borrowck_in_facts.subset_base;
+        let error_path_finder;
+        let (start, is_end): (Region, Box<dyn Fn(Region) -> bool + '_>) = match error {
+            BorrowckError::LoanInvalidated(err_loan) => {
+                error_path_finder = ErrorPathFinder::new(&self.borrowck_in_facts,
+                                                         &self.borrowck_out_facts,
+                                                         error_point,
+                                                         err_loan);
+                let error_region = if self.is_polonius_error(error_point, err_loan) {
+                    error_path_finder.find_error_region()
+                } else {
+                    error_path_finder.find_location_insensitive_error_region()
+                };
+                match error_region {
+                    Some(error_region) => (error_region, Box::new(|region|
+                        error_path_finder.issues_error_loan(region)
+                    )),
+                    None => return Vec::new(),
+                }
+            },
+            BorrowckError::SubsetRequired(region1, region2) =>
+                (region2, Box::new(move |region| region == region1)),
+            // move errors are explained by compute_move_error_graph() instead.
+            BorrowckError::UseOfMoved(_) => return Vec::new(),
+        };
+        let error_line = self.get_line_for_point(error_point).0;
+        let edge_cost: Box<dyn Fn(Region, Region) -> usize + '_> = match self.options.path_search {
+            PathSearch::ShortestByLines => Box::new(move |region1, region2|
+                self.line_distance_cost(region1, region2, error_line)
+            ),
+            PathSearch::FirstFound | PathSearch::ShortestByEdges |
+            PathSearch::LocationSensitive => Box::new(|_, _| 1),
+        };
+        let path_count = 1 + self.options.alternative_paths;
+        let mut ranked_paths = find_shortest_paths_backwards(outlives, start, &is_end, &edge_cost,
+                                                             path_count);
+
+        if self.options.path_search == PathSearch::FirstFound {
+            let mut first_path = Vec::new();
+            if !find_path_backwards(outlives, start, &is_end, &mut first_path) {
+                warn!("No path to explain the error was found for the start point {:?} and the \
+                       error {:?}!", error_point, error);
+                return Vec::new();
+            }
+            ranked_paths.retain(|(_, path)| *path != first_path);
+            ranked_paths.truncate(path_count - 1);
+            ranked_paths.insert(0, (first_path.len() - 1, first_path));
+        } else if ranked_paths.is_empty() {
+            warn!("No path to explain the error was found for the start point {:?} and the error \
+                   {:?}!", error_point, error);
+        }
+        ranked_paths
+    }
 
-            // Write the JSON dump to a directory that does not depend on the method name. Note that
-            // this will not work well with multiple errors (in different methods), since the file
-            // would be overwriten for each error. However, for now we limit the tools scope to
-            // only deal with files with a single error for simplicity. This could be changed in the
-            // future, e.g. by allowing control to the user regarding the method that shall be
-            // handled.
-            let error_graph_path_json = PathBuf::from("nll-facts")
-                .join("error_graph.json");
+    /// Searches the path that explains an invalidated loan for PathSearch::LocationSensitive, see
+    /// LocationSensitivePathFinder. Alternative paths are not searched in this case.
+    /// Returns the path (backwards, like find_error_paths()) together with the point of the
+    /// constraint that causes every edge on it. Returns None if no path is found, or if the path
+    /// visits a region twice (at different points), as such a path cannot be drawn as a graph of
+    /// regions. The error is then explained by a location-insensitive path instead.
+    fn find_location_sensitive_path(&self, error_point: PointIndex, error_loan: Loan)
+            -> Option<(Vec<Region>, FxHashMap<(Region, Region), Vec<PointIndex>>)> {
+        let error_path_finder = ErrorPathFinder::new(&self.borrowck_in_facts,
+                                                     &self.borrowck_out_facts,
+                                                     error_point,
+                                                     error_loan);
+        let error_region = error_path_finder.find_error_region()?;
+        let path_finder = LocationSensitivePathFinder::new(&self.borrowck_in_facts,
+                                                           &self.borrowck_out_facts,
+                                                           error_point,
+                                                           error_loan,
+                                                           error_region);
+        let steps = path_finder.compute_path()?;
+
+        let mut path = vec![error_region];
+        let mut edge_points = FxHashMap::default();
+        for step in steps.iter().rev() {
+            if path.contains(&step.region1) {
+                warn!("The location-sensitive path for the error at {:?} visits {:?} twice, \
+                       falling back to a location-insensitive path.", error_point, step.region1);
+                return None;
+            }
+            path.push(step.region1);
+            edge_points.insert((step.region1, step.region2), vec![step.base_point]);
+        }
+        Some((path, edge_points))
+    }
 
-            self.dump_outlive_error_graph_as_json(&enriched_graph_to_explain_last_error, &error_graph_path_json);
+    /// The cost of an edge for PathSearch::ShortestByLines: 1 plus the distance (in lines) of the
+    /// line that causes the constraint to the line of the error. Like this, the path whose
+    /// constraints are closest to the error is preferred, and among paths with the same distance
+    /// the one with fewer constraints. Edges without a line have the cost 1.
+    fn line_distance_cost(&self, region1: Region, region2: Region, error_line: usize) -> usize {
+        let (line_number, _) = self.find_first_line_for_points(&self.points_of_edge(region1,
+                                                                                    region2));
+        if line_number == usize::MAX {
+            1
+        } else {
+            1 + cmp::max(line_number, error_line) - cmp::min(line_number, error_line)
+        }
+    }
+
+    /// Gives the points of all constraints of the outlives relation between the two regions.
+    fn points_of_edge(&self, region1: Region, region2: Region) -> Vec<PointIndex> {
+        self.borrowck_in_facts.subset_base.iter()
+            .filter(|&&(r1, r2, _)| r1 == region1 && r2 == region2)
+            .map(|&(_, _, point)| point)
+            .collect()
+    }
+
+    /// Creates the description of an alternative path (given backwards, as it is found by
+    /// find_error_paths()) for an EnrichedErrorGraph.
+    fn create_alternative_path(&self, rank: usize, cost: usize, mut path: Vec<Region>)
+            -> AlternativePath {
+        path.reverse();
+        let constraint_lines = path.windows(2)
+            .map(|regions|
+                self.find_first_line_for_points(&self.points_of_edge(regions[0], regions[1]))
+            )
+            .collect();
+        AlternativePath {
+            rank,
+            cost,
+            regions: path,
+            constraint_lines,
+        }
+    }
+
+    /// Computes the constraints among the edges of the path of an error whose removal makes the
+    /// error disappear, see ConstraintCutFinder. Polonius is run again without the candidates, the
+    /// error is considered as fixed if the loan is no longer invalidated at the point of the error,
+    /// or if the two universal regions are no longer required to be subsets of each other.
+    fn compute_constraint_cut(&self, error_point: PointIndex, error: BorrowckError,
+                              path_edges: &[(Region, Region)]) -> Option<ConstraintCut> {
+        let error_remains = |output: &facts::AllOutputFacts| match error {
+            BorrowckError::LoanInvalidated(loan) =>
+                output.errors.get(&error_point).map_or(false, |loans| loans.contains(&loan)),
+            BorrowckError::SubsetRequired(region1, region2) =>
+                output.subset_errors.values().any(|subsets| subsets.contains(&(region1, region2))),
+            BorrowckError::UseOfMoved(moved_path) =>
+                output.move_errors.get(&error_point)
+                    .map_or(false, |paths| paths.contains(&moved_path)),
+        };
+        // errors that are only found location insensitively must be checked the same way.
+        let algorithm = match error {
+            BorrowckError::LoanInvalidated(loan) if !self.is_polonius_error(error_point, loan) =>
+                Algorithm::LocationInsensitive,
+            _ => self.options.polonius_algorithm,
+        };
+        let cut_finder = ConstraintCutFinder::new(&self.borrowck_in_facts, algorithm,
+                                                  error_remains);
+        let constraint_cut = cut_finder.compute_cut(path_edges);
+        if constraint_cut.is_none() {
+            info!("No constraint cut found for the error at {:?} for {:?}.", error_point, error);
+        }
+        constraint_cut
+    }
+
+    /// Creates a diagnostic in the JSON format of rustc for an error and the (improved) graph that
+    /// explains it. It is a note with the code of the error, so it does not report the error again. The primary span of the diagnostic is the point of the error, the point where
+    /// the invalidated loan is created, the point where it is used later (if it is known) and every
+    /// constraint on the path of the graph are added as labelled secondary spans, in the order of
+    /// the path. Constraints without any source location are added as notes instead, as are the
+    /// locals that introduce the regions on the path.
+    fn create_error_diagnostic(&self, error_graph: &EnrichedErrorGraph, error_point: PointIndex,
+                               error: BorrowckError) -> Diagnostic {
+        let source_map = self.tcx.sess.source_map();
+        let mut diagnostic = match error {
+            BorrowckError::LoanInvalidated(error_loan) => {
+                let message = match error_graph.error_code {
+                    Some(error_code) => format!("explanation of the lifetime error: {}",
+                                                error_code.description()),
+                    None => "explanation of the lifetime error: a borrow is invalidated while it \
+                             is still in use".to_string(),
+                };
+                let mut diagnostic = Diagnostic::new(
+                    "note",
+                    message,
+                    error_graph.error_code.map(RustcErrorCode::as_str));
+                diagnostic.spans.extend(DiagnosticSpan::from_span(
+                    source_map, self.get_span_for_point(error_point), true,
+                    Some("the borrow is invalidated here, while it is still live".to_string())));
+                if let Some(loan_point) = self.find_loan_point(error_loan) {
+                    diagnostic.spans.extend(DiagnosticSpan::from_span(
+                        source_map, self.get_span_for_point(loan_point), false,
+                        Some("the borrow is created here".to_string())));
+                }
+                if let Some(ref later_use_point) = error_graph.later_use_point {
+                    diagnostic.spans.extend(DiagnosticSpan::from_span(
+                        source_map, self.get_span_for_point(later_use_point.point), false,
+                        Some(later_use_point.label.clone())));
+                }
+                diagnostic
+            },
+            BorrowckError::SubsetRequired(region1, region2) => {
+                let mut diagnostic = Diagnostic::new(
+                    "note",
+                    format!("explanation of the lifetime error: {} must outlive {}",
+                            error_graph.describe_region(region1),
+                            error_graph.describe_region(region2)),
+                    None);
+                diagnostic.spans.extend(DiagnosticSpan::from_span(
+                    source_map, self.get_span_for_point(error_point), true,
+                    Some("this requires that the first lifetime outlives the second one".to_string())));
+                diagnostic
+            },
+            BorrowckError::UseOfMoved(_) =>
+                unreachable!("Move errors are explained by create_move_error_diagnostic()."),
+        };
+
+        let path_edges = error_graph.edges_in_path_order();
+        for (constraint_index, (region1, region2)) in path_edges.iter().enumerate() {
+            let label = format!("constraint {}: {:?} may point to {:?}", constraint_index + 1,
+                                region2, region1);
+            let span = error_graph.spans_for_edges.get(&(*region1, *region2)).cloned()
+                .unwrap_or(DUMMY_SP);
+            match DiagnosticSpan::from_span(source_map, span, false, Some(label.clone())) {
+                Some(diagnostic_span) => diagnostic.spans.push(diagnostic_span),
+                None => diagnostic.add_child("note", label, None),
+            }
+        }
+
+        for region in error_graph.regions_in_path_order() {
+            match error_graph.locals_mir_for_regions.get(&region) {
+                Some(Some(local_decl)) if local_decl.is_user_variable() => {
+                    let (_, ref local_name, _) = error_graph.locals_info_for_regions[&region];
+                    diagnostic.add_child(
+                        "note",
+                        format!("{:?} is a lifetime in the type of `{}`", region, local_name),
+                        DiagnosticSpan::from_span(source_map, local_decl.source_info.span, true,
+                                                  None));
+                },
+                _ => if let Some(name) = error_graph.captured_names_for_regions.get(&region) {
+                    diagnostic.add_child(
+                        "note",
+                        format!("{:?} is a lifetime in the type of `{}` of the enclosing function",
+                                region, name),
+                        None);
+                },
+            }
+        }
+
+        let mut described_captures = Vec::new();
+        for region in error_graph.regions_in_path_order() {
+            let capture_info = match error_graph.captures_for_regions.get(&region) {
+                Some(capture_info) => capture_info,
+                None => continue,
+            };
+            let capture = (&capture_info.closure_name, &capture_info.variable_name);
+            if described_captures.contains(&capture) {
+                continue;
+            }
+            described_captures.push(capture);
+            diagnostic.add_child(
+                "note",
+                format!("`{}` is captured {} by the closure `{}`, which is created here",
+                        capture_info.variable_name, capture_info.capture_mode,
+                        capture_info.closure_name),
+                DiagnosticSpan::from_span(source_map, capture_info.creation_span, true, None));
+            if let Some(call_span) = capture_info.call_span {
+                diagnostic.add_child(
+                    "note",
+                    format!("the closure `{}` is called here, so the capture of `{}` is still in \
+                             use", capture_info.closure_name, capture_info.variable_name),
+                    DiagnosticSpan::from_span(source_map, call_span, true, None));
+            }
+        }
 
+        if let Some(signature_note) = self.create_signature_note(error_graph, error_point, error) {
+            diagnostic.add_child("note", signature_note, None);
         }
 
+        diagnostic.render();
+        diagnostic
     }
 
     /// This function will write a graph (in dot/Graphviz format) to a file. This graph either is
@@ -444,11 +1803,11 @@ impl<'a, 'tcx> MirInfoPrinter<'a, 'tcx> {
     /// is part of the outlives relation graph.)
     fn print_outlive_error_graph(&self,
                                 error_graph: &EnrichedErrorGraph,
-                                graph_out_path: &PathBuf) {
+                                graph_out_path: &Path) -> io::Result<()> {
 
-        let mut graph_file = File::create(graph_out_path).expect("Unable to create file");
+        let mut graph_file = File::create(graph_out_path)?;
 
-        writeln!(graph_file, "digraph G {{");
+        writeln!(graph_file, "digraph G {{")?;
 
         let mut i = 0;
 
@@ -468,26 +1827,72 @@ impl<'a, 'tcx> MirInfoPrinter<'a, 'tcx> {
             }
 
             if *local_source1_snip != String::default(){
-                writeln!(graph_file, "{:?} [ shape=plaintext, color=blue, label =  <<table><tr><td>Lifetime {:?}</td></tr><tr><td>{}: &amp;'{:?}</td></tr><tr><td>{}: {}</td></tr>{}</table>> ]", region1, region1, local_name1, region1, line_number1, local_source1_snip.trim().replace("&","&amp;").replace("<", "&lt;").replace(">", "&gt;"), region1_lines_str);
+                writeln!(graph_file, "{:?} [ shape=plaintext, color=blue, label =  <<table><tr><td>Lifetime {:?}</td></tr><tr><td>{}: &amp;'{:?}</td></tr><tr><td>{}: {}</td></tr>{}</table>> ]", region1, region1, local_name1, region1, line_number1, local_source1_snip.trim().replace("&","&amp;").replace("<", "&lt;").replace(">", "&gt;"), region1_lines_str)?;
             }else {
                 writeln!(graph_file, "{:?} [ shape=plaintext, color=blue, label =  <<table><tr><td>Lifetime {:?}</td></tr><tr><td>{}: &amp;'{:?}</td></tr>{}</table>> ]", region1, region1, local_name1, region1, region1_lines_str
-                );
+                )?;
             }
             if *local_source2_snip != String::default(){
-                writeln!(graph_file, "{:?} [ shape=plaintext, color=blue, label =  <<table><tr><td>Lifetime {:?}</td></tr><tr><td>{}: &amp;'{:?}</td></tr><tr><td>{}: {}</td></tr>{}</table>> ]", region2, region2, local_name2, region2, line_number2, local_source2_snip.trim().replace("&","&amp;").replace("<", "&lt;").replace(">", "&gt;"), region2_lines_str);
+                writeln!(graph_file, "{:?} [ shape=plaintext, color=blue, label =  <<table><tr><td>Lifetime {:?}</td></tr><tr><td>{}: &amp;'{:?}</td></tr><tr><td>{}: {}</td></tr>{}</table>> ]", region2, region2, local_name2, region2, line_number2, local_source2_snip.trim().replace("&","&amp;").replace("<", "&lt;").replace(">", "&gt;"), region2_lines_str)?;
             }else {
-                writeln!(graph_file, "{:?} [ shape=plaintext, color=blue, label =  <<table><tr><td>Lifetime {:?}</td></tr><tr><td>{}: &amp;'{:?}</td></tr>{}</table>> ]", region2, region2, local_name2, region2, region2_lines_str);
+                writeln!(graph_file, "{:?} [ shape=plaintext, color=blue, label =  <<table><tr><td>Lifetime {:?}</td></tr><tr><td>{}: &amp;'{:?}</td></tr>{}</table>> ]", region2, region2, local_name2, region2, region2_lines_str)?;
             }
 
             // write the box (graph node)  with the constraint information, and the edges around it.
-            writeln!(graph_file, "{:?} [ shape=plaintext, label=  <<table><tr><td> Constraint </td></tr><tr><td> {:?} may point to {:?}</td></tr><tr><td> generated at line {:?}: </td></tr><tr><td> {} </td></tr></table>>  ]", i, region2, region1, ind, point_snip.trim().replace("&","&amp;").replace("<", "&lt;").replace(">", "&gt;"));
-            writeln!(graph_file, "{:?} -> {:?} -> {:?}\n", region1, i, region2);
+            writeln!(graph_file, "{:?} [ shape=plaintext, label=  <<table><tr><td> Constraint </td></tr><tr><td> {:?} may point to {:?}</td></tr><tr><td> generated at line {:?}: </td></tr><tr><td> {} </td></tr></table>>  ]", i, region2, region1, ind, point_snip.trim().replace("&","&amp;").replace("<", "&lt;").replace(">", "&gt;"))?;
+            writeln!(graph_file, "{:?} -> {:?} -> {:?}\n", region1, i, region2)?;
 
             i += 1;
         }
 
+        // the use that keeps the loan live at the error is drawn as a highlighted node, connected to
+        // the region that it keeps live (or to the last region of the path, if the region is not
+        // part of the improved graph).
+        if let Some(ref liveness_trace) = error_graph.liveness_trace {
+            writeln!(graph_file, "later_use [ shape=plaintext, style=filled, fillcolor=lightcoral, \
+                                  label = <<table><tr><td> Later use </td></tr><tr><td> {} </td></tr>\
+                                  <tr><td> {}: {} </td></tr></table>> ]",
+                     liveness_trace.describe().replace("&","&amp;").replace("<", "&lt;").replace(">", "&gt;"),
+                     liveness_trace.use_line.0,
+                     liveness_trace.use_line.1.trim().replace("&","&amp;").replace("<", "&lt;").replace(">", "&gt;"))?;
+            let regions = error_graph.regions_in_path_order();
+            let live_region = if regions.contains(&liveness_trace.region) {
+                Some(liveness_trace.region)
+            } else {
+                regions.last().cloned()
+            };
+            if let Some(live_region) = live_region {
+                writeln!(graph_file, "{:?} -> later_use [ color=red, penwidth=2, label = \"live until\" ]",
+                         live_region)?;
+            }
+        }
+
+        // the alternative paths are drawn as separate (dashed) chains of regions, with the lines of
+        // their constraints on the edges.
+        for alternative_path in error_graph.alternative_paths.iter() {
+            writeln!(graph_file, "subgraph cluster_alternative_{} {{", alternative_path.rank)?;
+            writeln!(graph_file, "label = \"Alternative {} (cost {})\"; style = dashed;",
+                     alternative_path.rank, alternative_path.cost)?;
+            for region in alternative_path.regions.iter() {
+                writeln!(graph_file, "alt{}_{:?} [ shape=plaintext, label = \"Lifetime {:?}\" ]",
+                         alternative_path.rank, region, region)?;
+            }
+            for (regions, (line_number, line_snip)) in alternative_path.regions.windows(2)
+                    .zip(alternative_path.constraint_lines.iter()) {
+                let label = if *line_number == usize::MAX {
+                    String::default()
+                } else {
+                    format!("{}: {}", line_number, line_snip.trim())
+                };
+                writeln!(graph_file, "alt{}_{:?} -> alt{}_{:?} [ style=dashed, label = {:?} ]",
+                         alternative_path.rank, regions[0], alternative_path.rank, regions[1],
+                         label)?;
+            }
+            writeln!(graph_file, "}}")?;
+        }
 
-        writeln!(graph_file, "}}");
+        writeln!(graph_file, "}}")?;
+        Ok(())
     }
 
     /// This function will serialize a graph to JSON and write it to a file. This graph either is
@@ -504,11 +1909,11 @@ impl<'a, 'tcx> MirInfoPrinter<'a, 'tcx> {
     /// location will be overwritten.
     fn dump_outlive_error_graph_as_json(&self,
                                         error_graph: &EnrichedErrorGraph,
-                                        graph_out_path: &PathBuf) {
-        let out_file = File::create(graph_out_path).expect("Unable to create file");
-        let res = serde_json::to_writer_pretty(out_file, error_graph);
+                                        graph_out_path: &Path) -> io::Result<()> {
+        let out_file = File::create(graph_out_path)?;
         // TODO ev. remove pretty when done with debugging!
-        debug!("Result from dumping: {:?}", res);
+        serde_json::to_writer_pretty(out_file, error_graph)
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))
     }
 
     /// Function that creates the enriched error graph for the passed graph information.
@@ -524,16 +1929,23 @@ impl<'a, 'tcx> MirInfoPrinter<'a, 'tcx> {
     /// In addition, this method needs a "map" from points to loans and regions. This is the map
     /// that will be passed to get_lines_for_region(...) for the mapping, so see this method's
     /// documentation for more details.
+    /// Last, the point and the loan of the error that the graph explains must be passed, they are
+    /// stored in the graph to identify the error.
     fn create_enriched_graph(&self, graph_information: &FxHashMap<(Region, Region), Vec<PointIndex>>,
-                             region_loan_point_map: &Vec<(Region, Loan, PointIndex)>)
-            -> EnrichedErrorGraph {
+                             region_loan_point_map: &Vec<(Region, Loan, PointIndex)>,
+                             error_point: PointIndex, error: BorrowckError)
+            -> EnrichedErrorGraph<'tcx> {
         let mut edges: Vec<(Region, Region)> =  graph_information.keys().map(|&(r1, r2)| (r1, r2)).collect();
         edges.dedup();
         let mut locals_mir_for_regions = FxHashMap::default();
         let mut locals_info_for_regions = FxHashMap::default();
         let mut lines_for_edges = FxHashMap::default();
         let mut lines_for_edges_start = FxHashMap::default();
+        let mut spans_for_edges = FxHashMap::default();
         let mut lines_for_regions = FxHashMap::default();
+        let mut captured_names_for_regions = FxHashMap::default();
+        let mut captures_for_regions = FxHashMap::default();
+        let mut universal_regions_descriptions = FxHashMap::default();
 
         for ((r1, r2), pts) in graph_information.iter() {
             if ! locals_info_for_regions.contains_key(r1) {
@@ -549,6 +1961,7 @@ impl<'a, 'tcx> MirInfoPrinter<'a, 'tcx> {
             let line_for_egge_points = self.find_first_line_for_points(pts);
             lines_for_edges.insert((*r1, *r2), line_for_egge_points.clone());
             lines_for_edges_start.insert(*r1, line_for_egge_points);
+            spans_for_edges.insert((*r1, *r2), self.find_first_span_for_points(pts));
 
             if ! lines_for_regions.contains_key(r1) {
                 lines_for_regions.insert(*r1, self.get_lines_for_region(*r1, region_loan_point_map));
@@ -556,23 +1969,77 @@ impl<'a, 'tcx> MirInfoPrinter<'a, 'tcx> {
             if ! lines_for_regions.contains_key(r2) {
                 lines_for_regions.insert(*r2, self.get_lines_for_region(*r2, region_loan_point_map));
             }
+            for region in [r1, r2].iter() {
+                if let Some(name) = self.captured_names_for_regions.get(region) {
+                    captured_names_for_regions.insert(**region, name.clone());
+                }
+                if let Some(capture_info) = self.captures_for_regions.get(region) {
+                    captures_for_regions.insert(**region, capture_info.clone());
+                }
+                if let Some(universal_region) = self.universal_regions.get(region) {
+                    universal_regions_descriptions.insert(**region,
+                                                          universal_region.description.clone());
+                }
+            }
         }
 
+        let later_use = match error {
+            BorrowckError::LoanInvalidated(loan) => self.compute_liveness_trace(error_point, loan),
+            _ => None,
+        };
+        let (borrow_point, invalidation_point, later_use_point) =
+            self.create_error_sites(error_point, error, later_use.as_ref());
+
         EnrichedErrorGraph{
             function_name: self.def_path.to_filename_friendly_no_crate(),
+            error_loan: match error {
+                BorrowckError::LoanInvalidated(loan) => Some(loan),
+                _ => None,
+            },
+            required_subset: match error {
+                BorrowckError::SubsetRequired(region1, region2) => Some((region1, region2)),
+                _ => None,
+            },
+            error_code: self.classify_error(error_point, error),
+            error_line: self.get_line_for_point(error_point),
+            loan_line: match error {
+                BorrowckError::LoanInvalidated(loan) =>
+                    self.find_loan_point(loan).map(|loan_point| self.get_line_for_point(loan_point)),
+                _ => None,
+            },
+            borrowed_name: match error {
+                BorrowckError::LoanInvalidated(loan) => self.find_borrowed_name(loan),
+                _ => None,
+            },
+            borrow_point,
+            invalidation_point,
+            later_use_point,
+            liveness_trace: later_use.map(|(_, liveness_trace)| liveness_trace),
+            accepted_by_polonius: match error {
+                BorrowckError::LoanInvalidated(loan) => !self.is_polonius_error(error_point, loan),
+                _ => false,
+            },
             edges,
             locals_mir_for_regions,
             locals_info_for_regions,
             lines_for_regions,
             lines_for_edges,
             lines_for_edges_start,
+            spans_for_edges,
+            captured_names_for_regions,
+            captures_for_regions,
+            universal_regions_descriptions,
+            constraint_cut: None,
+            cut_lines: Vec::new(),
+            path_cost: 0,
+            alternative_paths: Vec::new(),
         }
 
     }
 
     /// This method finds all lines (of source code) that are involved in a certain region.
     /// For this, it will first look up all points that are affected by this region in the map
-    /// that must be passed. Thereby it is intended that the map is either the borrow_region or the
+    /// that must be passed. Thereby it is intended that the map is either the loan_issued_at or the
     /// requires relation. (These are obtained from the Polonius input/output facts) The result
     /// might differ depending on the used relation.
     /// Duplicate lines, i.e. lines that have the same line number are ignored, i.e. each line is
@@ -608,23 +2075,104 @@ impl<'a, 'tcx> MirInfoPrinter<'a, 'tcx> {
     /// The resulting line is returned as a tuple giving first the line number, as usize, and then
     /// the actual source code (text), as String.
     fn get_line_for_point(&self, pt: PointIndex) -> (usize, String) {
+        self.get_line_for_span(self.get_span_for_point(pt))
+    }
+
+    /// Gives the (first) source line of a span, in the same form as get_line_for_point().
+    fn get_line_for_span(&self, span: Span) -> (usize, String) {
+        let point_line = self.tcx.sess.source_map().lookup_char_pos(span.lo()).line;
+        let point_ln = self.tcx.sess.source_map().lookup_line(span.lo()).unwrap();
+        let point_snip = point_ln.sf.get_line(point_line-1).unwrap().to_string();
+        (point_line, point_snip)
+    }
+
+    /// Finds the closures that are created in the function, and gives the information about their
+    /// captures for the regions of the temporaries that hold the captured variables when the
+    /// closure is created. (I.e. the regions of the operands of the closure aggregate, which are
+    /// the regions that the loans of captures by reference flow into.)
+    fn compute_captures_for_regions(&self) -> HashMap<Region, CaptureInfo> {
+        let mut result = HashMap::new();
+        for block_data in self.mir.basic_blocks.iter() {
+            for stmt in block_data.statements.iter() {
+                let assign = match stmt.kind {
+                    mir::StatementKind::Assign(ref assign) => assign,
+                    _ => continue,
+                };
+                let (ref closure_place, ref rvalue) = **assign;
+                let (closure_def_id, operands) = match *rvalue {
+                    mir::Rvalue::Aggregate(ref kind, ref operands) => match **kind {
+                        mir::AggregateKind::Closure(closure_def_id, _) => (closure_def_id, operands),
+                        _ => continue,
+                    },
+                    _ => continue,
+                };
+                let closure_name = closure_place.as_local()
+                    .and_then(|local| self.find_name_of_local(local))
+                    .unwrap_or_else(|| self.tcx.def_path_str(closure_def_id));
+                let creation_span = stmt.source_info.span;
+                let call_span = self.find_first_call_of_closure(closure_def_id);
+                let captures = self.tcx.closure_captures(closure_def_id.expect_local());
+                // the operands of the aggregate are the captured places, in the order of the
+                // captures.
+                for (operand, captured_place) in operands.iter().zip(captures.iter()) {
+                    let operand_local = match operand.place().and_then(|place| place.as_local()) {
+                        Some(local) => local,
+                        None => continue,
+                    };
+                    let capture_info = CaptureInfo {
+                        closure_name: closure_name.clone(),
+                        variable_name: captured_place.to_string(self.tcx),
+                        capture_mode: capture_mode_description(captured_place.info.capture_kind),
+                        creation_line: self.get_line_for_span(creation_span),
+                        call_line: call_span.map(|span| self.get_line_for_span(span)),
+                        creation_span,
+                        call_span,
+                    };
+                    for region in regions::regions_of_local(self.tcx, self.mir, operand_local) {
+                        result.insert(region, capture_info.clone());
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    /// Gives the span of the first call (the one on the lowest line) of the closure in the
+    /// function, if it is called in the function at all. A call is recognized as a call of a
+    /// function whose generic arguments contain the type of the closure, e.g.
+    /// `<{closure} as FnMut<()>>::call_mut`.
+    fn find_first_call_of_closure(&self, closure_def_id: hir::def_id::DefId) -> Option<Span> {
+        let source_map = self.tcx.sess.source_map();
+        self.mir.basic_blocks.iter()
+            .filter_map(|block_data| block_data.terminator.as_ref())
+            .filter(|terminator| match terminator.kind {
+                mir::TerminatorKind::Call { ref func, .. } => match *func.ty(self.mir, self.tcx).kind() {
+                    ty::FnDef(_, generic_args) => generic_args.types().any(|ty| match *ty.kind() {
+                        ty::Closure(def_id, _) => def_id == closure_def_id,
+                        _ => false,
+                    }),
+                    _ => false,
+                },
+                _ => false,
+            })
+            .map(|terminator| terminator.source_info.span)
+            .min_by_key(|&span| source_map.lookup_char_pos(span.lo()).line)
+    }
+
+    /// Gives the span of the statement or terminator in the MIR that the point belongs to.
+    fn get_span_for_point(&self, pt: PointIndex) -> Span {
         // code for the mapping copied from the (legacy) print_outlive_error_graph method,
         // slightly adapted.
         let point1 = self.interner.get_point(pt);
         let point_location = point1.location;
         let point_block = &self.mir[point_location.block];
-        let point_span;
         if point_block.statements.len() == point_location.statement_index{
             let terminator = point_block.terminator.as_ref().unwrap();
-            point_span = terminator.source_info.span;
+            terminator.source_info.span
         }else {
             let stmt_x = &point_block.statements[point_location.statement_index];
-            point_span = stmt_x.source_info.span;
+            stmt_x.source_info.span
         }
-        let point_line = self.tcx.sess.source_map().lookup_char_pos(point_span.lo()).line;
-        let point_ln = self.tcx.sess.source_map().lookup_line(point_span.lo()).unwrap();
-        let point_snip = point_ln.sf.get_line(point_line-1).unwrap().to_string();
-        (point_line, point_snip)
     }
 
     /// This function takes a Region and (tries to) map it to a local that introduced this region,
@@ -640,9 +2188,9 @@ impl<'a, 'tcx> MirInfoPrinter<'a, 'tcx> {
     /// message informing about this is logged at debug level. In addition, in this case, or when
     /// the mapping to a source code snipped fails, an empty string will be returned for the source
     /// code as well.
-    fn find_local_for_region(&self, reg: &Region) -> (Option<mir::LocalDecl>, usize, String, String) {
+    fn find_local_for_region(&self, reg: &Region) -> (Option<mir::LocalDecl<'tcx>>, usize, String, String) {
         let mut local_name = String::default();
-        let mut local_source = syntax_pos::DUMMY_SP;
+        let mut local_source = DUMMY_SP;
         let mut line_number = usize::default();
         let mut local_source_snip = String::default();
         let mut local_decl_option = None;
@@ -651,15 +2199,15 @@ impl<'a, 'tcx> MirInfoPrinter<'a, 'tcx> {
             // there is a local (x) for reg, get some details about it
             // (code copied from an old version)
             let local_decl = &self.mir.local_decls[*local_x1];
-            if local_decl.name != None {
-                local_name = local_decl.name.unwrap().to_string();
+            if let Some(name) = self.find_name_of_local(*local_x1) {
+                local_name = name;
                 local_source = local_decl.source_info.span;
             } else {
                 local_name = ("anonymous Variable").to_string();
-                for block_data in self.mir.basic_blocks().iter() {
+                for block_data in self.mir.basic_blocks.iter() {
                     for stmt in block_data.statements.iter() {
-                        if let mir::StatementKind::Assign(ref l, ref _r) = stmt.kind{
-                            match l.local() {
+                        if let mir::StatementKind::Assign(ref assign) = stmt.kind{
+                            match assign.0.local_or_deref_local() {
                                 Some(v) => if v==*local_x1 {
                                     local_source = stmt.source_info.span;
                                 }
@@ -680,6 +2228,22 @@ impl<'a, 'tcx> MirInfoPrinter<'a, 'tcx> {
         (local_decl_option, line_number, local_name, local_source_snip)
     }
 
+    /// Gives the name of the local in the source code, if it has one. (The names of the locals are
+    /// given by the debug info of the MIR.)
+    fn find_name_of_local(&self, local: mir::Local) -> Option<String> {
+        regions::find_name_of_local(self.mir, local)
+    }
+
+    /// Gives the span of the first point (the one on the lowest line) of a set of points, like
+    /// find_first_line_for_points() gives its line. Returns DUMMY_SP if the set is empty.
+    fn find_first_span_for_points(&self, pts: &[PointIndex]) -> Span {
+        let source_map = self.tcx.sess.source_map();
+        pts.iter()
+            .map(|&pt| self.get_span_for_point(pt))
+            .min_by_key(|&span| source_map.lookup_char_pos(span.lo()).line)
+            .unwrap_or(DUMMY_SP)
+    }
+
     /// This function takes a set of points, and returns the first line (line on the lowest line)
     /// that is related to these points.
     /// Such a set of points can e.g. be obtained as extra information to an edged in the outlives
@@ -713,6 +2277,48 @@ impl<'a, 'tcx> MirInfoPrinter<'a, 'tcx> {
     }
 }
 
+/// This struct describes how a variable is captured by a closure that is created in a function.
+/// It is attached to the regions of the temporaries that hold the captured variables, since these
+/// are the regions that appear in the path to an error that is caused by the capture.
+#[derive(Clone, Debug, serde_derive::Serialize)]
+pub struct CaptureInfo {
+    /// The name of the closure, i.e. the name of the variable that the closure is assigned to, or
+    /// the def path of the closure if it is not assigned to a variable.
+    pub closure_name: String,
+    /// The captured variable (or place, e.g. `x.f`), as it is given in the source code.
+    pub variable_name: String,
+    /// How the variable is captured, e.g. "by mutable reference".
+    pub capture_mode: String,
+    /// The line where the closure is created (and hence the variable is captured), given as it's
+    /// number and it's source code.
+    pub creation_line: (usize, String),
+    /// The (first) line where the closure is called in the function, if it is called there.
+    pub call_line: Option<(usize, String)>,
+    /// The span of the creation of the closure. Not included in a JSON dump, as spans are not
+    /// serializable.
+    #[serde(skip_serializing)]
+    creation_span: Span,
+    /// The span of the first call of the closure, if any. Not included in a JSON dump.
+    #[serde(skip_serializing)]
+    call_span: Option<Span>,
+}
+
+impl CaptureInfo {
+    /// Describes the capture, and where the closure is created and called, for the explanations of
+    /// errors.
+    pub fn describe(&self) -> String {
+        let description = format!("the closure `{}` captures `{}` {} when it is created at line {}",
+                                  self.closure_name, self.variable_name, self.capture_mode,
+                                  self.creation_line.0);
+        match self.call_line {
+            Some((call_line_number, _)) =>
+                format!("{}, and the capture is still in use when it is called at line {}",
+                        description, call_line_number),
+            None => description,
+        }
+    }
+}
+
 /// This struct describes a graph that explains a lifetime error in a method of a Rust program.
 /// The graph is connecting all regions/lifetimes that are relevant for this error by edges.
 /// In addition, this struct does also store quite soem extra information about this graph and
@@ -723,11 +2329,52 @@ impl<'a, 'tcx> MirInfoPrinter<'a, 'tcx> {
 /// information that is needed to create the enriched graph. This struct is primarily intended to
 /// store the information.
 #[derive(serde_derive::Serialize)]
-struct EnrichedErrorGraph<'tcx> {
+pub struct EnrichedErrorGraph<'tcx> {
     /// This shall give the name of the method/function that this error was found in, and hence the
     /// function/method from whom information is depicted by this graph.
     /// NOTE: For now, no fixed decisions regarding the format of the name were taken.
-    function_name: String,
+    pub function_name: String,
+    /// The loan (borrow) of the error that is explained by this graph. Together with the line of
+    /// the error it allows to tell apart the graphs of several errors in the same function.
+    /// None if the error is not about a loan, but about a required subset.
+    pub error_loan: Option<Loan>,
+    /// If the error is that a universal region must outlive another one (which is not known from
+    /// the signature), the two regions, the first one must outlive the second one. The graph then
+    /// describes the path from the first to the second one. None for errors about a loan.
+    pub required_subset: Option<(Region, Region)>,
+    /// The code that rustc uses for the error, as far as it can be derived from the kind of the
+    /// invalidated loan and the statement that invalidates it (see
+    /// error_codes::classify_loan_error()). None if the error cannot be classified, and for
+    /// required subsets.
+    pub error_code: Option<RustcErrorCode>,
+    /// The line where the error occurs, i.e. the line of the point where the loan is invalidated
+    /// while it is still live. It is given as it's number (usize) and it's source code (text,
+    /// String)
+    pub error_line: (usize, String),
+    /// The line where the invalidated loan is created, given as it's number and it's source code.
+    /// None for errors that are not about a loan, or if the creation of the loan was not found.
+    pub loan_line: Option<(usize, String)>,
+    /// The name of the variable that the invalidated loan borrows (from), if it has one. None for
+    /// errors that are not about a loan.
+    pub borrowed_name: Option<String>,
+    /// The point where the invalidated loan is created. This and the next two fields are the three
+    /// points that the messages of rustc about invalidated loans are built around. All of them are
+    /// None for errors that are not about a loan, and borrow_point is also None if the creation of
+    /// the loan was not found.
+    pub borrow_point: Option<ErrorSite>,
+    /// The point where the loan is invalidated, i.e. the point of the error.
+    pub invalidation_point: Option<ErrorSite>,
+    /// The point after the error where the loan is used, which is why it is still live at the point
+    /// of the error (see liveness_trace). None if no such use is found.
+    pub later_use_point: Option<ErrorSite>,
+    /// Tells which use keeps the loan live at the point of the error, and how it is reached from
+    /// there in the control flow graph. None for errors that are not about a loan, or if no such
+    /// use is found.
+    pub liveness_trace: Option<LivenessTrace>,
+    /// True if Polonius accepts the code, and the error is only reported by the borrow checker of
+    /// rustc (NLL). The graph then explains the error location insensitively, i.e. its constraints
+    /// may not hold at the same time. (See MirInfoPrinter::compute_location_insensitive_errors().)
+    pub accepted_by_polonius: bool,
     /// This is the core of the graph, the edges that define it
     edges: Vec<(Region, Region)>,
     /// This map shall contain an entry for all regions that are part of the graph, and give the
@@ -764,13 +2411,122 @@ struct EnrichedErrorGraph<'tcx> {
     /// "key must be a string"), this field will not be included in a JSON dump of this structure.
     /// Instead, the simplified lines_for_edges_start will be included.
     #[serde(skip_serializing)]
-    lines_for_edges: FxHashMap<(Region, Region), (usize, String)>,
+    pub lines_for_edges: FxHashMap<(Region, Region), (usize, String)>,
     /// This is the same as lines_for_edges, hence it maps from edges to a line that is considered
     /// to be have created this edged/constraint.
     /// However, it only identifies edges by the first region, i.e. the region the edge starts at.
     /// Therefore, this map can (and will) be included when creating a JSON dump of this structure.
-    lines_for_edges_start: FxHashMap<Region, (usize, String)>
+    lines_for_edges_start: FxHashMap<Region, (usize, String)>,
+    /// This maps from edges to the span of the point that is considered to have created the edge,
+    /// i.e. the span of the line given by lines_for_edges. (DUMMY_SP if the edge has no points.)
+    /// It is used to point at the constraints of the graph in diagnostics. Spans are not
+    /// serializable, so this field is not included in a JSON dump of this structure.
+    #[serde(skip_serializing)]
+    spans_for_edges: FxHashMap<(Region, Region), Span>,
+    /// If the graph explains an error in a closure, this maps the regions of the graph that are
+    /// linked to a variable of the enclosing function (e.g. a captured variable) to the name of
+    /// this variable. (See MirInfoPrinter::captured_names_for_regions.) Empty for other functions.
+    captured_names_for_regions: FxHashMap<Region, String>,
+    /// This maps the regions of the graph that belong to the capture of a variable by a closure
+    /// (see MirInfoPrinter::captures_for_regions) to the information about the capture.
+    pub captures_for_regions: FxHashMap<Region, CaptureInfo>,
+    /// This maps the regions of the graph that are universal regions, i.e. lifetimes of the
+    /// signature of the function (or 'static), to their description. (See
+    /// MirInfoPrinter::universal_regions.)
+    pub universal_regions_descriptions: FxHashMap<Region, String>,
+    /// The constraints (among the edges of the unimproved path) whose removal makes the error
+    /// disappear, see ConstraintCutFinder. None if it was not requested (by
+    /// `--rust-life-constraint-cut`) or no such constraints were found.
+    pub constraint_cut: Option<ConstraintCut>,
+    /// The lines that cause the constraints of the constraint_cut, sorted by their number. Empty
+    /// if there is no constraint cut.
+    pub cut_lines: Vec<(usize, String)>,
+    /// The cost of the path of the graph (before it was improved), as it was used to rank it among
+    /// the alternatives. (See PathSearch, for the first path that is found it is the number of its
+    /// edges.)
+    pub path_cost: usize,
+    /// Further paths that explain the error, ranked by their cost (the path of the graph itself has
+    /// rank 1). Only as many as requested by `--rust-life-alternatives` are included.
+    pub alternative_paths: Vec<AlternativePath>,
+}
+
+#[cfg(test)]
+impl<'tcx> EnrichedErrorGraph<'tcx> {
+    /// Creates a graph for an error at the given line of the function with the given name, whose
+    /// path consists of the given edges, each together with the line that causes it. Everything
+    /// else is left empty, the public fields can be filled in as needed. This is only intended for
+    /// the tests of the code that works on the graphs, e.g. the explanations in prose.
+    pub fn new_for_test(function_name: &str, error_line: (usize, &str),
+                        edges: &[(Region, Region, (usize, &str))]) -> Self {
+        let lines_for_edges: FxHashMap<(Region, Region), (usize, String)> = edges.iter()
+            .map(|&(region1, region2, (line_number, line))|
+                ((region1, region2), (line_number, line.to_string())))
+            .collect();
+        EnrichedErrorGraph {
+            function_name: function_name.to_string(),
+            error_loan: None,
+            required_subset: None,
+            error_code: None,
+            error_line: (error_line.0, error_line.1.to_string()),
+            loan_line: None,
+            borrowed_name: None,
+            borrow_point: None,
+            invalidation_point: None,
+            later_use_point: None,
+            liveness_trace: None,
+            accepted_by_polonius: false,
+            edges: edges.iter().map(|&(region1, region2, _)| (region1, region2)).collect(),
+            locals_mir_for_regions: FxHashMap::default(),
+            locals_info_for_regions: FxHashMap::default(),
+            lines_for_regions: FxHashMap::default(),
+            lines_for_edges_start: lines_for_edges.iter()
+                .map(|(&(region1, _), line)| (region1, line.clone()))
+                .collect(),
+            lines_for_edges,
+            spans_for_edges: FxHashMap::default(),
+            captured_names_for_regions: FxHashMap::default(),
+            captures_for_regions: FxHashMap::default(),
+            universal_regions_descriptions: FxHashMap::default(),
+            constraint_cut: None,
+            cut_lines: Vec::new(),
+            path_cost: edges.len(),
+            alternative_paths: Vec::new(),
+        }
+    }
+}
+
+/// A point that is relevant for an error, together with its location in the source code, see
+/// EnrichedErrorGraph::borrow_point.
+#[derive(serde_derive::Serialize)]
+pub struct ErrorSite {
+    /// The point, as it is used in the facts of Polonius.
+    pub point: PointIndex,
+    /// The line of the point, given as it's number and it's source code.
+    pub line: (usize, String),
+    /// The source code of the span of the point (e.g. `&mut v`), empty if it is not available.
+    pub snippet: String,
+    /// The span of the point, in the form of the spans of diagnostics (with the label). None if the
+    /// point has no span in the source code.
+    pub span: Option<DiagnosticSpan>,
+    /// Tells what happens at the point, like the labels of rustc, e.g. "`v` is borrowed here".
+    pub label: String,
+}
 
+/// An alternative path that explains an error, see EnrichedErrorGraph::alternative_paths.
+#[derive(Clone, Debug, serde_derive::Serialize)]
+pub struct AlternativePath {
+    /// The rank of the path among all paths that explain the error, starting at 2. (The path of
+    /// the graph has rank 1.)
+    pub rank: usize,
+    /// The cost of the path, see PathSearch.
+    pub cost: usize,
+    /// The regions on the path, in the order of the path (i.e. from the region of the loan, or the
+    /// region that must outlive the other one).
+    pub regions: Vec<Region>,
+    /// For every edge of the path (i.e. every pair of consecutive regions), the line that causes
+    /// the constraint, given as it's number (usize::MAX if there is none) and it's source
+    /// code.
+    pub constraint_lines: Vec<(usize, String)>,
 }
 
 impl<'tcx> EnrichedErrorGraph<'tcx> {
@@ -812,11 +2568,92 @@ impl<'tcx> EnrichedErrorGraph<'tcx> {
         result
     }
 
+    /// This method operates (only) on the edges of the graph and gives them in the order of the
+    /// path that the graph describes, i.e. starting with the edge of the entry region (see
+    /// find_entry_region) and following the edges up to the exit region.
+    pub fn edges_in_path_order(&self) -> Vec<(Region, Region)> {
+        let mut result = Vec::new();
+        let mut cur_region = self.find_entry_region();
+        while let Some(&edge) = self.edges.iter().find(|&&(r1, _)| r1 == cur_region) {
+            if result.contains(&edge) {
+                // the graph is cyclic, which is not expected for error paths. Stop here.
+                break;
+            }
+            result.push(edge);
+            cur_region = edge.1;
+        }
+        result
+    }
+
+    /// Describes a region for the notes that explain an error: by its description if it is a
+    /// universal region, by the name of the local that introduces it (e.g. "`x`") if this is a
+    /// variable of the user, otherwise by the region itself.
+    pub fn describe_region(&self, region: Region) -> String {
+        if let Some(description) = self.universal_regions_descriptions.get(&region) {
+            return description.clone();
+        }
+        match self.locals_mir_for_regions.get(&region) {
+            Some(Some(local_decl)) if local_decl.is_user_variable() =>
+                format!("`{}`", self.locals_info_for_regions[&region].1),
+            _ => match (self.captured_names_for_regions.get(&region),
+                        self.captures_for_regions.get(&region)) {
+                (Some(name), _) => format!("`{}` of the enclosing function", name),
+                (None, Some(capture_info)) =>
+                    format!("the capture of `{}` by the closure `{}`", capture_info.variable_name,
+                            capture_info.closure_name),
+                (None, None) => format!("the value with lifetime {:?}", region),
+            },
+        }
+    }
+
+    /// Describes the constraint cut of the graph (if it has one) by the lines that cause its
+    /// constraints: "breaking any one of the constraints at line(s) N, M fixes the error" if
+    /// removing a single constraint suffices, otherwise that all of them have to be broken.
+    pub fn describe_constraint_cut(&self) -> Option<String> {
+        let constraint_cut = self.constraint_cut.as_ref()?;
+        if self.cut_lines.is_empty() {
+            return None;
+        }
+        let line_numbers: Vec<String> = self.cut_lines.iter()
+            .map(|(line_number, _)| line_number.to_string())
+            .collect();
+        if constraint_cut.any_one_suffices || line_numbers.len() == 1 {
+            Some(format!("breaking any one of the constraints at line(s) {} fixes the error",
+                         line_numbers.join(", ")))
+        } else {
+            Some(format!("the constraints at lines {} all have to be broken to fix the error",
+                         line_numbers.join(", ")))
+        }
+    }
+
+    /// Describes that the error is only reported by the borrow checker of rustc (NLL), if that is
+    /// the case (see accepted_by_polonius): Polonius, the future borrow checker of Rust, accepts
+    /// the code, since the constraints of the path do not hold at the same point.
+    pub fn describe_polonius_acceptance(&self) -> Option<String> {
+        if !self.accepted_by_polonius {
+            return None;
+        }
+        Some("the current borrow checker (NLL) reports this error because it does not consider at \
+              which points the constraints hold, they may apply on different paths through the \
+              function; Polonius, the future borrow checker of Rust, accepts this code".to_owned())
+    }
+
+    /// Gives the regions of the graph in the order of the path that the graph describes, see
+    /// edges_in_path_order().
+    pub fn regions_in_path_order(&self) -> Vec<Region> {
+        let path_edges = self.edges_in_path_order();
+        path_edges.first().map(|&(region1, _)| region1).into_iter()
+            .chain(path_edges.iter().map(|&(_, region2)| region2))
+            .collect()
+    }
+
     /// This method will improve the graph that it is called on to make it more readable and
     /// understandable. However, "improving" is somewhat subtle and subjective.
     /// What this method does is removing nodes, and hence regions.
     /// More exactly, it will remove all regions that either are not associated with a local
     /// or that are associated with a local that has no name, hence that is an anonymous variable.
+    /// (Regions of closures that are linked to a variable of the enclosing function, regions of
+    /// captures of variables by closures and universal regions are kept.)
     /// However, the first and the last node in the graph (that actually is a path) will never be
     /// removed. (These regions are found by using the find_entry_region and find_exit_region
     /// methods) The mapping to locals is taken from the locals_for_regions field of self, so it
@@ -826,7 +2663,8 @@ impl<'tcx> EnrichedErrorGraph<'tcx> {
     /// the set of edges, i.e. it will remove the edges that contain unneeded regions and replace
     /// them with direct edges that connect all previous and posteriors nodes of the removed
     /// node without going over the removed node anymore.
-    /// In addition, this method will add an entry to lines_for_edges for all newly created edges.
+    /// In addition, this method will add an entry to lines_for_edges (and spans_for_edges) for all
+    /// newly created edges.
     /// If two edges are merged (as described before), the information form the first of these tow
     /// edges is inserted as information for the newly created edge. If this information is not
     /// equal to the one of the second edged (based on the line number), a debug message will be
@@ -845,6 +2683,7 @@ impl<'tcx> EnrichedErrorGraph<'tcx> {
 
         let mut new_edges = self.edges.clone();
         let mut new_lines_for_edges = self.lines_for_edges.clone();
+        let mut new_spans_for_edges = self.spans_for_edges.clone();
 
         /// helper closure that removes regions from the graph by manipulating new_edges.
         /// will never remove entry or exit nodes/regions of a graph, these are ignored.
@@ -871,6 +2710,8 @@ impl<'tcx> EnrichedErrorGraph<'tcx> {
                             first edge.", r1, reg, reg, r2);
                         }
                         new_lines_for_edges.insert((*r1, *r2), (*in_line_info_nr, in_line_info_src.clone()));
+                        let in_span = new_spans_for_edges[&(*r1, *reg)];
+                        new_spans_for_edges.insert((*r1, *r2), in_span);
                     }
                 }
             }
@@ -878,13 +2719,59 @@ impl<'tcx> EnrichedErrorGraph<'tcx> {
         };
 
         for (reg, local_decl_opt) in self.locals_mir_for_regions.iter() {
+            if self.captured_names_for_regions.contains_key(reg) ||
+                    self.captures_for_regions.contains_key(reg) ||
+                    self.universal_regions_descriptions.contains_key(reg) {
+                continue;
+            }
             match local_decl_opt {
                 None => remove_region_from_edges(reg),
-                Some(local_decl) => if local_decl.name.is_none() { remove_region_from_edges(reg) }
+                Some(local_decl) => if !local_decl.is_user_variable() { remove_region_from_edges(reg) }
                                  // else: keep this region
             }
         }
         self.edges = new_edges;
         self.lines_for_edges = new_lines_for_edges;
+        self.spans_for_edges = new_spans_for_edges;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{code_match_rank, match_greedily};
+    use error_codes::RustcErrorCode;
+
+    #[test]
+    fn the_codes_are_ranked() {
+        assert_eq!(code_match_rank(Some(RustcErrorCode::E0499), Some("E0499")), 0);
+        assert_eq!(code_match_rank(None, Some("E0499")), 1);
+        assert_eq!(code_match_rank(Some(RustcErrorCode::E0499), None), 1);
+        assert_eq!(code_match_rank(Some(RustcErrorCode::E0499), Some("E0502")), 2);
+    }
+
+    #[test]
+    fn the_errors_are_matched_one_to_one() {
+        // the errors 0 and 1 are both on the line of the error 0 of rustc, but only the code of
+        // error 1 matches, so error 0 is only reported by Polonius (even though it is closer). The
+        // error 1 of rustc is not on the line of any error, it is only reported by rustc.
+        let candidates = vec![((2, (0, 0)), 0, 0), ((0, (0, 4)), 1, 0)];
+        let matches = match_greedily(candidates);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches.get(&1), Some(&0));
+        assert!(!matches.contains_key(&0));
+        assert!(!matches.values().any(|&rustc_index| rustc_index == 1));
+    }
+
+    #[test]
+    fn a_matched_error_is_not_matched_again() {
+        // error 0 matches both errors of rustc, error 1 only the first one. Greedily, error 0 takes
+        // the first one (the best pair), and then neither takes the second one: error 0 is matched
+        // already, and error 1 does not match it. Hence error 1 is only reported by Polonius, and
+        // the second error of rustc only by rustc.
+        let candidates = vec![((1, (0, 0)), 0, 0), ((1, (0, 3)), 0, 1), ((1, (0, 1)), 1, 0)];
+        let matches = match_greedily(candidates);
+        assert_eq!(matches.get(&0), Some(&0));
+        assert!(!matches.contains_key(&1));
+        assert!(!matches.values().any(|&rustc_index| rustc_index == 1));
     }
 }
@@ -0,0 +1,310 @@
+// © 2020, ETH Zurich
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+/// Code for classifying the errors that Polonius reports into the error codes that rustc uses for
+/// them. Polonius only tells that a loan is invalidated at a point while it is still live, the kind
+/// of the error (and hence its code) is given by the kind of the loan and by the MIR statement (or
+/// terminator) that invalidates it.
+/// The classification follows the one of rustc (see rustc_borrowck::borrow_set and
+/// rustc_borrowck::conflict_errors), but only as far as it can be derived from the MIR, without
+/// the full borrow checker state. Hence it is a best effort, errors that cannot be classified get
+/// no code.
+
+use dump_borrowck_info::serde_derive;
+use rustc_hir::Mutability;
+use rustc_middle::mir;
+
+/// An error code of the borrow checker of rustc, see the documentation of the codes (e.g.
+/// `rustc --explain E0499`) for the details. It is serialized as the code itself, e.g. "E0499".
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde_derive::Serialize)]
+pub enum RustcErrorCode {
+    /// A variable is borrowed mutably while it is already borrowed mutably.
+    E0499,
+    /// A variable is borrowed mutably while it is already borrowed immutably, or vice versa.
+    E0502,
+    /// A value is used while it is borrowed mutably.
+    E0503,
+    /// A value is moved out while it is borrowed.
+    E0505,
+    /// A value is assigned to while it is borrowed.
+    E0506,
+    /// A borrowed value does not live long enough, i.e. it is dropped while it is still borrowed.
+    E0597,
+    /// A borrow may still be in use when the destructor of the value that it borrows from runs.
+    E0713,
+    /// A temporary value is dropped while it is still borrowed.
+    E0716,
+    /// A value is used after it may have been moved. (This is no loan error, but a move error.)
+    E0382,
+}
+
+impl RustcErrorCode {
+    /// Gives the code as a string, e.g. "E0499".
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RustcErrorCode::E0499 => "E0499",
+            RustcErrorCode::E0502 => "E0502",
+            RustcErrorCode::E0503 => "E0503",
+            RustcErrorCode::E0505 => "E0505",
+            RustcErrorCode::E0506 => "E0506",
+            RustcErrorCode::E0597 => "E0597",
+            RustcErrorCode::E0713 => "E0713",
+            RustcErrorCode::E0716 => "E0716",
+            RustcErrorCode::E0382 => "E0382",
+        }
+    }
+
+    /// Gives a short description of the kind of error, in the words of rustc's error messages.
+    pub fn description(self) -> &'static str {
+        match self {
+            RustcErrorCode::E0499 => "cannot borrow as mutable more than once at a time",
+            RustcErrorCode::E0502 => "cannot borrow as mutable because it is also borrowed as \
+                                      immutable (or vice versa)",
+            RustcErrorCode::E0503 => "cannot use a value because it was mutably borrowed",
+            RustcErrorCode::E0505 => "cannot move out of a value because it is borrowed",
+            RustcErrorCode::E0506 => "cannot assign to a value because it is borrowed",
+            RustcErrorCode::E0597 => "borrowed value does not live long enough",
+            RustcErrorCode::E0713 => "borrow may still be in use when destructor runs",
+            RustcErrorCode::E0716 => "temporary value dropped while borrowed",
+            RustcErrorCode::E0382 => "use of moved value",
+        }
+    }
+}
+
+/// The ways in which a statement (or terminator) accesses a place that conflicts with the borrowed
+/// place of a loan, as far as they matter for the classification of the error.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Access {
+    /// The place is borrowed again, with the given mutability.
+    Borrow(Mutability),
+    /// A new value is assigned to the place.
+    Assign,
+    /// The value is moved out of the place.
+    Move,
+    /// The value is copied out of the place.
+    Copy,
+}
+
+/// Classifies the invalidation of a loan into the matching error code of rustc. The loan is given
+/// by the location where it is created, the invalidation by the location of the error (both
+/// in the passed body, which must be the one that the facts were computed for).
+/// Returns None if the location of the loan is no borrow, or the statement at the location of the
+/// error does not access the borrowed place in a way that is known to invalidate the loan.
+pub fn classify_loan_error(body: &mir::Body<'_>, loan_location: mir::Location,
+                           error_location: mir::Location) -> Option<RustcErrorCode> {
+    let (loan_kind, borrowed_place) = find_borrow(body, loan_location)?;
+    let loan_is_mut = loan_kind.mutability() == Mutability::Mut;
+    debug!("[classify_loan_error] loan {:?} of {:?}, error at {:?}", loan_kind, borrowed_place,
+           error_location);
+
+    let block_data = &body[error_location.block];
+    if error_location.statement_index < block_data.statements.len() {
+        match block_data.statements[error_location.statement_index].kind {
+            mir::StatementKind::Assign(box (ref assigned_place, ref rvalue)) => {
+                if let mir::Rvalue::Ref(_, kind, ref place) = *rvalue {
+                    if places_conflict(place, &borrowed_place) {
+                        return classify_access(Access::Borrow(kind.mutability()), loan_is_mut);
+                    }
+                }
+                if places_conflict(assigned_place, &borrowed_place) {
+                    return classify_access(Access::Assign, loan_is_mut);
+                }
+                let mut operands = Vec::new();
+                collect_operands(rvalue, &mut operands);
+                classify_use_of_operands(&operands, &borrowed_place, loan_is_mut)
+            },
+            mir::StatementKind::StorageDead(local) if local == borrowed_place.local =>
+                Some(classify_drop(body, &borrowed_place)),
+            _ => None,
+        }
+    } else {
+        match block_data.terminator().kind {
+            mir::TerminatorKind::Drop { ref place, .. } if place.local == borrowed_place.local =>
+                Some(classify_drop(body, &borrowed_place)),
+            mir::TerminatorKind::Call { ref func, ref args, ref destination, .. } => {
+                if places_conflict(destination, &borrowed_place) {
+                    return classify_access(Access::Assign, loan_is_mut);
+                }
+                let operands: Vec<&mir::Operand> = std::iter::once(func)
+                    .chain(args.iter().map(|arg| &arg.node))
+                    .collect();
+                classify_use_of_operands(&operands, &borrowed_place, loan_is_mut)
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Classifies an access to (a place that conflicts with) the borrowed place of a loan, given by
+/// whether the loan is mutable: A second mutable borrow gives E0499, a mutable and an immutable one
+/// E0502, an assignment E0506, moving out E0505 and copying out of a mutable borrow E0503. Two
+/// immutable borrows, and copying out of an immutable borrow, do not conflict.
+fn classify_access(access: Access, loan_is_mut: bool) -> Option<RustcErrorCode> {
+    match access {
+        Access::Borrow(Mutability::Mut) if loan_is_mut => Some(RustcErrorCode::E0499),
+        Access::Borrow(Mutability::Mut) => Some(RustcErrorCode::E0502),
+        Access::Borrow(Mutability::Not) if loan_is_mut => Some(RustcErrorCode::E0502),
+        Access::Borrow(Mutability::Not) => None,
+        Access::Assign => Some(RustcErrorCode::E0506),
+        Access::Move => Some(RustcErrorCode::E0505),
+        Access::Copy if loan_is_mut => Some(RustcErrorCode::E0503),
+        Access::Copy => None,
+    }
+}
+
+/// Gives the kind of the borrow and the borrowed place of the loan that is created at the given
+/// location, if there is an assignment of a reference.
+pub fn find_borrow<'tcx>(body: &mir::Body<'tcx>, location: mir::Location)
+        -> Option<(mir::BorrowKind, mir::Place<'tcx>)> {
+    let statement = body[location.block].statements.get(location.statement_index)?;
+    match statement.kind {
+        mir::StatementKind::Assign(box (_, mir::Rvalue::Ref(_, kind, place))) => Some((kind, place)),
+        _ => None,
+    }
+}
+
+/// Classifies the drop of the local of a borrowed place (by StorageDead or a Drop terminator), see
+/// classify_drop_of_projections().
+fn classify_drop<'tcx>(body: &mir::Body<'tcx>, borrowed_place: &mir::Place<'tcx>)
+        -> RustcErrorCode {
+    classify_drop_of_projections(borrowed_place.projection,
+                                 body.local_decls[borrowed_place.local].is_user_variable())
+}
+
+/// Classifies the drop of the local of a borrowed place, given by the projections of the place and
+/// whether the local is a variable of the user. If the borrow goes through a dereference, it
+/// borrows data that the local only refers to, so the drop can only invalidate it by running a
+/// destructor (E0713). Otherwise, the borrowed value itself is dropped, which is a temporary
+/// (E0716) unless it is a variable of the user (E0597).
+fn classify_drop_of_projections<V, T>(projection: &[mir::ProjectionElem<V, T>],
+                                      is_user_variable: bool) -> RustcErrorCode {
+    if projection.iter().any(|elem| matches!(elem, mir::ProjectionElem::Deref)) {
+        RustcErrorCode::E0713
+    } else if is_user_variable {
+        RustcErrorCode::E0597
+    } else {
+        RustcErrorCode::E0716
+    }
+}
+
+/// Classifies the use of the operands of an invalidating statement, see classify_access(). The
+/// first operand that is moved or copied out of the borrowed place in a conflicting way gives the
+/// error code.
+fn classify_use_of_operands<'tcx>(operands: &[&mir::Operand<'tcx>],
+                                  borrowed_place: &mir::Place<'tcx>, loan_is_mut: bool)
+        -> Option<RustcErrorCode> {
+    operands.iter()
+        .filter_map(|operand| match **operand {
+            mir::Operand::Move(ref place) if places_conflict(place, borrowed_place) =>
+                Some(Access::Move),
+            mir::Operand::Copy(ref place) if places_conflict(place, borrowed_place) =>
+                Some(Access::Copy),
+            _ => None,
+        })
+        .find_map(|access| classify_access(access, loan_is_mut))
+}
+
+/// Collects the operands of an rvalue.
+fn collect_operands<'a, 'tcx>(rvalue: &'a mir::Rvalue<'tcx>,
+                              operands: &mut Vec<&'a mir::Operand<'tcx>>) {
+    match *rvalue {
+        mir::Rvalue::Use(ref operand) |
+        mir::Rvalue::Repeat(ref operand, _) |
+        mir::Rvalue::Cast(_, ref operand, _) |
+        mir::Rvalue::UnaryOp(_, ref operand) |
+        mir::Rvalue::ShallowInitBox(ref operand, _) => operands.push(operand),
+        mir::Rvalue::BinaryOp(_, box (ref operand1, ref operand2)) => {
+            operands.push(operand1);
+            operands.push(operand2);
+        },
+        mir::Rvalue::Aggregate(_, ref aggregate_operands) => operands.extend(aggregate_operands.iter()),
+        _ => {},
+    }
+}
+
+/// Tells if the two places may overlap, i.e. if they have the same local and the projections of
+/// one of them are a prefix of the ones of the other, see projections_conflict().
+fn places_conflict<'tcx>(place1: &mir::Place<'tcx>, place2: &mir::Place<'tcx>) -> bool {
+    place1.local == place2.local && projections_conflict(place1.projection, place2.projection)
+}
+
+/// Tells if the projections of two places of the same local may overlap, i.e. if the ones of one
+/// place are a prefix of the ones of the other. (This is a simplification of the check of rustc,
+/// which also takes e.g. indices into account. Different fields are disjoint either way.)
+fn projections_conflict<V: PartialEq, T: PartialEq>(projection1: &[mir::ProjectionElem<V, T>],
+                                                    projection2: &[mir::ProjectionElem<V, T>])
+        -> bool {
+    projection1.iter().zip(projection2.iter()).all(|(elem1, elem2)| elem1 == elem2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{classify_access, classify_drop_of_projections, projections_conflict, Access,
+                RustcErrorCode};
+    use rustc_hir::Mutability;
+    use rustc_middle::mir::{Local, ProjectionElem};
+    use rustc_target::abi::FieldIdx;
+
+    /// A projection element without types, which are not needed for comparing projections.
+    type Elem = ProjectionElem<Local, ()>;
+
+    fn field(index: u32) -> Elem {
+        ProjectionElem::Field(FieldIdx::from_u32(index), ())
+    }
+
+    #[test]
+    fn accesses_are_classified_like_rustc_does() {
+        let table = [
+            (Access::Borrow(Mutability::Mut), true, Some(RustcErrorCode::E0499)),
+            (Access::Borrow(Mutability::Mut), false, Some(RustcErrorCode::E0502)),
+            (Access::Borrow(Mutability::Not), true, Some(RustcErrorCode::E0502)),
+            (Access::Borrow(Mutability::Not), false, None),
+            (Access::Assign, true, Some(RustcErrorCode::E0506)),
+            (Access::Assign, false, Some(RustcErrorCode::E0506)),
+            (Access::Move, true, Some(RustcErrorCode::E0505)),
+            (Access::Move, false, Some(RustcErrorCode::E0505)),
+            (Access::Copy, true, Some(RustcErrorCode::E0503)),
+            (Access::Copy, false, None),
+        ];
+        for &(access, loan_is_mut, expected) in table.iter() {
+            assert_eq!(classify_access(access, loan_is_mut), expected,
+                       "{:?} of a loan that is mutable: {}", access, loan_is_mut);
+        }
+    }
+
+    #[test]
+    fn drops_are_classified_by_deref_and_user_variable() {
+        let table: [(&[Elem], bool, RustcErrorCode); 5] = [
+            (&[ProjectionElem::Deref], true, RustcErrorCode::E0713),
+            (&[field(0), ProjectionElem::Deref], false, RustcErrorCode::E0713),
+            (&[], true, RustcErrorCode::E0597),
+            (&[field(1)], true, RustcErrorCode::E0597),
+            (&[], false, RustcErrorCode::E0716),
+        ];
+        for &(projection, is_user_variable, expected) in table.iter() {
+            assert_eq!(classify_drop_of_projections(projection, is_user_variable), expected,
+                       "{:?} of a user variable: {}", projection, is_user_variable);
+        }
+    }
+
+    #[test]
+    fn projections_conflict_if_one_is_a_prefix_of_the_other() {
+        let table: [(&[Elem], &[Elem], bool); 6] = [
+            (&[], &[], true),
+            (&[], &[field(0)], true),
+            (&[field(0)], &[field(0), ProjectionElem::Deref], true),
+            (&[ProjectionElem::Deref, field(1)], &[ProjectionElem::Deref], true),
+            (&[field(0)], &[field(1)], false),
+            (&[field(0), field(1)], &[field(0), field(2)], false),
+        ];
+        for &(projection1, projection2, expected) in table.iter() {
+            assert_eq!(projections_conflict(projection1, projection2), expected,
+                       "{:?} and {:?}", projection1, projection2);
+            assert_eq!(projections_conflict(projection2, projection1), expected,
+                       "{:?} and {:?}", projection2, projection1);
+        }
+    }
+}
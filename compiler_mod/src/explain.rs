@@ -0,0 +1,348 @@
+// © 2020, ETH Zurich
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+/// Code for turning the graphs that explain errors into prose, i.e. into a list of numbered
+/// sentences that follow the path of the graph, e.g.
+/// "1. `a` borrows `v` at line 5. 2. `b` may point to the same data as `a` because of line 7 ..."
+/// The explanation can be rendered as plain text or as Markdown, so that any consumer (and not only
+/// the VS Code extension, that has its own rendering in TextualVisualization) can show it.
+
+use dump_borrowck_info::EnrichedErrorGraph;
+use move_errors::MoveErrorGraph;
+
+/// The formats that an explanation can be rendered in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExplanationFormat {
+    /// Plain text, the title is underlined and the relevant lines are indented.
+    Text,
+    /// Markdown, the title is a heading and the relevant lines are a code block.
+    Markdown,
+}
+
+impl ExplanationFormat {
+    /// Parses the format as it is given on the command line, i.e. `text` or `markdown` (or `md`).
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "text" | "txt" => Ok(ExplanationFormat::Text),
+            "markdown" | "md" => Ok(ExplanationFormat::Markdown),
+            _ => Err(format!("invalid explanation format `{}`, expected `text` or `markdown`",
+                             value)),
+        }
+    }
+
+    /// Gives the extension of the files that explanations in this format are written to.
+    pub fn file_extension(self) -> &'static str {
+        match self {
+            ExplanationFormat::Text => "txt",
+            ExplanationFormat::Markdown => "md",
+        }
+    }
+}
+
+/// The explanation of an error as a list of sentences, together with the source lines that they
+/// refer to.
+pub struct Explanation {
+    /// Tells which error is explained, e.g. "error[E0502] in `foo` at line 12".
+    pub title: String,
+    /// The sentences, in the order in which they shall be read (and numbered).
+    pub sentences: Vec<String>,
+    /// The source lines that the sentences refer to, given by their number and their source code,
+    /// sorted by line number and without duplicates.
+    pub lines: Vec<(usize, String)>,
+}
+
+impl Explanation {
+    fn new(title: String) -> Self {
+        Explanation {
+            title,
+            sentences: Vec::new(),
+            lines: Vec::new(),
+        }
+    }
+
+    /// Adds a sentence. It is capitalized and gets a full stop, so it can be written like a note
+    /// (e.g. "`x` may point to ...").
+    fn add_sentence(&mut self, sentence: String) {
+        let mut chars = sentence.chars();
+        let mut sentence = match chars.next() {
+            Some(first) => first.to_uppercase().chain(chars).collect::<String>(),
+            None => return,
+        };
+        if !sentence.ends_with('.') {
+            sentence.push('.');
+        }
+        self.sentences.push(sentence);
+    }
+
+    /// Adds a source line that the sentences refer to. Lines without a valid number (which are
+    /// used for information that was not found) are ignored.
+    fn add_line(&mut self, line: &(usize, String)) {
+        if line.0 == 0 || line.0 == usize::MAX {
+            return;
+        }
+        if let Err(index) = self.lines.binary_search_by_key(&line.0, |&(line_number, _)| line_number) {
+            self.lines.insert(index, (line.0, line.1.trim_end().to_string()));
+        }
+    }
+
+    /// Renders the explanation in the given format: The title, followed by the numbered sentences
+    /// and the relevant source lines.
+    pub fn render(&self, format: ExplanationFormat) -> String {
+        let mut rendered = String::new();
+        match format {
+            ExplanationFormat::Text => {
+                rendered.push_str(&format!("{}\n{}\n\n", self.title, "=".repeat(self.title.len())));
+            },
+            ExplanationFormat::Markdown => {
+                rendered.push_str(&format!("### {}\n\n", self.title));
+            },
+        }
+        for (index, sentence) in self.sentences.iter().enumerate() {
+            rendered.push_str(&format!("{}. {}\n", index + 1, sentence));
+        }
+        if self.lines.is_empty() {
+            return rendered;
+        }
+        let number_width = self.lines.iter()
+            .map(|(line_number, _)| line_number.to_string().len())
+            .max()
+            .unwrap_or(0);
+        rendered.push_str("\nRelevant lines:\n\n");
+        if format == ExplanationFormat::Markdown {
+            rendered.push_str("```rust\n");
+        }
+        for (line_number, line) in self.lines.iter() {
+            let indentation = if format == ExplanationFormat::Text { "    " } else { "" };
+            rendered.push_str(&format!("{}{:>width$} | {}\n", indentation, line_number, line,
+                                       width = number_width));
+        }
+        if format == ExplanationFormat::Markdown {
+            rendered.push_str("```\n");
+        }
+        rendered
+    }
+}
+
+/// Explains the error that an (improved) EnrichedErrorGraph describes. For an invalidated loan, the
+/// sentences start with the borrow, follow the constraints of the path (each one tells which value
+/// may point to the data of the previous one, and which line causes this), and end with the
+/// invalidation of the borrowed data and the later use of the borrow that keeps it alive. For a
+/// required subset, they start with the requirement and end with the signature of the function,
+/// that does not declare it. Captures of variables by closures and universal regions on the path
+/// are explained in between.
+pub fn explain_error_graph(error_graph: &EnrichedErrorGraph) -> Explanation {
+    let code = error_graph.error_code.map_or_else(String::new,
+                                                  |code| format!("[{}]", code.as_str()));
+    let mut explanation = Explanation::new(format!("error{} in `{}` at line {}", code,
+                                                   error_graph.function_name,
+                                                   error_graph.error_line.0));
+    if let Some(error_code) = error_graph.error_code {
+        explanation.add_sentence(format!("rustc reports this as {}: {}", error_code.as_str(),
+                                         error_code.description()));
+    }
+    let regions = error_graph.regions_in_path_order();
+    let borrowed = error_graph.borrowed_name.as_ref()
+        .map_or_else(|| "the borrowed data".to_string(), |name| format!("`{}`", name));
+
+    if let (Some(_), Some(entry_region)) = (error_graph.error_loan, regions.first()) {
+        match error_graph.loan_line {
+            Some(ref loan_line) => {
+                explanation.add_sentence(format!("{} borrows {} at line {}",
+                                                 error_graph.describe_region(*entry_region),
+                                                 borrowed, loan_line.0));
+                explanation.add_line(loan_line);
+            },
+            None => explanation.add_sentence(format!("{} borrows {}",
+                                                     error_graph.describe_region(*entry_region),
+                                                     borrowed)),
+        }
+    }
+    if let Some((region1, region2)) = error_graph.required_subset {
+        explanation.add_sentence(format!("the function requires that {} outlives {} at line {}",
+                                         error_graph.describe_region(region1),
+                                         error_graph.describe_region(region2),
+                                         error_graph.error_line.0));
+    }
+
+    for (region1, region2) in error_graph.edges_in_path_order() {
+        let description1 = error_graph.describe_region(region1);
+        let description2 = error_graph.describe_region(region2);
+        // sentences like "`x` may point to the same data as `x`" do not explain anything.
+        if description1 == description2 {
+            continue;
+        }
+        let sentence = format!("{} may point to the same data as {}", description2, description1);
+        match error_graph.lines_for_edges.get(&(region1, region2)) {
+            Some(line) if line.0 != usize::MAX => {
+                explanation.add_sentence(format!("{} because of line {} (`{}`)", sentence, line.0,
+                                                 line.1.trim()));
+                explanation.add_line(line);
+            },
+            _ => explanation.add_sentence(sentence),
+        }
+    }
+
+    let mut described_captures = Vec::new();
+    for region in regions.iter() {
+        if let Some(capture_info) = error_graph.captures_for_regions.get(region) {
+            let description = capture_info.describe();
+            if !described_captures.contains(&description) {
+                explanation.add_line(&capture_info.creation_line);
+                if let Some(ref call_line) = capture_info.call_line {
+                    explanation.add_line(call_line);
+                }
+                explanation.add_sentence(description.clone());
+                described_captures.push(description);
+            }
+        }
+    }
+
+    explanation.add_line(&error_graph.error_line);
+    match (error_graph.error_loan, error_graph.required_subset) {
+        (Some(_), _) => {
+            let exit_region = regions.last().cloned();
+            let exit_description = exit_region
+                .map_or_else(|| "the borrow".to_string(),
+                             |region| error_graph.describe_region(region));
+            explanation.add_sentence(format!("{} is invalidated at line {} (`{}`), while {} may \
+                                              still point to it", borrowed,
+                                             error_graph.error_line.0,
+                                             error_graph.error_line.1.trim(), exit_description));
+            if let Some(ref liveness_trace) = error_graph.liveness_trace {
+                explanation.add_line(&liveness_trace.use_line);
+                explanation.add_sentence(liveness_trace.describe());
+            }
+            if let Some(universal_description) = exit_region
+                    .and_then(|region| error_graph.universal_regions_descriptions.get(&region)) {
+                explanation.add_sentence(format!("{} is part of the signature of the function, so \
+                                                  it lasts until after the function returns",
+                                                 universal_description));
+            }
+        },
+        (None, Some((region1, region2))) => {
+            let universal_descriptions = (
+                error_graph.universal_regions_descriptions.get(&region1),
+                error_graph.universal_regions_descriptions.get(&region2));
+            if let (Some(description1), Some(description2)) = universal_descriptions {
+                explanation.add_sentence(format!("the signature of the function does not require \
+                                                  that {} outlives {}", description1,
+                                                 description2));
+            }
+        },
+        (None, None) => {},
+    }
+
+    if let Some(cut_description) = error_graph.describe_constraint_cut() {
+        for line in error_graph.cut_lines.iter() {
+            explanation.add_line(line);
+        }
+        explanation.add_sentence(cut_description);
+    }
+    if let Some(acceptance_description) = error_graph.describe_polonius_acceptance() {
+        explanation.add_sentence(acceptance_description);
+    }
+    explanation
+}
+
+/// Explains the use of a moved value that a MoveErrorGraph describes, by its notes (see
+/// MoveErrorGraph::explanation_notes()).
+pub fn explain_move_error_graph(move_error_graph: &MoveErrorGraph) -> Explanation {
+    let mut explanation = Explanation::new(format!("error[{}] in `{}` at line {}",
+                                                   move_error_graph.error_code.as_str(),
+                                                   move_error_graph.function_name,
+                                                   move_error_graph.use_line.0));
+    explanation.add_sentence(format!("rustc reports this as {}: {}",
+                                     move_error_graph.error_code.as_str(),
+                                     move_error_graph.error_code.description()));
+    for note in move_error_graph.explanation_notes() {
+        explanation.add_sentence(note);
+    }
+    for line in move_error_graph.lines_on_path.iter() {
+        explanation.add_line(line);
+    }
+    explanation
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{explain_error_graph, ExplanationFormat};
+    use dump_borrowck_info::EnrichedErrorGraph;
+    use error_codes::RustcErrorCode;
+    use facts::{Loan, Region};
+    use liveness::LivenessTrace;
+
+    /// The graph of an error where `v` is borrowed at line 3, the borrow flows over two
+    /// constraints (the second one has no line), and `v` is invalidated at line 5 while the borrow
+    /// is used later at line 6.
+    fn error_graph() -> EnrichedErrorGraph<'static> {
+        let mut error_graph = EnrichedErrorGraph::new_for_test(
+            "foo", (5, "    v.push(1);"), &[
+                (Region::from(1), Region::from(2), (4, "    let s = r;")),
+                (Region::from(2), Region::from(3), (usize::MAX, "")),
+            ]);
+        error_graph.error_loan = Some(Loan::from(0));
+        error_graph.error_code = Some(RustcErrorCode::E0502);
+        error_graph.loan_line = Some((3, "    let r = &v;".to_string()));
+        error_graph.borrowed_name = Some("v".to_string());
+        error_graph.liveness_trace = Some(LivenessTrace {
+            region: Region::from(3),
+            variable_name: "`s`".to_string(),
+            is_drop: false,
+            error_line: (5, "    v.push(1);".to_string()),
+            use_line: (6, "    s.len();".to_string()),
+            lines_on_path: Vec::new(),
+        });
+        error_graph
+    }
+
+    const SENTENCES: &str = "\
+1. Rustc reports this as E0502: cannot borrow as mutable because it is also borrowed as immutable \
+(or vice versa).
+2. The value with lifetime R1 borrows `v` at line 3.
+3. The value with lifetime R2 may point to the same data as the value with lifetime R1 because of \
+line 4 (`let s = r;`).
+4. The value with lifetime R3 may point to the same data as the value with lifetime R2.
+5. `v` is invalidated at line 5 (`v.push(1);`), while the value with lifetime R3 may still point \
+to it.
+6. The borrow is still live at line 5, because `s` is used at line 6 (`s.len();`) and may still \
+point to it.
+";
+
+    #[test]
+    fn the_explanation_is_rendered_as_numbered_text() {
+        let rendered = explain_error_graph(&error_graph()).render(ExplanationFormat::Text);
+        assert_eq!(rendered, format!("\
+error[E0502] in `foo` at line 5
+===============================
+
+{}
+Relevant lines:
+
+    3 |     let r = &v;
+    4 |     let s = r;
+    5 |     v.push(1);
+    6 |     s.len();
+", SENTENCES));
+    }
+
+    #[test]
+    fn the_explanation_is_rendered_as_markdown() {
+        let rendered = explain_error_graph(&error_graph()).render(ExplanationFormat::Markdown);
+        assert_eq!(rendered, format!("\
+### error[E0502] in `foo` at line 5
+
+{}
+Relevant lines:
+
+```rust
+3 |     let r = &v;
+4 |     let s = r;
+5 |     v.push(1);
+6 |     s.len();
+```
+", SENTENCES));
+    }
+}
@@ -3,33 +3,48 @@
 /// This code was adapted from the
 /// [Polonius](https://github.com/rust-lang-nursery/polonius/blob/master/src/facts.rs)
 /// source code.
-
-use dump_borrowck_info::csv::ReaderBuilder;
+///
+/// Normally, the facts are taken straight from the borrow checker (see borrowck_facts), and are
+/// converted by FactLoader::load_facts_from_compiler. Alternatively, they can be read from the TSV
+/// files that rustc writes when -Znll-facts is passed. This is only intended for debugging.
+/// The regular expressions that are needed to parse the files are compiled only once, since
+/// compiling them again for every row was needlessly slow. A file that is missing or has a row
+/// that cannot be parsed is reported as FactLoadError, instead of panicking.
+
+use dump_borrowck_info::csv::{self, ReaderBuilder, StringRecord};
 use dump_borrowck_info::regex::Regex;
-use rustc::mir;
-use dump_borrowck_info::rustc_data_structures::indexed_vec::Idx;
+use rustc_borrowck::consumers::{LocationTable, PoloniusInput, RichLocation, RustcFacts};
+use rustc_middle::mir;
 use dump_borrowck_info::serde::de::DeserializeOwned;
 use dump_borrowck_info::serde_derive;
 use std::collections::HashMap;
+use std::error::Error;
 use std::hash::Hash;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::fmt;
 
 use dump_borrowck_info::polonius_engine;
 
+lazy_static! {
+    static ref REGION_REGEX: Regex = Regex::new(r"^'\?(?P<id>\d+)$").unwrap();
+    static ref LOAN_REGEX: Regex = Regex::new(r"^bw(?P<id>\d+)$").unwrap();
+    static ref VARIABLE_REGEX: Regex = Regex::new(r"^_(?P<id>\d+)$").unwrap();
+    static ref PATH_REGEX: Regex = Regex::new(r"^mp(?P<id>\d+)$").unwrap();
+    static ref POINT_REGEX: Regex =
+        Regex::new(r"^(?P<type>Mid|Start)\(bb(?P<bb>\d+)\[(?P<stmt>\d+)\]\)$").unwrap();
+}
 
 /// Macro for declaring index types for referencing interned facts.
 macro_rules! index_type {
-    ($typ:ident, $debug_str:ident) => {
+    ($(#[$attr:meta])* $typ:ident, $debug_str:ident) => {
+        $(#[$attr])*
         #[derive(Ord, PartialOrd, Eq, PartialEq, Clone, Copy, Hash, serde_derive::Serialize)]
         pub struct $typ(usize);
 
         impl From<usize> for $typ {
             fn from(index: usize) -> $typ {
-                $typ {
-                    0: index,
-                }
+                $typ(index)
             }
         }
 
@@ -53,39 +68,113 @@ macro_rules! index_type {
     };
 }
 
-index_type!(PointIndex, P);
-/// A unique identifier of a loan.
-index_type!(Loan, L);
-/// A unique identifier of a region.
-index_type!(Region, R);
+index_type!(
+    /// A unique identifier of a point, i.e. of the start or the middle of a statement of the MIR.
+    PointIndex, P);
+index_type!(
+    /// A unique identifier of a loan.
+    Loan, L);
+index_type!(
+    /// A unique identifier of a region.
+    Region, R);
+index_type!(
+    /// A unique identifier of a variable, i.e. of a local of the MIR.
+    Variable, V);
+index_type!(
+    /// A unique identifier of a move path.
+    MovePath, Mp);
+
+/// The types of the atoms of the facts that are used by rust-life.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RustLifeFacts;
+
+impl polonius_engine::FactTypes for RustLifeFacts {
+    type Origin = Region;
+    type Loan = Loan;
+    type Point = PointIndex;
+    type Variable = Variable;
+    type Path = MovePath;
+}
+
+/// The type of the points in the facts that rustc provides.
+type RustcPoint = <RustcFacts as polonius_engine::FactTypes>::Point;
 
 impl FromStr for Region {
 
-    type Err = ();
+    type Err = MalformedAtomError;
 
     fn from_str(region: &str) -> Result<Self, Self::Err> {
-        let re = Regex::new(r"^\\'_#(?P<id>\d+)r$").unwrap();
-        let caps = re.captures(region).unwrap();
-        let id: usize = caps["id"].parse().unwrap();
-        Ok(Self {
-            0: id,
-        })
+        let caps = REGION_REGEX.captures(region)
+            .ok_or_else(|| MalformedAtomError::new(stringify!(Region), region))?;
+        let id: usize = caps["id"].parse()
+            .map_err(|_| MalformedAtomError::new(stringify!(Region), region))?;
+        Ok(Self(id))
     }
 }
 
 impl FromStr for Loan {
 
-    type Err = ();
+    type Err = MalformedAtomError;
 
     fn from_str(loan: &str) -> Result<Self, Self::Err> {
-        let re = Regex::new(r"^bw(?P<id>\d+)$").unwrap();
-        let caps = re.captures(loan).unwrap();
-        let id: usize = caps["id"].parse().unwrap();
-        Ok(Self {
-            0: id,
-        })
+        let caps = LOAN_REGEX.captures(loan)
+            .ok_or_else(|| MalformedAtomError::new(stringify!(Loan), loan))?;
+        let id: usize = caps["id"].parse()
+            .map_err(|_| MalformedAtomError::new(stringify!(Loan), loan))?;
+        Ok(Self(id))
+    }
+
+}
+
+impl FromStr for Variable {
+
+    type Err = MalformedAtomError;
+
+    fn from_str(variable: &str) -> Result<Self, Self::Err> {
+        let caps = VARIABLE_REGEX.captures(variable)
+            .ok_or_else(|| MalformedAtomError::new(stringify!(Variable), variable))?;
+        let id: usize = caps["id"].parse()
+            .map_err(|_| MalformedAtomError::new(stringify!(Variable), variable))?;
+        Ok(Self(id))
+    }
+
+}
+
+impl FromStr for MovePath {
+
+    type Err = MalformedAtomError;
+
+    fn from_str(path: &str) -> Result<Self, Self::Err> {
+        let caps = PATH_REGEX.captures(path)
+            .ok_or_else(|| MalformedAtomError::new(stringify!(MovePath), path))?;
+        let id: usize = caps["id"].parse()
+            .map_err(|_| MalformedAtomError::new(stringify!(MovePath), path))?;
+        Ok(Self(id))
+    }
+
+}
+
+/// An atom of a fact (a region, loan, variable, move path or point) that could not be parsed,
+/// given by the kind of the atom and the offending text.
+#[derive(Debug)]
+pub struct MalformedAtomError {
+    kind: &'static str,
+    text: String,
+}
+
+impl MalformedAtomError {
+    fn new(kind: &'static str, text: &str) -> Self {
+        MalformedAtomError {
+            kind,
+            text: text.to_owned(),
+        }
     }
+}
 
+impl fmt::Display for MalformedAtomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "malformed {} `{}`", self.kind, self.text)
+    }
 }
 
 /// The type of the point. Either the start of a statement or in the
@@ -121,17 +210,17 @@ pub struct Point {
 
 impl FromStr for Point {
 
-    type Err = ();
+    type Err = MalformedAtomError;
 
     fn from_str(point: &str) -> Result<Self, Self::Err> {
-        let re = Regex::new(r"^(?P<type>Mid|Start)\(bb(?P<bb>\d+)\[(?P<stmt>\d+)\]\)$").unwrap();
-        let caps = re.captures(point).unwrap();
-        let point_type: PointType = caps["type"].parse().unwrap();
-        let basic_block: usize = caps["bb"].parse().unwrap();
-        let statement_index: usize = caps["stmt"].parse().unwrap();
+        let malformed = || MalformedAtomError::new(stringify!(Point), point);
+        let caps = POINT_REGEX.captures(point).ok_or_else(malformed)?;
+        let point_type: PointType = caps["type"].parse().map_err(|_| malformed())?;
+        let basic_block: usize = caps["bb"].parse().map_err(|_| malformed())?;
+        let statement_index: usize = caps["stmt"].parse().map_err(|_| malformed())?;
         Ok(Self {
             location: mir::Location {
-                block: mir::BasicBlock::new(basic_block),
+                block: mir::BasicBlock::from_usize(basic_block),
                 statement_index: statement_index,
             },
             typ: point_type,
@@ -140,12 +229,13 @@ impl FromStr for Point {
 
 }
 
-pub type AllInputFacts = polonius_engine::AllFacts<Region, Loan, PointIndex>;
-pub type AllOutputFacts = polonius_engine::Output<Region, Loan, PointIndex>;
+pub type AllInputFacts = polonius_engine::AllFacts<RustLifeFacts>;
+pub type AllOutputFacts = polonius_engine::Output<RustLifeFacts>;
 
 
 /// A table that stores a mapping between interned elements of type
 /// `SourceType` and their indices.
+#[derive(Clone)]
 pub struct InternerTable<SourceType: Eq, IndexType: From<usize> + Copy> {
     /// For looking up from index type to source type.
     interned_elements: Vec<SourceType>,
@@ -187,10 +277,11 @@ impl<SourceType, IndexType> InternerTable<SourceType, IndexType>
 
 trait InternTo<FromType, ToType> {
 
-    fn intern(&mut self, element: FromType) -> ToType;
+    fn intern(&mut self, element: FromType) -> Result<ToType, MalformedAtomError>;
 
 }
 
+#[derive(Clone)]
 pub struct Interner {
     points: InternerTable<Point, PointIndex>,
 }
@@ -206,25 +297,48 @@ impl Interner {
         self.points.get_element(index)
     }
 
+    /// Interns a point of the facts that rustc provides, the location table is needed to find the
+    /// location of the point in the MIR.
+    fn intern_rustc_point(&mut self, location_table: &LocationTable, point: RustcPoint)
+            -> PointIndex {
+        let point = match location_table.to_location(point) {
+            RichLocation::Start(location) => Point { location, typ: PointType::Start },
+            RichLocation::Mid(location) => Point { location, typ: PointType::Mid },
+        };
+        self.points.get_or_create_index(point)
+    }
+
 
 }
 
 impl InternTo<String, Region> for Interner {
-    fn intern(&mut self, element: String) -> Region {
-        element.parse().unwrap()
+    fn intern(&mut self, element: String) -> Result<Region, MalformedAtomError> {
+        element.parse()
     }
 }
 
 impl InternTo<String, Loan> for Interner {
-    fn intern(&mut self, element: String) -> Loan {
-        element.parse().unwrap()
+    fn intern(&mut self, element: String) -> Result<Loan, MalformedAtomError> {
+        element.parse()
+    }
+}
+
+impl InternTo<String, Variable> for Interner {
+    fn intern(&mut self, element: String) -> Result<Variable, MalformedAtomError> {
+        element.parse()
+    }
+}
+
+impl InternTo<String, MovePath> for Interner {
+    fn intern(&mut self, element: String) -> Result<MovePath, MalformedAtomError> {
+        element.parse()
     }
 }
 
 impl InternTo<String, PointIndex> for Interner {
-    fn intern(&mut self, element: String) -> PointIndex {
-        let point = element.parse().unwrap();
-        self.points.get_or_create_index(point)
+    fn intern(&mut self, element: String) -> Result<PointIndex, MalformedAtomError> {
+        let point = element.parse()?;
+        Ok(self.points.get_or_create_index(point))
     }
 }
 
@@ -233,8 +347,8 @@ impl<A, B> InternTo<(String, String), (A, B)> for Interner
         Interner: InternTo<String, A>,
         Interner: InternTo<String, B>,
 {
-    fn intern(&mut self, (e1, e2): (String, String)) -> (A, B) {
-        (self.intern(e1), self.intern(e2))
+    fn intern(&mut self, (e1, e2): (String, String)) -> Result<(A, B), MalformedAtomError> {
+        Ok((self.intern(e1)?, self.intern(e2)?))
     }
 }
 
@@ -244,23 +358,99 @@ impl<A, B, C> InternTo<(String, String, String), (A, B, C)> for Interner
         Interner: InternTo<String, B>,
         Interner: InternTo<String, C>,
 {
-    fn intern(&mut self, (e1, e2, e3): (String, String, String)) -> (A, B, C) {
-        (self.intern(e1), self.intern(e2), self.intern(e3))
+    fn intern(&mut self, (e1, e2, e3): (String, String, String))
+            -> Result<(A, B, C), MalformedAtomError> {
+        Ok((self.intern(e1)?, self.intern(e2)?, self.intern(e3)?))
+    }
+}
+
+/// An error that occurred while reading the facts from the files that rustc writes (see
+/// FactLoader::load_all_facts()): the file is missing or cannot be read, or one of its rows is
+/// malformed.
+#[derive(Debug)]
+pub struct FactLoadError {
+    /// The file of the facts.
+    pub file: PathBuf,
+    /// The line of the offending row (starting at 1), None if the file could not be read at all.
+    pub line: Option<u64>,
+    /// The text of the offending row, with its fields separated by tabs. Empty if the file or the
+    /// row could not be read.
+    pub text: String,
+    /// What went wrong.
+    pub message: String,
+}
+
+impl FactLoadError {
+    fn new(file: &Path, record: Option<&StringRecord>, message: String) -> Self {
+        FactLoadError {
+            file: file.to_path_buf(),
+            line: record.and_then(|record| record.position()).map(|position| position.line()),
+            text: record.map_or_else(String::new, |record|
+                record.iter().collect::<Vec<_>>().join("\t")
+            ),
+            message,
+        }
+    }
+
+    /// Creates the error for a file that cannot be read (or parsed as CSV), with the line where
+    /// reading failed, if the reader knows it. (The csv crate only gives it for some kinds of
+    /// errors, and has no accessor for it in the version that is used.)
+    fn from_csv_error(file: &Path, err: &csv::Error) -> Self {
+        let position = match *err.kind() {
+            csv::ErrorKind::Utf8 { ref pos, .. } |
+            csv::ErrorKind::UnequalLengths { ref pos, .. } |
+            csv::ErrorKind::Deserialize { ref pos, .. } => pos.as_ref(),
+            _ => None,
+        };
+        FactLoadError {
+            file: file.to_path_buf(),
+            line: position.map(|position| position.line()),
+            text: String::new(),
+            message: err.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for FactLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line {
+            Some(line) if self.text.is_empty() =>
+                write!(f, "{}:{}: {}", self.file.display(), line, self.message),
+            Some(line) => write!(f, "{}:{}: {} (in the row `{}`)", self.file.display(), line,
+                                 self.message, self.text),
+            None => write!(f, "{}: {}", self.file.display(), self.message),
+        }
     }
 }
 
-fn load_facts_from_file<T: DeserializeOwned>(facts_dir: &Path, facts_type: &str) -> Vec<T> {
+impl Error for FactLoadError {}
+
+/// Reads the facts of the given type from their file in the directory, and interns them. Stops at
+/// the first row that cannot be deserialized or interned.
+fn load_facts<F: DeserializeOwned, T>(interner: &mut Interner, facts_dir: &Path, facts_type: &str)
+        -> Result<Vec<T>, FactLoadError>
+    where
+        Interner: InternTo<F, T>
+{
     let filename = format!("{}.facts", facts_type);
     let facts_file = facts_dir.join(&filename);
     let mut reader = ReaderBuilder::new()
          .delimiter(b'\t')
          .has_headers(false)
-         .from_path(facts_file)
-         .unwrap();
-    reader
-        .deserialize()
-        .map(|row| row.unwrap())
-        .collect()
+         .from_path(&facts_file)
+         .map_err(|err| FactLoadError::from_csv_error(&facts_file, &err))?;
+    let mut facts = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|err| FactLoadError::from_csv_error(&facts_file, &err))?;
+        let fact: F = record.deserialize(None).map_err(|err|
+            FactLoadError::new(&facts_file, Some(&record), err.to_string())
+        )?;
+        let fact = Interner::intern(interner, fact).map_err(|err|
+            FactLoadError::new(&facts_file, Some(&record), err.to_string())
+        )?;
+        facts.push(fact);
+    }
+    Ok(facts)
 }
 
 impl Interner {
@@ -283,60 +473,184 @@ impl FactLoader {
             facts: AllInputFacts::default(),
         }
     }
-    pub fn load_all_facts(&mut self, facts_dir: &Path) {
-
-        let facts = load_facts::<(String, String, String), _>(&mut self.interner, facts_dir, "borrow_region");
-        self.facts.borrow_region.extend(facts);
-
-        let facts = load_facts::<String, Region>(&mut self.interner, facts_dir, "universal_region");
-        self.facts.universal_region.extend(facts);
-
-        let facts = load_facts::<(String, String), _>(&mut self.interner, facts_dir, "cfg_edge");
-        self.facts.cfg_edge.extend(facts);
-
-        let facts = load_facts::<(String, String), _>(&mut self.interner, facts_dir, "killed");
-        self.facts.killed.extend(facts);
-
-        let facts = load_facts::<(String, String, String), _>(&mut self.interner, facts_dir, "outlives");
-        self.facts.outlives.extend(facts);
-
-        let facts = load_facts::<(String, String), _>(&mut self.interner, facts_dir, "region_live_at");
-        self.facts.region_live_at.extend(facts);
-
-        let facts = load_facts::<(String, String), _>(&mut self.interner, facts_dir, "invalidates");
-        self.facts.invalidates.extend(facts);
+    /// Takes the facts that the borrow checker of rustc provides and converts them to the types that
+    /// are used by rust-life. The location table of the body is needed to map the points of the
+    /// facts to locations in the MIR.
+    pub fn load_facts_from_compiler(&mut self, input_facts: &PoloniusInput,
+                                    location_table: &LocationTable) {
+        let interner = &mut self.interner;
+        let facts = &mut self.facts;
+        let mut point = |point: RustcPoint| interner.intern_rustc_point(location_table, point);
+        let region = |region: <RustcFacts as polonius_engine::FactTypes>::Origin| Region::from(region.as_usize());
+        let loan = |loan: <RustcFacts as polonius_engine::FactTypes>::Loan| Loan::from(loan.as_usize());
+        let variable = |local: mir::Local| Variable::from(local.as_usize());
+        let path = |path: <RustcFacts as polonius_engine::FactTypes>::Path| MovePath::from(path.as_usize());
+
+        facts.loan_issued_at.extend(input_facts.loan_issued_at.iter()
+            .map(|&(r, l, p)| (region(r), loan(l), point(p))));
+        facts.universal_region.extend(input_facts.universal_region.iter()
+            .map(|&r| region(r)));
+        facts.cfg_edge.extend(input_facts.cfg_edge.iter()
+            .map(|&(p1, p2)| (point(p1), point(p2))));
+        facts.loan_killed_at.extend(input_facts.loan_killed_at.iter()
+            .map(|&(l, p)| (loan(l), point(p))));
+        facts.subset_base.extend(input_facts.subset_base.iter()
+            .map(|&(r1, r2, p)| (region(r1), region(r2), point(p))));
+        facts.loan_invalidated_at.extend(input_facts.loan_invalidated_at.iter()
+            .map(|&(p, l)| (point(p), loan(l))));
+        facts.var_used_at.extend(input_facts.var_used_at.iter()
+            .map(|&(v, p)| (variable(v), point(p))));
+        facts.var_defined_at.extend(input_facts.var_defined_at.iter()
+            .map(|&(v, p)| (variable(v), point(p))));
+        facts.var_dropped_at.extend(input_facts.var_dropped_at.iter()
+            .map(|&(v, p)| (variable(v), point(p))));
+        facts.use_of_var_derefs_origin.extend(input_facts.use_of_var_derefs_origin.iter()
+            .map(|&(v, r)| (variable(v), region(r))));
+        facts.drop_of_var_derefs_origin.extend(input_facts.drop_of_var_derefs_origin.iter()
+            .map(|&(v, r)| (variable(v), region(r))));
+        facts.child_path.extend(input_facts.child_path.iter()
+            .map(|&(m1, m2)| (path(m1), path(m2))));
+        facts.path_is_var.extend(input_facts.path_is_var.iter()
+            .map(|&(m, v)| (path(m), variable(v))));
+        facts.path_assigned_at_base.extend(input_facts.path_assigned_at_base.iter()
+            .map(|&(m, p)| (path(m), point(p))));
+        facts.path_moved_at_base.extend(input_facts.path_moved_at_base.iter()
+            .map(|&(m, p)| (path(m), point(p))));
+        facts.path_accessed_at_base.extend(input_facts.path_accessed_at_base.iter()
+            .map(|&(m, p)| (path(m), point(p))));
+        facts.known_placeholder_subset.extend(input_facts.known_placeholder_subset.iter()
+            .map(|&(r1, r2)| (region(r1), region(r2))));
+        facts.placeholder.extend(input_facts.placeholder.iter()
+            .map(|&(r, l)| (region(r), loan(l))));
     }
-    pub fn load_expl_facts(&mut self, facts_dir: &Path) {
-
-        let facts = load_facts::<(String, String, String), _>(&mut self.interner, facts_dir, "borrow_region");
-        self.facts.borrow_region.extend(facts);
-
-        let facts = load_facts::<String, Region>(&mut self.interner, facts_dir, "universal_region");
-        self.facts.universal_region.extend(facts);
-
-        let facts = load_facts::<(String, String), _>(&mut self.interner, facts_dir, "cfg_edge");
-        self.facts.cfg_edge.extend(facts);
 
-        let facts = load_facts::<(String, String), _>(&mut self.interner, facts_dir, "killed");
-        self.facts.killed.extend(facts);
+    /// Reads the facts from the files that rustc writes to the given directory if -Znll-facts is
+    /// passed. (Only intended for debugging, normally load_facts_from_compiler is used.)
+    /// Fails if a file is missing or malformed, in this case none of the facts of the directory are
+    /// added to the ones of the loader, and its interner is left unchanged as well. (The points are
+    /// interned into a copy of the interner, which replaces it once all files are loaded.)
+    pub fn load_all_facts(&mut self, facts_dir: &Path) -> Result<(), FactLoadError> {
+        let mut interner = self.interner.clone();
+        let facts = AllInputFacts {
+            loan_issued_at: load_facts::<(String, String, String), _>(&mut interner, facts_dir, "loan_issued_at")?,
+            universal_region: load_facts::<String, Region>(&mut interner, facts_dir, "universal_region")?,
+            cfg_edge: load_facts::<(String, String), _>(&mut interner, facts_dir, "cfg_edge")?,
+            loan_killed_at: load_facts::<(String, String), _>(&mut interner, facts_dir, "loan_killed_at")?,
+            subset_base: load_facts::<(String, String, String), _>(&mut interner, facts_dir, "subset_base")?,
+            loan_invalidated_at: load_facts::<(String, String), _>(&mut interner, facts_dir, "loan_invalidated_at")?,
+            var_used_at: load_facts::<(String, String), _>(&mut interner, facts_dir, "var_used_at")?,
+            var_defined_at: load_facts::<(String, String), _>(&mut interner, facts_dir, "var_defined_at")?,
+            var_dropped_at: load_facts::<(String, String), _>(&mut interner, facts_dir, "var_dropped_at")?,
+            use_of_var_derefs_origin: load_facts::<(String, String), _>(&mut interner, facts_dir, "use_of_var_derefs_origin")?,
+            drop_of_var_derefs_origin: load_facts::<(String, String), _>(&mut interner, facts_dir, "drop_of_var_derefs_origin")?,
+            child_path: load_facts::<(String, String), _>(&mut interner, facts_dir, "child_path")?,
+            path_is_var: load_facts::<(String, String), _>(&mut interner, facts_dir, "path_is_var")?,
+            path_assigned_at_base: load_facts::<(String, String), _>(&mut interner, facts_dir, "path_assigned_at_base")?,
+            path_moved_at_base: load_facts::<(String, String), _>(&mut interner, facts_dir, "path_moved_at_base")?,
+            path_accessed_at_base: load_facts::<(String, String), _>(&mut interner, facts_dir, "path_accessed_at_base")?,
+            known_placeholder_subset: load_facts::<(String, String), _>(&mut interner, facts_dir, "known_placeholder_subset")?,
+            placeholder: load_facts::<(String, String), _>(&mut interner, facts_dir, "placeholder")?,
+        };
+
+        // all files could be loaded, so the facts are complete.
+        self.interner = interner;
+        self.facts.loan_issued_at.extend(facts.loan_issued_at);
+        self.facts.universal_region.extend(facts.universal_region);
+        self.facts.cfg_edge.extend(facts.cfg_edge);
+        self.facts.loan_killed_at.extend(facts.loan_killed_at);
+        self.facts.subset_base.extend(facts.subset_base);
+        self.facts.loan_invalidated_at.extend(facts.loan_invalidated_at);
+        self.facts.var_used_at.extend(facts.var_used_at);
+        self.facts.var_defined_at.extend(facts.var_defined_at);
+        self.facts.var_dropped_at.extend(facts.var_dropped_at);
+        self.facts.use_of_var_derefs_origin.extend(facts.use_of_var_derefs_origin);
+        self.facts.drop_of_var_derefs_origin.extend(facts.drop_of_var_derefs_origin);
+        self.facts.child_path.extend(facts.child_path);
+        self.facts.path_is_var.extend(facts.path_is_var);
+        self.facts.path_assigned_at_base.extend(facts.path_assigned_at_base);
+        self.facts.path_moved_at_base.extend(facts.path_moved_at_base);
+        self.facts.path_accessed_at_base.extend(facts.path_accessed_at_base);
+        self.facts.known_placeholder_subset.extend(facts.known_placeholder_subset);
+        self.facts.placeholder.extend(facts.placeholder);
+
+        Ok(())
+    }
+}
 
-        let facts = load_facts::<(String, String, String), _>(&mut self.interner, facts_dir, "expl_outlives");
-        self.facts.outlives.extend(facts);
+#[cfg(test)]
+mod tests {
+    use super::{FactLoader, PointIndex};
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+
+    /// The types of the facts that load_all_facts() reads, one file for each of them.
+    const FACT_TYPES: &[&str] = &[
+        "loan_issued_at", "universal_region", "cfg_edge", "loan_killed_at", "subset_base",
+        "loan_invalidated_at", "var_used_at", "var_defined_at", "var_dropped_at",
+        "use_of_var_derefs_origin", "drop_of_var_derefs_origin", "child_path", "path_is_var",
+        "path_assigned_at_base", "path_moved_at_base", "path_accessed_at_base",
+        "known_placeholder_subset", "placeholder",
+    ];
+
+    /// Creates a directory with empty files for all facts (except the ones that are missing), and
+    /// the given contents for the other ones, in the format that rustc writes.
+    fn facts_dir(name: &str, contents: &[(&str, &str)], missing: &[&str]) -> PathBuf {
+        let facts_dir = env::temp_dir().join(format!("rust-life-facts-test-{}", name));
+        let _ = fs::remove_dir_all(&facts_dir);
+        fs::create_dir_all(&facts_dir).unwrap();
+        for &facts_type in FACT_TYPES.iter().filter(|facts_type| !missing.contains(facts_type)) {
+            let content = contents.iter()
+                .find(|&&(contents_type, _)| contents_type == facts_type)
+                .map_or("", |&(_, content)| content);
+            fs::write(facts_dir.join(format!("{}.facts", facts_type)), content).unwrap();
+        }
+        facts_dir
+    }
 
-        let facts = load_facts::<(String, String), _>(&mut self.interner, facts_dir, "region_live_at");
-        self.facts.region_live_at.extend(facts);
+    const CFG_EDGES: &str = "\"Start(bb0[0])\"\t\"Mid(bb0[0])\"\n\
+                             \"Mid(bb0[0])\"\t\"Start(bb0[1])\"\n";
+
+    #[test]
+    fn the_facts_of_a_directory_are_loaded() {
+        let facts_dir = facts_dir("valid", &[("cfg_edge", CFG_EDGES)], &[]);
+        let mut loader = FactLoader::new();
+        loader.load_all_facts(&facts_dir).unwrap();
+        assert_eq!(loader.facts.cfg_edge, vec![(PointIndex::from(0), PointIndex::from(1)),
+                                               (PointIndex::from(1), PointIndex::from(2))]);
+        assert_eq!(loader.interner.get_point(PointIndex::from(2)),
+                   &"Start(bb0[1])".parse().unwrap());
+        fs::remove_dir_all(&facts_dir).unwrap();
+    }
 
-        let facts = load_facts::<(String, String), _>(&mut self.interner, facts_dir, "invalidates");
-        self.facts.invalidates.extend(facts);
+    #[test]
+    fn a_malformed_row_is_reported_and_nothing_is_loaded() {
+        // the points of the cfg_edge facts are interned before the subset_base facts are read.
+        let facts_dir = facts_dir("malformed", &[
+            ("cfg_edge", CFG_EDGES),
+            ("subset_base", "\"'?0\"\t\"'?1\"\t\"Mid(bb0[0])\"\n\
+                             \"'?1\"\t\"'?2\"\t\"End(bb0[1])\"\n"),
+        ], &[]);
+        let mut loader = FactLoader::new();
+        let err = loader.load_all_facts(&facts_dir).unwrap_err();
+        assert_eq!(err.file, facts_dir.join("subset_base.facts"));
+        assert_eq!(err.line, Some(2));
+        assert_eq!(err.text, "'?1\t'?2\tEnd(bb0[1])");
+        assert!(loader.facts.cfg_edge.is_empty());
+        // no point was interned, so the next point gets the first index.
+        let point = "Mid(bb1[0])".parse().unwrap();
+        assert_eq!(loader.interner.points.get_or_create_index(point), PointIndex::from(0));
+        fs::remove_dir_all(&facts_dir).unwrap();
     }
-}
 
-fn load_facts<F: DeserializeOwned, T>(interner: &mut Interner, facts_dir: &Path, facts_type: &str) -> Vec<T>
-    where
-        Interner: InternTo<F, T>
-{
-    load_facts_from_file(facts_dir, facts_type)
-        .into_iter()
-        .map(|fact| Interner::intern(interner, fact))
-        .collect()
+    #[test]
+    fn a_missing_file_is_reported() {
+        let facts_dir = facts_dir("missing", &[], &["var_used_at"]);
+        let mut loader = FactLoader::new();
+        let err = loader.load_all_facts(&facts_dir).unwrap_err();
+        assert_eq!(err.file, facts_dir.join("var_used_at.facts"));
+        assert_eq!(err.line, None);
+        assert_eq!(err.text, "");
+        fs::remove_dir_all(&facts_dir).unwrap();
+    }
 }
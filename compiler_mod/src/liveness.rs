@@ -0,0 +1,264 @@
+// © 2020, ETH Zurich
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+/// Code for explaining why an invalidated loan is still live at the point of the error. Polonius
+/// only reports an error if a region that contains the loan is live there, and a region is live
+/// at a point if a variable whose type contains it may be used (or dropped) later, without being
+/// assigned a new value before (see the var_used_at, var_dropped_at, var_defined_at,
+/// use_of_var_derefs_origin and drop_of_var_derefs_origin facts).
+/// The path that explains an error only tells how the loan gets into the live region, this module
+/// finds the use that keeps the region live, by tracing the liveness forward along the control flow
+/// graph from the point of the error.
+
+use dump_borrowck_info::serde_derive;
+use facts::{self, Loan, PointIndex, Region, Variable};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// A use (or drop) of a variable that makes a region live at the point of an error, as found by
+/// the LivenessTraceFinder.
+pub struct LiveUse {
+    /// The region that contains the loan and is live at the point of the error.
+    pub region: Region,
+    /// The variable whose type contains the region.
+    pub variable: Variable,
+    /// True if the variable is dropped, rather than used.
+    pub is_drop: bool,
+    /// The points on the path in the control flow graph from the point of the error to the use,
+    /// including both.
+    pub points: Vec<PointIndex>,
+}
+
+/// This struct holds the data that is needed to find the use that keeps an invalidated loan live
+/// at the point of the error. After initializing all fields (best done by using the provided
+/// constructor), call compute_live_use() to run the actual computation.
+pub struct LivenessTraceFinder<'a> {
+    all_facts: &'a facts::AllInputFacts,
+    /// The output of Polonius, it must have been computed with dumping enabled, since
+    /// origin_live_on_entry and origin_contains_loan_at are needed.
+    output: &'a facts::AllOutputFacts,
+    error_point: PointIndex,
+    error_loan: Loan,
+}
+
+impl<'a> LivenessTraceFinder<'a> {
+    pub fn new(all_facts: &'a facts::AllInputFacts, output: &'a facts::AllOutputFacts,
+               error_point: PointIndex, error_loan: Loan) -> Self {
+        LivenessTraceFinder {
+            all_facts,
+            output,
+            error_point,
+            error_loan,
+        }
+    }
+
+    /// Finds the use that keeps the loan live at the error point: Among the variables whose type
+    /// contains a region that contains the loan and is live at the error point, the one with the
+    /// shortest path in the control flow graph from the error point to a use (or drop) of it is
+    /// taken. The path must not pass through an assignment to the variable (except at the use
+    /// itself, which happens before the assignment), since the variable holds another value
+    /// afterwards.
+    /// Returns None if no such use is found.
+    pub fn compute_live_use(&self) -> Option<LiveUse> {
+        trace!("[compute_live_use] enter");
+        let live_regions: Vec<Region> = self.output.origin_live_on_entry.get(&self.error_point)
+            .into_iter()
+            .flatten()
+            .cloned()
+            .filter(|&region| self.contains_loan(region))
+            .collect();
+        debug!("live regions that contain {:?}: {:?}", self.error_loan, live_regions);
+
+        let mut candidates: Vec<(Region, Variable, bool)> = Vec::new();
+        for &(variable, region) in self.all_facts.use_of_var_derefs_origin.iter() {
+            if live_regions.contains(&region) {
+                candidates.push((region, variable, false));
+            }
+        }
+        for &(variable, region) in self.all_facts.drop_of_var_derefs_origin.iter() {
+            if live_regions.contains(&region) {
+                candidates.push((region, variable, true));
+            }
+        }
+
+        let live_use = candidates.into_iter()
+            .filter_map(|(region, variable, is_drop)|
+                self.trace_variable(variable, is_drop).map(|points| LiveUse {
+                    region,
+                    variable,
+                    is_drop,
+                    points,
+                })
+            )
+            .min_by_key(|live_use| (live_use.points.len(), live_use.is_drop));
+        if let Some(ref live_use) = live_use {
+            debug!("{:?} is live at {:?} because {:?} is used (drop: {:?}) at {:?}",
+                   live_use.region, self.error_point, live_use.variable, live_use.is_drop,
+                   live_use.points.last());
+        }
+        trace!("[compute_live_use] exit");
+        live_use
+    }
+
+    /// Searches the shortest path in the control flow graph from the error point to a use (or a
+    /// drop, if is_drop is true) of the variable, that does not pass through an assignment to it.
+    /// Returns the points on the path, None if there is no such path.
+    fn trace_variable(&self, variable: Variable, is_drop: bool) -> Option<Vec<PointIndex>> {
+        let use_facts = if is_drop {
+            &self.all_facts.var_dropped_at
+        } else {
+            &self.all_facts.var_used_at
+        };
+        let use_points: HashSet<PointIndex> = use_facts.iter()
+            .filter(|&&(v, _)| v == variable)
+            .map(|&(_, point)| point)
+            .collect();
+        let definition_points: HashSet<PointIndex> = self.all_facts.var_defined_at.iter()
+            .filter(|&&(v, _)| v == variable)
+            .map(|&(_, point)| point)
+            .collect();
+
+        let mut predecessors: HashMap<PointIndex, PointIndex> = HashMap::new();
+        let mut visited: HashSet<PointIndex> = HashSet::new();
+        let mut queue: VecDeque<PointIndex> = VecDeque::new();
+        visited.insert(self.error_point);
+        queue.push_back(self.error_point);
+        while let Some(point) = queue.pop_front() {
+            if use_points.contains(&point) {
+                let mut points = vec![point];
+                let mut cur_point = point;
+                while let Some(&predecessor) = predecessors.get(&cur_point) {
+                    points.push(predecessor);
+                    cur_point = predecessor;
+                }
+                points.reverse();
+                return Some(points);
+            }
+            if definition_points.contains(&point) {
+                // the variable is overwritten here, later uses do not use the value of the error.
+                continue;
+            }
+            for &(_, successor) in self.all_facts.cfg_edge.iter().filter(|&&(from, _)| from == point) {
+                if visited.insert(successor) {
+                    predecessors.insert(successor, point);
+                    queue.push_back(successor);
+                }
+            }
+        }
+        None
+    }
+
+    /// Checks if the region contains the error loan at the error point.
+    fn contains_loan(&self, region: Region) -> bool {
+        self.output.origin_contains_loan_at.get(&self.error_point)
+            .and_then(|region_map| region_map.get(&region))
+            .map_or(false, |loans| loans.contains(&self.error_loan))
+    }
+}
+
+/// This struct describes why an invalidated loan is still live at the point of the error, by the
+/// use that keeps it live (see LiveUse). It is filled by the MirInfoPrinter and included in the
+/// EnrichedErrorGraph.
+#[derive(Clone, Debug, serde_derive::Serialize)]
+pub struct LivenessTrace {
+    /// The region that contains the loan and is live at the point of the error.
+    pub region: Region,
+    /// The name of the variable that is used, or a description like "a temporary" if it has none.
+    pub variable_name: String,
+    /// True if the variable is dropped (at the end of its scope), rather than used.
+    pub is_drop: bool,
+    /// The line of the error, where the loan is still live.
+    pub error_line: (usize, String),
+    /// The line of the use (or drop), given as it's number and it's source code.
+    pub use_line: (usize, String),
+    /// The distinct source lines on the path in the control flow graph from the error to the use,
+    /// in the order of the path, including the line of the error and the one of the use.
+    /// Consecutive points on the same line are given by a single entry.
+    pub lines_on_path: Vec<(usize, String)>,
+}
+
+impl LivenessTrace {
+    /// Describes why the borrow is still live at the error, for the explanations of errors.
+    pub fn describe(&self) -> String {
+        let use_description = if self.is_drop {
+            format!("{} is dropped at line {}", self.variable_name, self.use_line.0)
+        } else {
+            format!("{} is used at line {} (`{}`)", self.variable_name, self.use_line.0,
+                    self.use_line.1.trim())
+        };
+        format!("the borrow is still live at line {}, because {} and may still point to it",
+                self.error_line.0, use_description)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LiveUse, LivenessTraceFinder};
+    use dump_borrowck_info::polonius_engine::{Algorithm, Output};
+    use facts::{AllInputFacts, AllOutputFacts, Loan, PointIndex, Region, Variable};
+
+    /// Builds the facts of a function with the points 0 to 5 in a row, where the loan 0 is issued
+    /// in region 0 at point 0 and invalidated at the error point. Both variables 0 and 1 have
+    /// region 0 in their type, they are used and defined at the given points.
+    fn facts(error_point: usize, uses: &[(usize, usize)], definitions: &[(usize, usize)])
+            -> AllInputFacts {
+        let pairs = |pairs: &[(usize, usize)]| pairs.iter()
+            .map(|&(variable, point)| (Variable::from(variable), PointIndex::from(point)))
+            .collect();
+        AllInputFacts {
+            cfg_edge: (0..5)
+                .map(|point| (PointIndex::from(point), PointIndex::from(point + 1)))
+                .collect(),
+            loan_issued_at: vec![(Region::from(0), Loan::from(0), PointIndex::from(0))],
+            loan_invalidated_at: vec![(PointIndex::from(error_point), Loan::from(0))],
+            var_used_at: pairs(uses),
+            var_defined_at: pairs(definitions),
+            use_of_var_derefs_origin: vec![(Variable::from(0), Region::from(0)),
+                                           (Variable::from(1), Region::from(0))],
+            ..AllInputFacts::default()
+        }
+    }
+
+    /// Runs Polonius with all outputs (the liveness and the loans of the regions are needed),
+    /// checks that it reports the error, and searches the use that keeps the loan live there.
+    fn compute_live_use(all_facts: &AllInputFacts, error_point: usize) -> Option<LiveUse> {
+        let error_point = PointIndex::from(error_point);
+        let output: AllOutputFacts = Output::compute(all_facts, Algorithm::Naive, true);
+        assert_eq!(output.errors.get(&error_point), Some(&vec![Loan::from(0)]));
+        LivenessTraceFinder::new(all_facts, &output, error_point, Loan::from(0))
+            .compute_live_use()
+    }
+
+    fn points(points: &[usize]) -> Vec<PointIndex> {
+        points.iter().map(|&point| PointIndex::from(point)).collect()
+    }
+
+    #[test]
+    fn a_use_at_the_error_point_is_found() {
+        // variable 0 is used at the error point itself, variable 1 only later.
+        let all_facts = facts(2, &[(0, 2), (1, 4)], &[]);
+        let live_use = compute_live_use(&all_facts, 2).unwrap();
+        assert_eq!(live_use.region, Region::from(0));
+        assert_eq!(live_use.variable, Variable::from(0));
+        assert!(!live_use.is_drop);
+        assert_eq!(live_use.points, points(&[2]));
+    }
+
+    #[test]
+    fn the_trace_stops_at_a_redefinition() {
+        // variable 0 is assigned a new value at point 3 before it is used at 4, hence only the use
+        // of variable 1 at point 5 keeps the loan live.
+        let all_facts = facts(2, &[(0, 4), (1, 5)], &[(0, 3)]);
+        let live_use = compute_live_use(&all_facts, 2).unwrap();
+        assert_eq!(live_use.variable, Variable::from(1));
+        assert_eq!(live_use.points, points(&[2, 3, 4, 5]));
+
+        // a use at the redefinition itself (like in `x = f(x)`) happens before the assignment.
+        let all_facts = facts(2, &[(0, 3), (1, 5)], &[(0, 3)]);
+        let live_use = compute_live_use(&all_facts, 2).unwrap();
+        assert_eq!(live_use.variable, Variable::from(0));
+        assert_eq!(live_use.points, points(&[2, 3]));
+    }
+}
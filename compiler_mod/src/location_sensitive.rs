@@ -0,0 +1,351 @@
+// © 2020, ETH Zurich
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+/// Code for finding the path that explains an invalidated loan in a location-sensitive way, i.e.
+/// over pairs of a region and a point instead of the flattened outlives relation. The search of
+/// find_path_backwards() (in dump_borrowck_info) ignores the points of the constraints, so it may
+/// chain constraints that never hold at the same time (e.g. constraints from different iterations
+/// of a loop, or from exclusive branches of an if/else).
+/// This search follows the rules of (naive) Polonius instead:
+/// * A subset holds at the point of its subset_base fact, and is propagated along a cfg_edge to
+///   the next point if both of its regions are live there. Subsets are transitive at every point,
+///   hence a chain of subsets over regions that are dead at the next point is propagated as well,
+///   as a single subset that is composed of the constraints of the chain.
+/// * A loan flows from a region into every region that the region is a subset of at the same
+///   point, and it stays in the region along a cfg_edge if it is not killed and the region is
+///   live at the next point.
+///
+/// Hence every step of the resulting path is valid on a real execution path from the borrow to the
+/// error.
+
+use facts::{self, Loan, PointIndex, Region};
+use std::cmp::Reverse;
+use std::collections::{hash_map, BinaryHeap, HashMap, HashSet};
+
+/// The constraints that a subset at a point is composed of, in the order of the chain from its
+/// first to its second region. Every constraint is given by its two regions and the point of its
+/// subset_base fact. (A subset that is not composed consists of a single constraint.)
+type SubsetConstraints = Vec<(Region, Region, PointIndex)>;
+
+/// For every pair of a region and a point that the search has reached, the next pair on the way to
+/// the error, and the steps between the two pairs (none if they are connected by an edge of the
+/// control flow graph).
+type NextPairs = HashMap<(Region, PointIndex), ((Region, PointIndex), Vec<PathStep>)>;
+
+/// A step of a location-sensitive path: the loan flows from region1 into region2 at the given
+/// point, since region1 is a subset of region2 there. (For a subset that is composed of several
+/// constraints, there is a step at the point for each of them, even though the regions in between
+/// are dead at the point.)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PathStep {
+    pub region1: Region,
+    pub region2: Region,
+    /// The point where the loan flows from region1 to region2.
+    pub point: PointIndex,
+    /// The point of the subset_base fact that established the subset, i.e. the point of the
+    /// constraint that causes the step. (The subset may have been propagated to point from there.)
+    pub base_point: PointIndex,
+}
+
+/// This struct holds the data that is needed to find a location-sensitive path for an
+/// invalidated loan. After initializing all fields (best done by using the provided constructor),
+/// call compute_path() to run the actual path computation.
+pub struct LocationSensitivePathFinder<'a> {
+    all_facts: &'a facts::AllInputFacts,
+    /// The output of Polonius, it must have been computed with dumping enabled, since
+    /// origin_live_on_entry and origin_contains_loan_at are needed.
+    output: &'a facts::AllOutputFacts,
+    error_point: PointIndex,
+    error_loan: Loan,
+    /// The region that contains the loan at the error point and is live there, see
+    /// ErrorPathFinder::find_error_region().
+    error_region: Region,
+}
+
+impl<'a> LocationSensitivePathFinder<'a> {
+    pub fn new(all_facts: &'a facts::AllInputFacts, output: &'a facts::AllOutputFacts,
+               error_point: PointIndex, error_loan: Loan, error_region: Region) -> Self {
+        LocationSensitivePathFinder {
+            all_facts,
+            output,
+            error_point,
+            error_loan,
+            error_region,
+        }
+    }
+
+    /// Computes the path with the fewest steps (i.e. constraints) over pairs of a region and a
+    /// point, from the point where the loan is issued to the error region at the error point. The
+    /// search goes backwards from the error, moving along the control flow graph (which is free) and
+    /// along the subsets that hold at a point (which costs one step per constraint of the subset),
+    /// but only through pairs where the region contains the loan.
+    /// Returns the steps in the order of the path (i.e. starting at the region of the loan), None
+    /// if no path is found.
+    pub fn compute_path(&self) -> Option<Vec<PathStep>> {
+        trace!("[compute_path] enter");
+        let live_regions: HashSet<(Region, PointIndex)> = self.output.origin_live_on_entry.iter()
+            .flat_map(|(&point, regions)| regions.iter().map(move |&region| (region, point)))
+            .collect();
+        let subsets = self.compute_subsets(&live_regions);
+        let mut cfg_predecessors: HashMap<PointIndex, Vec<PointIndex>> = HashMap::new();
+        for &(from, to) in self.all_facts.cfg_edge.iter() {
+            cfg_predecessors.entry(to).or_default().push(from);
+        }
+        let killed_points: HashSet<PointIndex> = self.all_facts.loan_killed_at.iter()
+            .filter(|&&(loan, _)| loan == self.error_loan)
+            .map(|&(_, point)| point)
+            .collect();
+
+        // Dijkstra's algorithm backwards from the error. For every reached pair, the number of
+        // constraints on the cheapest known way to the error is stored, and the next pair on this
+        // way, together with the steps if the two are connected by a subset. Both are replaced
+        // whenever the pair is reached more cheaply, so the pair that is first reached by a subset
+        // can still be reached by an edge of the control flow graph later.
+        let start = (self.error_region, self.error_point);
+        let mut distances: HashMap<(Region, PointIndex), usize> = HashMap::new();
+        let mut next_pairs: NextPairs = HashMap::new();
+        let mut visited: HashSet<(Region, PointIndex)> = HashSet::new();
+        let mut queue: BinaryHeap<Reverse<(usize, Region, PointIndex)>> = BinaryHeap::new();
+        distances.insert(start, 0);
+        queue.push(Reverse((0, start.0, start.1)));
+        while let Some(Reverse((distance, region, point))) = queue.pop() {
+            let pair = (region, point);
+            if !visited.insert(pair) {
+                continue;
+            }
+            if self.all_facts.loan_issued_at.iter()
+                    .any(|&(r, l, p)| r == region && l == self.error_loan && p == point) {
+                let path = self.reconstruct_path(pair, &next_pairs);
+                debug!("location-sensitive path: {:?}", path);
+                trace!("[compute_path] exit");
+                return Some(path);
+            }
+
+            // the loan stayed in the region along an edge of the control flow graph.
+            if live_regions.contains(&(region, point)) {
+                for &predecessor in cfg_predecessors.get(&point).into_iter().flatten() {
+                    let predecessor_pair = (region, predecessor);
+                    if !visited.contains(&predecessor_pair) &&
+                            !killed_points.contains(&predecessor) &&
+                            self.contains_loan(region, predecessor) &&
+                            distances.get(&predecessor_pair)
+                                .map_or(true, |&best_distance| distance < best_distance) {
+                        distances.insert(predecessor_pair, distance);
+                        next_pairs.insert(predecessor_pair, (pair, Vec::new()));
+                        queue.push(Reverse((distance, region, predecessor)));
+                    }
+                }
+            }
+
+            // the loan flowed into the region from a subset at the same point.
+            for (&(region1, region2), constraints) in subsets.get(&point).into_iter().flatten() {
+                let subset_pair = (region1, point);
+                let subset_distance = distance + constraints.len();
+                if region2 != region || visited.contains(&subset_pair) ||
+                        !self.contains_loan(region1, point) ||
+                        distances.get(&subset_pair)
+                            .map_or(false, |&best_distance| best_distance <= subset_distance) {
+                    continue;
+                }
+                let steps = constraints.iter()
+                    .map(|&(step_region1, step_region2, base_point)| PathStep {
+                        region1: step_region1,
+                        region2: step_region2,
+                        point,
+                        base_point,
+                    })
+                    .collect();
+                distances.insert(subset_pair, subset_distance);
+                next_pairs.insert(subset_pair, (pair, steps));
+                queue.push(Reverse((subset_distance, region1, point)));
+            }
+        }
+
+        warn!("No location-sensitive path was found for the error at {:?} for {:?}!",
+              self.error_point, self.error_loan);
+        trace!("[compute_path] exit");
+        None
+    }
+
+    /// Computes the subsets that hold at every point, mapped to the constraints that they are
+    /// composed of. A subset holds at the point of its subset_base fact, and at every point that
+    /// follows along a cfg_edge where both regions are live. Unlike Polonius, the transitive
+    /// subsets are not added at every point (a transitive subset is explained better by its parts,
+    /// which the search follows one by one), but only where Polonius needs them: when a chain of
+    /// subsets passes through regions that are dead at the next point, so that its parts are not
+    /// propagated. Of several chains for the same subset, the one that is found first is kept.
+    fn compute_subsets(&self, live_regions: &HashSet<(Region, PointIndex)>)
+            -> HashMap<PointIndex, HashMap<(Region, Region), SubsetConstraints>> {
+        let mut cfg_successors: HashMap<PointIndex, Vec<PointIndex>> = HashMap::new();
+        for &(from, to) in self.all_facts.cfg_edge.iter() {
+            cfg_successors.entry(from).or_default().push(to);
+        }
+        let mut subsets: HashMap<PointIndex, HashMap<(Region, Region), SubsetConstraints>> =
+            HashMap::new();
+        let mut worklist: Vec<PointIndex> = Vec::new();
+        for &(region1, region2, point) in self.all_facts.subset_base.iter() {
+            subsets.entry(point).or_default()
+                .entry((region1, region2))
+                .or_insert_with(|| vec![(region1, region2, point)]);
+            worklist.push(point);
+        }
+        while let Some(point) = worklist.pop() {
+            for &successor in cfg_successors.get(&point).into_iter().flatten() {
+                let propagated_subsets = propagate_subsets(&subsets[&point], |region|
+                    live_regions.contains(&(region, successor)));
+                let successor_subsets = subsets.entry(successor).or_default();
+                let mut changed = false;
+                for (subset, constraints) in propagated_subsets {
+                    if let hash_map::Entry::Vacant(entry) = successor_subsets.entry(subset) {
+                        entry.insert(constraints);
+                        changed = true;
+                    }
+                }
+                if changed {
+                    worklist.push(successor);
+                }
+            }
+        }
+        subsets
+    }
+
+    /// Checks if the region contains the error loan at the point, according to the output of
+    /// Polonius.
+    fn contains_loan(&self, region: Region, point: PointIndex) -> bool {
+        self.output.origin_contains_loan_at.get(&point)
+            .and_then(|region_map| region_map.get(&region))
+            .map_or(false, |loans| loans.contains(&self.error_loan))
+    }
+
+    /// Follows the stored next pairs from the pair where the loan is issued to the error, and
+    /// collects the steps on the way.
+    fn reconstruct_path(&self, loan_pair: (Region, PointIndex), next_pairs: &NextPairs)
+            -> Vec<PathStep> {
+        let mut steps = Vec::new();
+        let mut cur_pair = loan_pair;
+        while let Some((next_pair, pair_steps)) = next_pairs.get(&cur_pair) {
+            steps.extend(pair_steps.iter().cloned());
+            cur_pair = *next_pair;
+        }
+        steps
+    }
+}
+
+/// Gives the subsets of a point that are propagated to a successor of it, given by the regions
+/// that are live at the successor: every subset whose regions are both live, and the subsets that
+/// are composed of a chain of subsets of the point whose regions in between are all dead (i.e.
+/// that would be lost otherwise, see compute_subsets()). The chains are searched breadth-first
+/// from every live region, so the composed subsets have the fewest parts.
+fn propagate_subsets<L: Fn(Region) -> bool>(subsets: &HashMap<(Region, Region), SubsetConstraints>,
+                                            is_live: L)
+        -> HashMap<(Region, Region), SubsetConstraints> {
+    let mut outgoing: HashMap<Region, Vec<(Region, &SubsetConstraints)>> = HashMap::new();
+    for (&(region1, region2), constraints) in subsets.iter() {
+        outgoing.entry(region1).or_default().push((region2, constraints));
+    }
+    let mut propagated_subsets = HashMap::new();
+    for &first_region in outgoing.keys().filter(|&&region| is_live(region)) {
+        let mut visited: HashSet<Region> = HashSet::new();
+        visited.insert(first_region);
+        let mut chains: Vec<(Region, SubsetConstraints)> = vec![(first_region, Vec::new())];
+        while !chains.is_empty() {
+            let mut next_chains = Vec::new();
+            for (region, chain) in chains {
+                for &(next_region, constraints) in outgoing.get(&region).into_iter().flatten() {
+                    if !visited.insert(next_region) {
+                        continue;
+                    }
+                    let mut next_chain = chain.clone();
+                    next_chain.extend(constraints.iter().cloned());
+                    if is_live(next_region) {
+                        propagated_subsets.insert((first_region, next_region), next_chain);
+                    } else {
+                        // the region is dead at the successor, the chain is continued through it.
+                        next_chains.push((next_region, next_chain));
+                    }
+                }
+            }
+            chains = next_chains;
+        }
+    }
+    propagated_subsets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LocationSensitivePathFinder, PathStep};
+    use dump_borrowck_info::polonius_engine::{Algorithm, Output};
+    use facts::{AllInputFacts, AllOutputFacts, Loan, PointIndex, Region, Variable};
+
+    /// Builds the facts of a function with the points 0 to 4 in a row, where the loan 0 is issued
+    /// in region 0 at the given point and invalidated at point 3. Every region is dereferenced by
+    /// the variable with the same index, which is used at the given point (hence the region is live
+    /// up to there), the given subsets are given by (region1, region2, point).
+    fn facts(loan_point: usize, uses: &[(usize, usize)], subsets: &[(usize, usize, usize)])
+            -> AllInputFacts {
+        AllInputFacts {
+            cfg_edge: (0..4)
+                .map(|point| (PointIndex::from(point), PointIndex::from(point + 1)))
+                .collect(),
+            loan_issued_at: vec![(Region::from(0), Loan::from(0), PointIndex::from(loan_point))],
+            loan_invalidated_at: vec![(PointIndex::from(3), Loan::from(0))],
+            var_used_at: uses.iter()
+                .map(|&(variable, point)| (Variable::from(variable), PointIndex::from(point)))
+                .collect(),
+            use_of_var_derefs_origin: uses.iter()
+                .map(|&(variable, _)| (Variable::from(variable), Region::from(variable)))
+                .collect(),
+            subset_base: subsets.iter()
+                .map(|&(region1, region2, point)|
+                    (Region::from(region1), Region::from(region2), PointIndex::from(point)))
+                .collect(),
+            ..AllInputFacts::default()
+        }
+    }
+
+    /// Runs Polonius with all outputs (the search needs the liveness and the loans of the regions),
+    /// checks that it reports the error at point 3, and searches the path to region 2 there.
+    fn compute_path(all_facts: &AllInputFacts) -> Option<Vec<PathStep>> {
+        let output: AllOutputFacts = Output::compute(all_facts, Algorithm::Naive, true);
+        assert_eq!(output.errors.get(&PointIndex::from(3)), Some(&vec![Loan::from(0)]));
+        LocationSensitivePathFinder::new(all_facts, &output, PointIndex::from(3), Loan::from(0),
+                                         Region::from(2)).compute_path()
+    }
+
+    fn step(region1: usize, region2: usize, point: usize, base_point: usize) -> PathStep {
+        PathStep {
+            region1: Region::from(region1),
+            region2: Region::from(region2),
+            point: PointIndex::from(point),
+            base_point: PointIndex::from(base_point),
+        }
+    }
+
+    #[test]
+    fn the_subsets_are_followed_at_their_points() {
+        // the loan flows from region 0 into region 1 at point 1, and into region 2 at point 2.
+        let all_facts = facts(0, &[(0, 1), (1, 2), (2, 4)], &[(0, 1, 1), (1, 2, 2)]);
+        assert_eq!(compute_path(&all_facts), Some(vec![step(0, 1, 1, 1), step(1, 2, 2, 2)]));
+    }
+
+    #[test]
+    fn a_subset_is_composed_over_a_dead_region() {
+        // both subsets hold at point 0, where region 1 is live for the last time. The loan is only
+        // issued at point 1, where it flows into region 2 by the composed subset.
+        let all_facts = facts(1, &[(0, 1), (1, 0), (2, 4)], &[(0, 1, 0), (1, 2, 0)]);
+        assert_eq!(compute_path(&all_facts), Some(vec![step(0, 1, 1, 0), step(1, 2, 1, 0)]));
+    }
+
+    #[test]
+    fn a_subset_is_not_followed_after_the_error() {
+        // region 0 is a subset of region 2 at point 4 as well, which a location-insensitive path
+        // would take, but the loan only flows into region 2 by the subsets over region 1 before the
+        // error.
+        let all_facts = facts(0, &[(0, 4), (1, 2), (2, 4)],
+                              &[(0, 2, 4), (0, 1, 1), (1, 2, 2)]);
+        assert_eq!(compute_path(&all_facts), Some(vec![step(0, 1, 1, 1), step(1, 2, 2, 2)]));
+    }
+}
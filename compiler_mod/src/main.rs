@@ -4,59 +4,99 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
-#![feature(box_syntax)]
 #![feature(rustc_private)]
-#![feature(vec_remove_item)]
+#![feature(box_patterns)]
 
 extern crate env_logger;
-extern crate getopts;
+#[macro_use]
+extern crate lazy_static;
 #[macro_use]
 extern crate log;
 //extern crate prusti;
-extern crate rustc;
+extern crate rustc_borrowck;
 extern crate rustc_driver;
+extern crate rustc_hir;
 extern crate rustc_interface;
 extern crate rustc_errors;
-extern crate rustc_codegen_utils;
-extern crate syntax;
-extern crate syntax_pos;
+extern crate rustc_middle;
+extern crate rustc_session;
+extern crate rustc_span;
+#[cfg(test)]
+extern crate rustc_target;
 
+mod algorithm_comparison;
+mod borrowck_errors;
+mod borrowck_facts;
+mod constraint_cut;
+mod diagnostics;
 //mod driver_utils;
 mod dump_borrowck_info;
+mod error_codes;
+mod explain;
 mod facts;
+mod liveness;
+mod location_sensitive;
+mod move_errors;
+mod option_names;
+mod options;
+mod output;
+mod path_search;
 mod regions;
 
-use std::env::set_var;
-use rustc::hir::def_id::DefId;
+use rustc_driver::Compilation;
+use rustc_hir::def_id::DefId;
 use rustc_interface::interface;
+use rustc_interface::Queries;
+use options::RustLifeOptions;
 
 pub type ProcedureDefId = DefId;
 
 /// Struct holding the compiler callbacks for rust-life.
-/// Could be used to store state for callbacks, but is not done for now.
-struct RustLifeCallbacks {}
+/// It stores the options of rust-life, since they are needed by the callbacks.
+struct RustLifeCallbacks<'o> {
+    options: &'o RustLifeOptions,
+}
 
-impl RustLifeCallbacks {
+impl<'o> RustLifeCallbacks<'o> {
     /// Function that creates a RustLifeCallback.
-    fn new() -> RustLifeCallbacks {
-        RustLifeCallbacks { }
+    fn new(options: &'o RustLifeOptions) -> RustLifeCallbacks<'o> {
+        RustLifeCallbacks {
+            options,
+        }
     }
 }
 
-impl rustc_driver::Callbacks for RustLifeCallbacks {
-    fn after_parsing(&mut self, compiler: &interface::Compiler) -> bool {
-        trace!("[RustLifeCallbacks.after_parsing] enter");
+impl<'o> rustc_driver::Callbacks for RustLifeCallbacks<'o> {
+    fn config(&mut self, config: &mut interface::Config) {
+        // Replace the borrow checker query by one that keeps the facts of every body for us.
+        config.override_queries = Some(borrowck_facts::override_queries);
+    }
+
+    fn after_expansion<'tcx>(&mut self, _compiler: &interface::Compiler,
+                             queries: &'tcx Queries<'tcx>) -> Compilation {
+        trace!("[RustLifeCallbacks.after_expansion] enter");
 
-        // TODO pass correct args.
-        compiler
+        // The hook of rustc for the emission of diagnostics is installed by now, so it can be
+        // wrapped by the one that holds back the errors of the borrow checker.
+        borrowck_errors::install_hook();
+
+        let options = self.options;
+        queries
             .global_ctxt()
             .unwrap()
-            .peek_mut()
-            .enter(|tcx| dump_borrowck_info::dump_borrowck_info(tcx));
-        // Ev. change the called function to take tcx by reference?
+            .enter(|tcx| {
+                // Run the analysis (and thereby the borrow checker on all bodies) here, since the
+                // after_analysis callback is not called any more if the analysis reports errors,
+                // which is exactly the case we are interested in. The errors of the borrow checker
+                // are held back and reported once the explanations are attached to them (by the
+                // guard, so that they are reported even if something panics on the way).
+                let _held_errors = borrowck_errors::HeldErrorsGuard::new(tcx);
+                let _ = tcx.analysis(());
+                dump_borrowck_info::dump_borrowck_info(tcx, options);
+            });
 
         // Stop!
-        false
+        Compilation::Stop
     }
 
 }
@@ -64,20 +104,28 @@ impl rustc_driver::Callbacks for RustLifeCallbacks {
 pub fn main() {
     env_logger::init();
     trace!("[main] enter");
-    set_var("POLONIUS_ALGORITHM", "Naive");
     let mut args: Vec<String> = std::env::args().collect();
-    args.push("-Zborrowck=mir".to_owned());
-    //args.push("-Ztwo-phase-borrows".to_owned());
-    args.push("-Zpolonius".to_owned());
-    args.push("-Znll-facts".to_owned());
-    args.push("-Zidentify-regions".to_owned());
-    args.push("-Zdump-mir=all".to_owned());
-    args.push("-Zdump-mir-dir=log/mir/".to_owned());
-
-    let result = rustc_driver::report_ices_to_stderr_if_any(move || {
-        rustc_driver::run_compiler(&args, &mut RustLifeCallbacks::new(), None, None)
-    }).and_then(|result| result);
+    let options = match RustLifeOptions::from_args(&mut args) {
+        Ok(options) => options,
+        Err(msg) => {
+            eprintln!("error: {}", msg);
+            std::process::exit(1);
+        }
+    };
+    debug!("Output directory: {:?}, work directory: {:?}", options.out_dir, options.work_dir);
+    if options.facts_from_files {
+        // The facts are then read from the files that rustc writes, instead of taking them from
+        // the borrow checker directly.
+        args.push("-Znll-facts".to_owned());
+        args.push(format!("-Znll-facts-dir={}", options.facts_dir().display()));
+    }
+
+    let exit_code = rustc_driver::catch_with_exit_code(|| {
+        rustc_driver::RunCompiler::new(&args, &mut RustLifeCallbacks::new(&options)).run()
+    });
+
+    options.clean_up_work_dir();
 
     trace!("[main] exit");
-    std::process::exit(result.is_err() as i32);
+    std::process::exit(exit_code);
 }
@@ -0,0 +1,258 @@
+// © 2020, ETH Zurich
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+/// Code for explaining the use of (possibly) moved values (E0382), based on the move and
+/// initialization facts of Polonius (path_moved_at_base, path_assigned_at_base,
+/// path_accessed_at_base, child_path and path_is_var).
+/// This is the counterpart of the ErrorPathFinder for errors about loans: Instead of a path in the
+/// outlives relation, a move error is explained by a path in the control flow graph, from the point
+/// where the value is moved to the point where it is used afterwards.
+
+use dump_borrowck_info::serde_derive;
+use error_codes::RustcErrorCode;
+use facts::{self, MovePath, PointIndex, Variable};
+use rustc_span::Span;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// This struct holds the data that is needed to find a path in the control flow graph that explains
+/// a move error, i.e. the use of a move path at a point where it may be moved out.
+/// After initializing all fields (best done by using the provided constructor), call
+/// compute_move_path() to run the actual path computation.
+pub struct MoveErrorPathFinder<'a> {
+    all_facts: &'a facts::AllInputFacts,
+    /// The point where the (possibly) moved path is used, as given by the move_errors output.
+    error_point: PointIndex,
+    /// The path that is used while it may be moved out.
+    moved_path: MovePath,
+}
+
+impl<'a> MoveErrorPathFinder<'a> {
+    pub fn new(all_facts: &'a facts::AllInputFacts, error_point: PointIndex, moved_path: MovePath)
+            -> Self {
+        MoveErrorPathFinder {
+            all_facts,
+            error_point,
+            moved_path,
+        }
+    }
+
+    /// Computes the shortest path in the control flow graph from a point where the path (or one of
+    /// its parents, which moves the path as well) is moved to the error point, that does not pass
+    /// through an assignment to the path (or to one of its parents, which initializes the path
+    /// again). The path contains at least one edge, so a move in a loop that is reached again in a
+    /// later iteration (e.g. a value that is moved into a function call in the loop body) is found
+    /// as well.
+    /// The result is the list of the points on the path, starting at the point of the move and
+    /// ending at the error point. None if no such path is found.
+    pub fn compute_move_path(&self) -> Option<Vec<PointIndex>> {
+        trace!("[compute_move_path] enter");
+
+        let related_paths = self.path_and_parents();
+        debug!("related_paths: {:?}", related_paths);
+        let move_points: HashSet<PointIndex> = self.all_facts.path_moved_at_base.iter()
+            .filter(|(path, _)| related_paths.contains(path))
+            .map(|&(_, point)| point)
+            .collect();
+        let assignment_points: HashSet<PointIndex> = self.all_facts.path_assigned_at_base.iter()
+            .filter(|(path, _)| related_paths.contains(path))
+            .map(|&(_, point)| point)
+            .collect();
+        debug!("move_points: {:?}, assignment_points: {:?}", move_points, assignment_points);
+
+        let mut predecessors: HashMap<PointIndex, Vec<PointIndex>> = HashMap::new();
+        for &(from, to) in self.all_facts.cfg_edge.iter() {
+            predecessors.entry(to).or_default().push(from);
+        }
+
+        // breadth-first search backwards from the error point, the successor of every visited point
+        // on the way to the error point is stored, to reconstruct the path.
+        let mut successors: HashMap<PointIndex, PointIndex> = HashMap::new();
+        let mut queue: VecDeque<PointIndex> = VecDeque::new();
+        queue.push_back(self.error_point);
+        while let Some(point) = queue.pop_front() {
+            for &predecessor in predecessors.get(&point).into_iter().flatten() {
+                if successors.contains_key(&predecessor) {
+                    continue;
+                }
+                successors.insert(predecessor, point);
+                if move_points.contains(&predecessor) {
+                    let mut path = vec![predecessor];
+                    let mut cur_point = predecessor;
+                    while let Some(&next_point) = successors.get(&cur_point) {
+                        path.push(next_point);
+                        if next_point == self.error_point {
+                            break;
+                        }
+                        cur_point = next_point;
+                    }
+                    debug!("move path: {:?}", path);
+                    trace!("[compute_move_path] exit");
+                    return Some(path);
+                }
+                // the path is initialized again by an assignment, so any move before it does not
+                // reach the error point on this way.
+                if !assignment_points.contains(&predecessor) {
+                    queue.push_back(predecessor);
+                }
+            }
+        }
+
+        warn!("No move was found for the use of {:?} at {:?}!", self.moved_path, self.error_point);
+        trace!("[compute_move_path] exit");
+        None
+    }
+
+    /// Gives the variable whose value contains the moved path, i.e. the variable of the root of
+    /// the path.
+    pub fn root_variable(&self) -> Option<Variable> {
+        let root_path = *self.path_and_parents().last().unwrap();
+        self.all_facts.path_is_var.iter()
+            .find(|&&(path, _)| path == root_path)
+            .map(|&(_, variable)| variable)
+    }
+
+    /// Gives the moved path followed by all its parents (as given by child_path), the root of the
+    /// path is the last element.
+    fn path_and_parents(&self) -> Vec<MovePath> {
+        let mut paths = vec![self.moved_path];
+        let mut cur_path = self.moved_path;
+        while let Some(&(_, parent)) = self.all_facts.child_path.iter()
+                .find(|&&(child, _)| child == cur_path) {
+            if paths.contains(&parent) {
+                break;
+            }
+            paths.push(parent);
+            cur_path = parent;
+        }
+        paths
+    }
+}
+
+/// This struct describes the explanation of a move error, i.e. of the use of a value that may have
+/// been moved before. Like the EnrichedErrorGraph for errors about loans, it is filled by the
+/// MirInfoPrinter, and can be dumped as JSON.
+/// The explanation is a path in the control flow graph, from the move to the use, given by the
+/// source lines on it.
+#[derive(serde_derive::Serialize)]
+pub struct MoveErrorGraph {
+    /// The name of the function that this error was found in, in the same format as in the
+    /// EnrichedErrorGraph.
+    pub function_name: String,
+    /// The code that rustc uses for the error, this is always E0382.
+    pub error_code: RustcErrorCode,
+    /// The move path that is used after it may have been moved.
+    pub moved_path: MovePath,
+    /// The name of the variable that the moved path belongs to. (If only a part of the variable is
+    /// moved, the path is a child of the variable.)
+    pub variable_name: String,
+    /// The line where the value is moved, given as it's number and it's source code.
+    pub move_line: (usize, String),
+    /// Describes how the value is moved, e.g. "moved into the call of `foo`".
+    pub move_kind: String,
+    /// The line where the value is used after the move.
+    pub use_line: (usize, String),
+    /// The distinct source lines on the path in the control flow graph from the move to the use, in
+    /// the order of the path, including the line of the move and the one of the use. Consecutive
+    /// points on the same line are given by a single entry.
+    pub lines_on_path: Vec<(usize, String)>,
+    /// True if the move happens in an earlier iteration of a loop, i.e. if the path from the move
+    /// to the use goes back to an earlier line.
+    pub in_loop: bool,
+    /// The span of the move. Not included in a JSON dump, as spans are not serializable.
+    #[serde(skip_serializing)]
+    pub move_span: Span,
+    /// The span of the use. Not included in a JSON dump.
+    #[serde(skip_serializing)]
+    pub use_span: Span,
+}
+
+impl MoveErrorGraph {
+    /// Gives the notes that explain the move error, for attaching them to the error of rustc.
+    pub fn explanation_notes(&self) -> Vec<String> {
+        let mut notes = vec![format!("`{}` is {} at line {}", self.variable_name, self.move_kind,
+                                     self.move_line.0)];
+        if self.in_loop {
+            notes.push(format!("the move at line {} happens in an earlier iteration of the loop \
+                                that contains line {}", self.move_line.0, self.use_line.0));
+        }
+        let intermediate_lines: Vec<String> = self.lines_on_path.iter()
+            .map(|&(line_number, _)| line_number)
+            .filter(|&line_number| line_number != self.move_line.0 &&
+                line_number != self.use_line.0)
+            .map(|line_number| line_number.to_string())
+            .collect();
+        if intermediate_lines.is_empty() {
+            notes.push(format!("`{}` is not assigned a new value before it is used at line {}",
+                               self.variable_name, self.use_line.0));
+        } else {
+            notes.push(format!("`{}` is not assigned a new value on the way over line(s) {} \
+                                before it is used at line {}", self.variable_name,
+                               intermediate_lines.join(", "), self.use_line.0));
+        }
+        notes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MoveErrorPathFinder;
+    use facts::{AllInputFacts, MovePath, PointIndex};
+
+    fn points(points: &[usize]) -> Vec<PointIndex> {
+        points.iter().map(|&point| PointIndex::from(point)).collect()
+    }
+
+    /// Builds the facts of a control flow graph with the given edges, in which the move path 1 is
+    /// a child of the move path 0, and the given paths are moved and assigned at the given points.
+    fn facts(cfg_edges: &[(usize, usize)], moves: &[(usize, usize)],
+             assignments: &[(usize, usize)]) -> AllInputFacts {
+        let pairs = |pairs: &[(usize, usize)]| pairs.iter()
+            .map(|&(path, point)| (MovePath::from(path), PointIndex::from(point)))
+            .collect();
+        AllInputFacts {
+            cfg_edge: cfg_edges.iter()
+                .map(|&(from, to)| (PointIndex::from(from), PointIndex::from(to)))
+                .collect(),
+            child_path: vec![(MovePath::from(1), MovePath::from(0))],
+            path_moved_at_base: pairs(moves),
+            path_assigned_at_base: pairs(assignments),
+            ..AllInputFacts::default()
+        }
+    }
+
+    fn compute_move_path(all_facts: &AllInputFacts, error_point: usize, moved_path: usize)
+            -> Option<Vec<PointIndex>> {
+        MoveErrorPathFinder::new(all_facts, PointIndex::from(error_point),
+                                 MovePath::from(moved_path)).compute_move_path()
+    }
+
+    #[test]
+    fn a_move_of_the_parent_path_is_found() {
+        // the whole value (path 0) is moved at 1, its field (path 1) is used at 3.
+        let all_facts = facts(&[(0, 1), (1, 2), (2, 3)], &[(0, 1)], &[]);
+        assert_eq!(compute_move_path(&all_facts, 3, 1), Some(points(&[1, 2, 3])));
+        // a move of the field does not move the whole value, though.
+        let all_facts = facts(&[(0, 1), (1, 2), (2, 3)], &[(1, 1)], &[]);
+        assert_eq!(compute_move_path(&all_facts, 3, 0), None);
+    }
+
+    #[test]
+    fn the_search_is_blocked_by_a_reassignment() {
+        // the path is moved at 1, and assigned again at 2 on the way to the use at 3.
+        let all_facts = facts(&[(0, 1), (1, 2), (2, 3)], &[(1, 1)], &[(0, 2)]);
+        assert_eq!(compute_move_path(&all_facts, 3, 1), None);
+        // with a second way from 1 over 4 to the use, the move reaches the use on this way.
+        let all_facts = facts(&[(0, 1), (1, 2), (2, 3), (1, 4), (4, 3)], &[(1, 1)], &[(0, 2)]);
+        assert_eq!(compute_move_path(&all_facts, 3, 1), Some(points(&[1, 4, 3])));
+    }
+
+    #[test]
+    fn a_move_in_an_earlier_iteration_of_a_loop_is_found() {
+        // the path is moved and used at 1, in a loop over 1 and 2.
+        let all_facts = facts(&[(0, 1), (1, 2), (2, 1)], &[(1, 1)], &[]);
+        assert_eq!(compute_move_path(&all_facts, 1, 1), Some(points(&[1, 2, 1])));
+    }
+}
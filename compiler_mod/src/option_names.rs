@@ -0,0 +1,22 @@
+// © 2020, ETH Zurich
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+/// The names of the command line options of the rust-life driver. They are parsed by the options
+/// module, and the cargo front end (src/bin/cargo-rust-life.rs, which includes this file as module
+/// of its own) needs them to tell which of its arguments (and their values) are for the driver.
+/// Hence a new option only has to be added here and in RustLifeOptions::from_args().
+
+/// The prefix that all options of rust-life share.
+pub const OPTION_PREFIX: &str = "--rust-life-";
+
+/// The options (without the prefix) that take a value, given either as `<option> <value>` or as
+/// `<option>=<value>`.
+pub const VALUE_OPTIONS: &[&str] = &["out-dir", "work-dir", "fn", "position", "explain",
+                                     "path-search", "alternatives", "algorithm", "compare"];
+
+/// The options (without the prefix) that do not take a value.
+pub const FLAG_OPTIONS: &[&str] = &["keep-work-dir", "facts-from-files", "json-diagnostics",
+                                    "constraint-cut", "explain-rustc-only"];
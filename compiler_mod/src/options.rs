@@ -0,0 +1,499 @@
+// © 2020, ETH Zurich
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+/// Code for the command line options of rust-life. These are passed to the driver together with the
+/// normal arguments for rustc, and are removed from them before rustc gets to see the arguments.
+/// All options of rust-life start with `--rust-life-`, and they can be given either as
+/// `--rust-life-<name> <value>` or as `--rust-life-<name>=<value>`.
+
+use dump_borrowck_info::polonius_engine::Algorithm;
+use explain::ExplanationFormat;
+use option_names::{FLAG_OPTIONS, OPTION_PREFIX, VALUE_OPTIONS};
+use path_search::PathSearch;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The directory (relative to the working directory the driver is started in) that the results are
+/// written to if no `--rust-life-out-dir` is given.
+const DEFAULT_OUT_DIR: &str = "rust-life-out";
+
+/// Holds the options that were passed to rust-life.
+/// All paths are absolute, so they stay valid even if the working directory of the process is
+/// changed.
+pub struct RustLifeOptions {
+    /// The directory that all results (i.e. the manifest and the explanations of the errors) are
+    /// written to. Given by `--rust-life-out-dir <dir>`.
+    pub out_dir: PathBuf,
+    /// The directory that the files that are only needed while running (e.g. the facts that rustc
+    /// dumps if --rust-life-facts-from-files is given) are written to. Given by `--rust-life-work-dir <dir>`, if this option
+    /// is not given, a fresh temporary directory is used for every run, so that concurrent runs do
+    /// not interfere.
+    pub work_dir: PathBuf,
+    /// If true, the work directory is not deleted at the end of the run. This is always the case if
+    /// the work directory was given explicitly, otherwise it can be requested by the flag
+    /// `--rust-life-keep-work-dir`. (Useful for debugging.)
+    pub keep_work_dir: bool,
+    /// If true, rustc writes the Polonius facts to files in the work directory, and they are read
+    /// from there, instead of taking them from the borrow checker in memory. This is only intended
+    /// for debugging, e.g. to check the facts of a run. Given by `--rust-life-facts-from-files`.
+    pub facts_from_files: bool,
+    /// If true, the explanation of every error that is explained and reported by rustc is printed
+    /// to stderr as a diagnostic in the JSON format of rustc (as with `--error-format=json`, one
+    /// line per diagnostic), with the explanation given by its secondary spans and notes. The
+    /// diagnostic is a note, not a second error, and replaces the notes that are otherwise
+    /// attached to the error of rustc. Given by `--rust-life-json-diagnostics`.
+    pub json_diagnostics: bool,
+    /// If given, every error that is explained is also explained in prose (see the explain
+    /// module), which is written in this format next to the graphs of the error. Given by
+    /// `--rust-life-explain <text|markdown>`.
+    pub explanation_format: Option<ExplanationFormat>,
+    /// If true, the constraints whose removal makes an error disappear are searched for every error
+    /// that is explained (see the constraint_cut module). This requires running Polonius again for
+    /// every candidate, hence it is only done on request, by the flag `--rust-life-constraint-cut`.
+    pub constraint_cut: bool,
+    /// The strategy for choosing the path that explains an error, given by
+    /// `--rust-life-path-search <first|edges|lines|cfg>`. By default, the path with the fewest
+    /// edges is taken.
+    pub path_search: PathSearch,
+    /// The number of alternative paths (ranked after the one that explains the error) that are
+    /// included in the explanation of every error, given by `--rust-life-alternatives <k>`. None
+    /// by default.
+    pub alternative_paths: usize,
+    /// The algorithm of Polonius that computes the errors that are explained, given by
+    /// `--rust-life-algorithm <name>` (with the names of Algorithm::from_str(), e.g. `Naive`,
+    /// `DatafrogOpt` or `LocationInsensitive`). By default, the naive algorithm is used. Note that
+    /// the explanations need the location sensitive results (e.g. which regions contain a loan at
+    /// a point), which the location insensitive algorithm does not compute, so its errors are
+    /// reported without explanations.
+    pub polonius_algorithm: Algorithm,
+    /// Further algorithms of Polonius that are run on the same facts as polonius_algorithm, to
+    /// check if they find the same errors (see the algorithm_comparison module). Given by
+    /// `--rust-life-compare <name>[,<name>...]`, none by default.
+    pub compare_algorithms: Vec<Algorithm>,
+    /// If true, the errors that rustc (NLL) reports, but Polonius accepts, are explained as well,
+    /// by the errors that the location insensitive algorithm of Polonius finds for the same facts.
+    /// (These are typically errors for borrows that are only returned on some paths.) Given by
+    /// the flag `--rust-life-explain-rustc-only`.
+    pub explain_rustc_only: bool,
+    /// Glob patterns that select the functions that shall be analyzed, given by (possibly repeated)
+    /// `--rust-life-fn <glob>`. A function is selected if any pattern matches its def path (as it
+    /// is given in the manifest, optionally prefixed with the name of the crate, e.g. `Foo::bar`
+    /// or `my_crate::Foo::bar`). In the patterns, `*` matches any sequence of characters
+    /// (including `::`) and `?` matches any single character. If no pattern is given, all
+    /// functions are analyzed.
+    pub function_globs: Vec<String>,
+    /// A position in the source code, given by `--rust-life-position <file>:<line>[:<column>]`. If
+    /// it is given, only the innermost function that contains this position is analyzed, and only
+    /// the error that is closest to this position is explained. (This is what an editor needs to
+    /// explain the error at the cursor.)
+    pub position: Option<SourcePosition>,
+    /// The working directory that the driver was started in. Relative paths of source files, as
+    /// they are given to rustc, are relative to it.
+    pub invocation_dir: PathBuf,
+}
+
+/// A position in a source file, as given by the user. Lines and columns are counted from 1, as
+/// editors do.
+pub struct SourcePosition {
+    /// The (absolute) path of the source file.
+    pub file: PathBuf,
+    pub line: usize,
+    /// The column, if none is given, only the line is considered.
+    pub column: Option<usize>,
+}
+
+impl SourcePosition {
+    /// Parses a position given as `<file>:<line>[:<column>]`. Relative paths are made absolute
+    /// against the passed directory.
+    fn parse(value: &str, cur_dir: &Path) -> Result<Self, String> {
+        let parse_number = |number: &str| number.parse::<usize>().ok().filter(|&n| n > 0);
+        let invalid = || format!("invalid position `{}`, expected `<file>:<line>[:<column>]`", value);
+        // split from the right, since the path of the file might contain colons as well.
+        let parts: Vec<&str> = value.rsplitn(3, ':').collect();
+        let (file, line, column) = match parts.as_slice() {
+            [last, middle, first] => match (parse_number(middle), parse_number(last)) {
+                (Some(line), Some(column)) => (first.to_string(), line, Some(column)),
+                (None, Some(line)) => (format!("{}:{}", first, middle), line, None),
+                _ => return Err(invalid()),
+            },
+            [last, first] => (first.to_string(), parse_number(last).ok_or_else(invalid)?, None),
+            _ => return Err(invalid()),
+        };
+        Ok(SourcePosition {
+            file: cur_dir.join(file),
+            line,
+            column,
+        })
+    }
+
+    /// Checks if the given file, with a path as given to rustc, is the file of this position. A
+    /// relative path is interpreted relative to the passed directory.
+    pub fn is_in_file(&self, file: &Path, invocation_dir: &Path) -> bool {
+        let file = invocation_dir.join(file);
+        match (fs::canonicalize(&file), fs::canonicalize(&self.file)) {
+            (Ok(file), Ok(own_file)) => file == own_file,
+            _ => file == self.file,
+        }
+    }
+}
+
+impl RustLifeOptions {
+    /// Extracts all options of rust-life from the passed arguments. The options that were
+    /// recognized are removed from args, so the remaining arguments can be passed on to rustc.
+    /// If no work directory was given, a new temporary one is created.
+    /// Returns a message that describes the problem if an option is unknown or lacks its value, or
+    /// if a directory cannot be created.
+    pub fn from_args(args: &mut Vec<String>) -> Result<Self, String> {
+        let mut out_dir = None;
+        let mut work_dir = None;
+        let mut keep_work_dir = false;
+        let mut facts_from_files = false;
+        let mut json_diagnostics = false;
+        let mut explanation_format = None;
+        let mut constraint_cut = false;
+        let mut path_search = PathSearch::ShortestByEdges;
+        let mut alternative_paths = 0;
+        let mut polonius_algorithm = Algorithm::Naive;
+        let mut compare_algorithms = Vec::new();
+        let mut explain_rustc_only = false;
+        let mut function_globs = Vec::new();
+        let mut position = None;
+
+        let mut remaining_args = Vec::with_capacity(args.len());
+        let mut args_iter = args.drain(..);
+        while let Some(arg) = args_iter.next() {
+            if !arg.starts_with(OPTION_PREFIX) {
+                remaining_args.push(arg);
+                continue;
+            }
+            let (name, inline_value) = match arg.find('=') {
+                Some(pos) => (arg[OPTION_PREFIX.len()..pos].to_owned(), Some(arg[pos + 1..].to_owned())),
+                None => (arg[OPTION_PREFIX.len()..].to_owned(), None),
+            };
+            match name.as_str() {
+                value_option if VALUE_OPTIONS.contains(&value_option) => {
+                    let value = match inline_value.or_else(|| args_iter.next()) {
+                        Some(value) => value,
+                        None => return Err(format!("missing value for option `{}`", arg)),
+                    };
+                    match name.as_str() {
+                        "out-dir" => out_dir = Some(PathBuf::from(value)),
+                        "work-dir" => work_dir = Some(PathBuf::from(value)),
+                        "fn" => function_globs.push(value),
+                        "explain" => explanation_format = Some(ExplanationFormat::parse(&value)?),
+                        "path-search" => path_search = PathSearch::parse(&value)?,
+                        "alternatives" => alternative_paths = value.parse::<usize>().map_err(|_|
+                            format!("invalid number of alternatives `{}`", value)
+                        )?,
+                        "algorithm" => polonius_algorithm = parse_algorithm(&value)?,
+                        "compare" => for name in value.split(',') {
+                            compare_algorithms.push(parse_algorithm(name.trim())?);
+                        },
+                        "position" => position = Some(value),
+                        _ => unreachable!("the option `{}` is not handled", arg),
+                    }
+                },
+                flag if FLAG_OPTIONS.contains(&flag) => {
+                    if inline_value.is_some() {
+                        return Err(format!("option `{}` does not take a value", arg));
+                    }
+                    match flag {
+                        "keep-work-dir" => keep_work_dir = true,
+                        "facts-from-files" => facts_from_files = true,
+                        "json-diagnostics" => json_diagnostics = true,
+                        "constraint-cut" => constraint_cut = true,
+                        "explain-rustc-only" => explain_rustc_only = true,
+                        _ => unreachable!("the option `{}` is not handled", arg),
+                    }
+                },
+                _ => return Err(format!("unknown option `{}`", arg)),
+            }
+        }
+        drop(args_iter);
+        *args = remaining_args;
+
+        let cur_dir = env::current_dir().map_err(|err| err.to_string())?;
+        let position = match position {
+            Some(position) => Some(SourcePosition::parse(&position, &cur_dir)?),
+            None => None,
+        };
+        let out_dir = cur_dir.join(out_dir.unwrap_or_else(|| PathBuf::from(DEFAULT_OUT_DIR)));
+        let work_dir = match work_dir {
+            Some(work_dir) => {
+                keep_work_dir = true;
+                let work_dir = cur_dir.join(work_dir);
+                fs::create_dir_all(&work_dir).map_err(|err|
+                    format!("cannot create work directory {:?}: {}", work_dir, err)
+                )?;
+                work_dir
+            },
+            None => create_temp_work_dir().map_err(|err|
+                format!("cannot create a temporary work directory: {}", err)
+            )?,
+        };
+
+        Ok(RustLifeOptions {
+            out_dir,
+            work_dir,
+            keep_work_dir,
+            facts_from_files,
+            json_diagnostics,
+            explanation_format,
+            constraint_cut,
+            path_search,
+            alternative_paths,
+            polonius_algorithm,
+            compare_algorithms,
+            explain_rustc_only,
+            function_globs,
+            position,
+            invocation_dir: cur_dir,
+        })
+    }
+
+    /// Checks if the function with the given def path (as given in the manifest) shall be
+    /// analyzed, according to the glob patterns that were passed. (See function_globs.)
+    pub fn is_function_selected(&self, crate_name: &str, def_path: &str) -> bool {
+        let crate_def_path = format!("{}::{}", crate_name, def_path);
+        self.function_globs.is_empty() || self.function_globs.iter().any(|glob|
+            glob_matches(glob.as_bytes(), def_path.as_bytes()) ||
+                glob_matches(glob.as_bytes(), crate_def_path.as_bytes())
+        )
+    }
+
+    /// Gives the directory that rustc writes the Polonius facts to if they shall be read from files.
+    /// (Is passed as -Znll-facts-dir)
+    pub fn facts_dir(&self) -> PathBuf {
+        self.work_dir.join("nll-facts")
+    }
+
+    /// Deletes the work directory, unless it shall be kept. Failures are only reported to the log,
+    /// since they do not affect the results of the run.
+    pub fn clean_up_work_dir(&self) {
+        if self.keep_work_dir {
+            info!("Keeping the work directory {:?}", self.work_dir);
+        } else if let Err(err) = fs::remove_dir_all(&self.work_dir) {
+            warn!("Unable to remove the work directory {:?}: {}", self.work_dir, err);
+        }
+    }
+}
+
+/// Parses the name of an algorithm of Polonius, as Algorithm::from_str() does (i.e. ignoring the
+/// case), but with a message that tells which option is invalid.
+fn parse_algorithm(value: &str) -> Result<Algorithm, String> {
+    Algorithm::from_str(value).map_err(|err|
+        format!("invalid Polonius algorithm `{}`, {}", value, err)
+    )
+}
+
+/// Checks if the text matches the glob pattern, where `*` matches any sequence of characters and `?`
+/// matches any single character. (Works on bytes, a `?` therefore matches only ASCII characters.)
+/// Only the last `*` is ever retried (by letting it consume one more character), since an earlier
+/// `*` can not produce any match that the last one misses. Hence the match takes at most
+/// len(glob) * len(text) steps, instead of backtracking exponentially over all stars.
+fn glob_matches(glob: &[u8], text: &[u8]) -> bool {
+    let mut glob_pos = 0;
+    let mut text_pos = 0;
+    // the position of the last `*` in the glob, and the position in the text where the part of the
+    // glob after it is tried to match next.
+    let mut last_star: Option<(usize, usize)> = None;
+    while text_pos < text.len() {
+        match glob.get(glob_pos) {
+            Some(b'*') => {
+                last_star = Some((glob_pos, text_pos));
+                glob_pos += 1;
+            },
+            Some(&c) if c == b'?' || c == text[text_pos] => {
+                glob_pos += 1;
+                text_pos += 1;
+            },
+            _ => match last_star {
+                Some((star_pos, star_text_pos)) => {
+                    last_star = Some((star_pos, star_text_pos + 1));
+                    glob_pos = star_pos + 1;
+                    text_pos = star_text_pos + 1;
+                },
+                None => return false,
+            },
+        }
+    }
+    glob[glob_pos..].iter().all(|&c| c == b'*')
+}
+
+/// Creates a new, empty directory in the temporary directory of the system. The name of the
+/// directory contains the id of the process and the current time, so that every run of rust-life
+/// gets its own directory.
+fn create_temp_work_dir() -> io::Result<PathBuf> {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos())
+        .unwrap_or(0);
+    let work_dir = env::temp_dir().join(format!("rust-life-{}-{}", process::id(), nanos));
+    fs::create_dir(&work_dir)?;
+    Ok(work_dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{glob_matches, RustLifeOptions, SourcePosition};
+    use option_names::{FLAG_OPTIONS, OPTION_PREFIX, VALUE_OPTIONS};
+    use std::env;
+    use std::path::{Path, PathBuf};
+
+    fn matches(glob: &str, text: &str) -> bool {
+        glob_matches(glob.as_bytes(), text.as_bytes())
+    }
+
+    #[test]
+    fn glob_without_wildcards_matches_only_the_same_text() {
+        assert!(matches("Foo::bar", "Foo::bar"));
+        assert!(!matches("Foo::bar", "Foo::baz"));
+        assert!(!matches("Foo::bar", "Foo::bar2"));
+        assert!(!matches("Foo::bar2", "Foo::bar"));
+        assert!(matches("", ""));
+        assert!(!matches("", "a"));
+    }
+
+    #[test]
+    fn glob_star_matches_any_sequence_including_path_separators() {
+        assert!(matches("*", ""));
+        assert!(matches("*", "main"));
+        assert!(matches("Foo::*", "Foo::bar"));
+        assert!(matches("Foo::*", "Foo::"));
+        assert!(matches("*::bar", "a::b::bar"));
+        assert!(matches("main*", "main::{closure#0}"));
+        assert!(matches("*closure*", "main::{closure#0}"));
+        assert!(!matches("Foo::*", "Bar::foo"));
+        assert!(matches("a**b", "ab"));
+    }
+
+    #[test]
+    fn glob_question_mark_matches_a_single_character() {
+        assert!(matches("ba?", "bar"));
+        assert!(!matches("ba?", "ba"));
+        assert!(!matches("ba?", "barr"));
+        assert!(matches("?*", "x"));
+        assert!(!matches("?*", ""));
+    }
+
+    #[test]
+    fn glob_star_backtracks_to_later_occurrences() {
+        assert!(matches("*ab", "aab"));
+        assert!(matches("a*b*c", "abbbc"));
+        assert!(matches("*a*b", "xaxxab"));
+        assert!(!matches("*a*b", "xaxxa"));
+        assert!(matches("*::*::f", "a::b::c::f"));
+    }
+
+    #[test]
+    fn glob_with_many_stars_finishes_quickly() {
+        let text = "a".repeat(100);
+        let glob = format!("{}b", "*a".repeat(20));
+        assert!(!matches(&glob, &text));
+    }
+
+    fn parse(value: &str) -> Result<SourcePosition, String> {
+        SourcePosition::parse(value, Path::new("/work"))
+    }
+
+    #[test]
+    fn position_with_line() {
+        let position = parse("src/main.rs:10").unwrap();
+        assert_eq!(position.file, PathBuf::from("/work/src/main.rs"));
+        assert_eq!(position.line, 10);
+        assert_eq!(position.column, None);
+    }
+
+    #[test]
+    fn position_with_line_and_column() {
+        let position = parse("src/main.rs:10:5").unwrap();
+        assert_eq!(position.file, PathBuf::from("/work/src/main.rs"));
+        assert_eq!(position.line, 10);
+        assert_eq!(position.column, Some(5));
+    }
+
+    #[test]
+    fn position_in_file_whose_path_contains_a_colon() {
+        let position = parse("C:\\x.rs:10").unwrap();
+        assert_eq!(position.file, Path::new("/work").join("C:\\x.rs"));
+        assert_eq!(position.line, 10);
+        assert_eq!(position.column, None);
+
+        let position = parse("C:\\x.rs:10:5").unwrap();
+        assert_eq!(position.file, Path::new("/work").join("C:\\x.rs"));
+        assert_eq!(position.line, 10);
+        assert_eq!(position.column, Some(5));
+    }
+
+    #[test]
+    fn absolute_position_is_not_joined() {
+        let position = parse("/src/lib.rs:3").unwrap();
+        assert_eq!(position.file, PathBuf::from("/src/lib.rs"));
+    }
+
+    #[test]
+    fn invalid_positions_are_rejected() {
+        assert!(parse("main.rs").is_err());
+        assert!(parse("main.rs:").is_err());
+        assert!(parse("main.rs:0").is_err());
+        assert!(parse("main.rs:x").is_err());
+        assert!(parse("main.rs:10:x").is_err());
+        assert!(parse("main.rs:10:0").is_err());
+    }
+
+    /// A valid value for every option that takes one, to check that all of them are known.
+    fn example_value(option: &str) -> &'static str {
+        match option {
+            "out-dir" => "rust-life-out",
+            "work-dir" => "rust-life-options-test",
+            "fn" => "*",
+            "position" => "main.rs:1",
+            "explain" => "text",
+            "path-search" => "edges",
+            "alternatives" => "2",
+            "algorithm" => "Naive",
+            "compare" => "Naive,DatafrogOpt",
+            _ => panic!("no example value for the option `{}`", option),
+        }
+    }
+
+    #[test]
+    fn all_shared_option_names_are_accepted_and_removed() {
+        let work_dir = env::temp_dir().join("rust-life-options-test");
+        let mut args = vec!["rustc".to_string(), "main.rs".to_string()];
+        for option in VALUE_OPTIONS {
+            args.push(format!("{}{}", OPTION_PREFIX, option));
+            if *option == "work-dir" {
+                args.push(work_dir.to_string_lossy().into_owned());
+            } else {
+                args.push(example_value(option).to_string());
+            }
+        }
+        for option in FLAG_OPTIONS {
+            args.push(format!("{}{}", OPTION_PREFIX, option));
+        }
+        let options = RustLifeOptions::from_args(&mut args).unwrap();
+        assert_eq!(args, vec!["rustc".to_string(), "main.rs".to_string()]);
+        assert!(options.keep_work_dir && options.facts_from_files && options.json_diagnostics &&
+                options.constraint_cut && options.explain_rustc_only);
+        assert_eq!(options.work_dir, work_dir);
+        assert_eq!(options.alternative_paths, 2);
+    }
+
+    #[test]
+    fn unknown_options_and_flags_with_values_are_rejected() {
+        let mut args = vec!["--rust-life-unknown".to_string()];
+        assert!(RustLifeOptions::from_args(&mut args).is_err());
+        let mut args = vec!["--rust-life-keep-work-dir=yes".to_string()];
+        assert!(RustLifeOptions::from_args(&mut args).is_err());
+        let mut args = vec!["--rust-life-fn".to_string()];
+        assert!(RustLifeOptions::from_args(&mut args).is_err());
+    }
+}
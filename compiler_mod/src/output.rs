@@ -0,0 +1,164 @@
+// © 2020, ETH Zurich
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+/// Code for laying out the files that are written by rust-life, and for the manifest that lists
+/// all analyzed functions, their errors and the files that were produced for them.
+
+use facts::{Loan, MovePath, Region};
+use dump_borrowck_info::serde_derive;
+use dump_borrowck_info::serde_json;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Name of the manifest file, it is always located directly in the root of the output directory.
+const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/// Describes where the files that are written by rust-life are located.
+/// All files are placed below a single root directory. For every error of a function there is a
+/// separate directory, keyed by the name of the crate, the (file name friendly) def path of the
+/// function and the index of the error, i.e. `<root>/<crate>/<def path>/error_<index>/`. This
+/// gives a deterministic location for the explanation of every error in a crate.
+/// All paths that are returned by the methods of this struct (except the ones that are explicitly
+/// absolute) are relative to the root, this is also how they are stored in the manifest.
+pub struct OutputLayout {
+    root: PathBuf,
+}
+
+impl OutputLayout {
+    pub fn new(root: PathBuf) -> Self {
+        OutputLayout {
+            root,
+        }
+    }
+
+    /// Gives the (absolute, or relative to the working directory if the root is) path of the
+    /// manifest file.
+    pub fn manifest_path(&self) -> PathBuf {
+        self.root.join(MANIFEST_FILE_NAME)
+    }
+
+    /// Gives the directory (relative to the root) for the files of the given function, that are not
+    /// about a single error. The function must be given by its def path, in the file name friendly
+    /// form without the crate name. (I.e. as given by DefPath::to_filename_friendly_no_crate())
+    pub fn function_dir(&self, crate_name: &str, function_name: &str) -> PathBuf {
+        PathBuf::from(crate_name).join(function_name)
+    }
+
+    /// Gives the directory (relative to the root) for the error with the given index in the given
+    /// function. The function must be given in the same form as for function_dir(), the directory
+    /// of the error is inside the one of the function.
+    pub fn error_dir(&self, crate_name: &str, function_name: &str, error_index: usize) -> PathBuf {
+        self.function_dir(crate_name, function_name)
+            .join(format!("error_{}", error_index))
+    }
+
+    /// Turns a path that is relative to the root into one that can be used to access the file,
+    /// i.e. one that is absolute or relative to the working directory.
+    pub fn resolve(&self, relative_path: &Path) -> PathBuf {
+        self.root.join(relative_path)
+    }
+
+    /// Creates the directory (given relative to the root) including all its parents if it does not
+    /// exist yet.
+    pub fn create_dir(&self, relative_path: &Path) -> io::Result<()> {
+        fs::create_dir_all(self.resolve(relative_path))
+    }
+
+    /// Writes the manifest (as JSON) to its location in the root, overwriting any manifest that
+    /// already exists there.
+    pub fn write_manifest(&self, manifest: &Manifest) -> io::Result<()> {
+        fs::create_dir_all(&self.root)?;
+        let manifest_file = File::create(self.manifest_path())?;
+        serde_json::to_writer_pretty(manifest_file, manifest)
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))
+    }
+}
+
+/// The manifest lists every function that was analyzed in a crate, the errors that were found in
+/// it and the files that were produced to explain them. It is written to the root of the output
+/// directory and shall allow tools to find the explanation for any error in the crate.
+#[derive(serde_derive::Serialize)]
+pub struct Manifest {
+    /// The name of the crate that was analyzed.
+    pub crate_name: String,
+    /// All functions that were analyzed, sorted by their def path. Functions without any error are
+    /// included as well.
+    pub functions: Vec<FunctionEntry>,
+}
+
+/// The entry of the manifest for a single function.
+#[derive(serde_derive::Serialize)]
+pub struct FunctionEntry {
+    /// The full def path of the function, as it is printed by rustc. (E.g. `main::foo` for a
+    /// function `foo` that is nested in `main`, or `Foo::bar` for a method of `Foo`.)
+    pub def_path: String,
+    /// The file name friendly def path of the function, without the crate name. This is the same
+    /// as the function_name in the EnrichedErrorGraph of the errors of this function.
+    pub function_name: String,
+    /// The comparison of the errors of several algorithms of Polonius for this function (see
+    /// algorithm_comparison), relative to the root of the output directory. None if no comparison
+    /// was requested.
+    pub algorithm_comparison_file: Option<PathBuf>,
+    /// All errors that Polonius reported for this function, ordered by their index.
+    pub errors: Vec<ErrorEntry>,
+}
+
+/// The entry of the manifest for a single error, i.e. for one pair of a point and a loan that is
+/// invalidated at this point, for a pair of universal regions where the first one must outlive
+/// the second one, or for a pair of a point and a move path that is used there after a move.
+/// Errors that only rustc reports (see ErrorStatus::RustcOnly) get an entry as well, but only
+/// with the fields that describe the error of rustc.
+#[derive(serde_derive::Serialize)]
+pub struct ErrorEntry {
+    /// The index of the error in the function, this is also used as part of its directory. Errors
+    /// that only rustc reports are numbered after all errors of Polonius.
+    pub index: usize,
+    /// Tells if the user sees the error, i.e. if the borrow checker of rustc reports it as well.
+    pub status: ErrorStatus,
+    /// The code of the matching error of rustc, if there is one and it has a code.
+    pub rustc_code: Option<String>,
+    /// The message of the matching error of rustc, None if there is none.
+    pub rustc_message: Option<String>,
+    /// The loan that is invalidated by the error. None if the error is not about a loan, but about
+    /// a universal region that must outlive another one, see required_subset.
+    pub loan: Option<Loan>,
+    /// If the error is that a universal region must outlive another one, which is not known from
+    /// the signature of the function, the two regions. (The first must outlive the second.)
+    pub required_subset: Option<(Region, Region)>,
+    /// If the error is the use of a value that may have been moved out, the move path of the
+    /// value.
+    pub moved_path: Option<MovePath>,
+    /// The line where the error occurs, given as it's number and it's source code.
+    pub line: (usize, String),
+    /// The JSON dump of the EnrichedErrorGraph that explains the error, relative to the root of
+    /// the output directory. None if no explanation was found for this error.
+    pub json_file: Option<PathBuf>,
+    /// The (improved) error graph in dot format, relative to the root of the output directory.
+    /// None if no explanation was found for this error.
+    pub dot_file: Option<PathBuf>,
+    /// The explanation of the error in prose (as text or Markdown), relative to the root of the
+    /// output directory. None if no explanation was found, or none was requested.
+    pub explanation_file: Option<PathBuf>,
+}
+
+/// Tells if an error is reported by Polonius (and hence explained by rust-life), by the borrow
+/// checker of rustc (and hence seen by the user), or by both. The two can disagree, e.g. Polonius
+/// accepts some programs that rustc rejects, or it reports an error at a different point.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde_derive::Serialize)]
+pub enum ErrorStatus {
+    /// Both report the error (i.e. rustc reports an error on the line of the point of the
+    /// Polonius error), so the explanation is about an error that the user sees.
+    #[serde(rename = "explained")]
+    Explained,
+    /// Only Polonius reports the error, its explanation is not shown to the user (e.g. as notes
+    /// or JSON diagnostic), since the user does not see the error.
+    #[serde(rename = "polonius-only")]
+    PoloniusOnly,
+    /// Only rustc reports the error, so there is no explanation for it.
+    #[serde(rename = "rustc-only")]
+    RustcOnly,
+}
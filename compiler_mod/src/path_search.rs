@@ -0,0 +1,314 @@
+// © 2020, ETH Zurich
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+/// Code for searching the paths in the outlives relation that explain an error by their cost, as
+/// alternative to the depth-first search of find_path_backwards() (in dump_borrowck_info), which
+/// takes the first path it finds, and hence depends on the order of the outlives relation.
+/// The shortest path is found by Dijkstra's algorithm, further (ranked) alternatives by Yen's
+/// algorithm for the k shortest simple paths.
+/// Like find_path_backwards(), the search goes backwards through the outlives relation: It starts
+/// at the region where the error becomes visible (e.g. the region that contains the loan at the
+/// error point) and ends at a region that fulfills a termination criterion (e.g. the region of
+/// the loan). All paths are given in this backwards order, i.e. starting with the start region.
+
+use facts::{PointIndex, Region};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+/// The strategies for choosing the path that explains an error.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PathSearch {
+    /// The first path that a depth-first search finds. (This is what rust-life always did, it is
+    /// kept for comparison.)
+    FirstFound,
+    /// The path with the fewest edges, i.e. constraints.
+    ShortestByEdges,
+    /// The path whose constraints are closest to the line of the error, see
+    /// MirInfoPrinter::line_distance_cost().
+    ShortestByLines,
+    /// The path with the fewest edges over pairs of a region and a point, that respects the control
+    /// flow graph (see the location_sensitive module). It is only available for invalidated loans,
+    /// other errors use ShortestByEdges instead.
+    LocationSensitive,
+}
+
+impl PathSearch {
+    /// Parses the strategy as it is given on the command line, i.e. `first`, `edges`, `lines` or
+    /// `cfg`.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "first" => Ok(PathSearch::FirstFound),
+            "edges" => Ok(PathSearch::ShortestByEdges),
+            "lines" => Ok(PathSearch::ShortestByLines),
+            "cfg" => Ok(PathSearch::LocationSensitive),
+            _ => Err(format!("invalid path search `{}`, expected `first`, `edges`, `lines` or `cfg`",
+                             value)),
+        }
+    }
+}
+
+/// Finds up to k distinct simple paths (backwards) in the outlives relation from start to a
+/// region that fulfills is_end, ranked by their cost, i.e. the sum of the costs of their edges.
+/// The cost of an edge is given by edge_cost for the two regions of the edge as they are in the
+/// outlives relation (i.e. the region where the path continues first). Paths of equal cost are
+/// ranked by their number of edges.
+/// Returns the paths together with their cost, the cheapest first. The result is empty if no path
+/// is found.
+pub fn find_shortest_paths_backwards<F, C>(outlives: &[(Region, Region, PointIndex)],
+                                           start: Region, is_end: &F, edge_cost: &C, k: usize)
+        -> Vec<(usize, Vec<Region>)>
+        where F: Fn(Region) -> bool, C: Fn(Region, Region) -> usize {
+    trace!("[find_shortest_paths_backwards] enter");
+    let mut predecessors: HashMap<Region, Vec<Region>> = HashMap::new();
+    for &(region1, region2, _) in outlives.iter() {
+        let region_predecessors = predecessors.entry(region2).or_default();
+        if !region_predecessors.contains(&region1) {
+            region_predecessors.push(region1);
+        }
+    }
+    // the cost of a path that is given backwards, i.e. of its edges in reversed direction.
+    let path_cost = |path: &[Region]| -> usize {
+        path.windows(2).map(|regions| edge_cost(regions[1], regions[0])).sum()
+    };
+
+    let mut result: Vec<(usize, Vec<Region>)> = Vec::new();
+    match find_shortest_path(&predecessors, start, is_end, edge_cost, &HashSet::new(),
+                             &HashSet::new()) {
+        Some(shortest_path) => result.push(shortest_path),
+        None => {
+            trace!("[find_shortest_paths_backwards] exit");
+            return result;
+        },
+    }
+
+    // Yen's algorithm: every further path deviates from one of the paths that were found before
+    // at some region (the spur region), and takes the shortest way from there that does not
+    // repeat the regions before it and does not continue like any found path with the same root.
+    let mut candidates: Vec<(usize, Vec<Region>)> = Vec::new();
+    while result.len() < k {
+        let last_path = result.last().unwrap().1.clone();
+        for spur_index in 0..last_path.len().saturating_sub(1) {
+            let root_path = &last_path[..=spur_index];
+            let spur_region = last_path[spur_index];
+            let removed_edges: HashSet<(Region, Region)> = result.iter()
+                .map(|(_, path)| path)
+                .filter(|path| path.len() > spur_index + 1 && &path[..=spur_index] == root_path)
+                .map(|path| (path[spur_index + 1], path[spur_index]))
+                .collect();
+            let removed_regions: HashSet<Region> =
+                root_path[..spur_index].iter().cloned().collect();
+            if let Some((spur_cost, spur_path)) = find_shortest_path(
+                    &predecessors, spur_region, is_end, edge_cost, &removed_edges,
+                    &removed_regions) {
+                let mut path = root_path[..spur_index].to_vec();
+                path.extend(spur_path);
+                let cost = path_cost(root_path) + spur_cost;
+                if !candidates.iter().chain(result.iter()).any(|(_, other)| *other == path) {
+                    candidates.push((cost, path));
+                }
+            }
+        }
+        if candidates.is_empty() {
+            break;
+        }
+        let best_index = (0..candidates.len())
+            .min_by_key(|&index| (candidates[index].0, candidates[index].1.len()))
+            .unwrap();
+        result.push(candidates.remove(best_index));
+    }
+    debug!("ranked paths: {:?}", result);
+    trace!("[find_shortest_paths_backwards] exit");
+    result
+}
+
+/// Dijkstra's algorithm on the reversed outlives relation (given by the predecessors of every
+/// region), from start to the cheapest region that fulfills is_end, ignoring the removed edges
+/// (given in the direction of the outlives relation) and regions. Of several paths with the same
+/// cost, the one with the fewest edges is taken, i.e. the paths are compared by (cost, edges).
+/// Returns the cost and the path (starting with start), None if no such region can be reached.
+fn find_shortest_path<F, C>(predecessors: &HashMap<Region, Vec<Region>>, start: Region,
+                            is_end: &F, edge_cost: &C,
+                            removed_edges: &HashSet<(Region, Region)>,
+                            removed_regions: &HashSet<Region>) -> Option<(usize, Vec<Region>)>
+        where F: Fn(Region) -> bool, C: Fn(Region, Region) -> usize {
+    // the cost and the number of edges of the best path to every region found so far.
+    let mut costs: HashMap<Region, (usize, usize)> = HashMap::new();
+    let mut successors: HashMap<Region, Region> = HashMap::new();
+    let mut queue = BinaryHeap::new();
+    costs.insert(start, (0, 0));
+    queue.push(Reverse((0, 0, start)));
+    while let Some(Reverse((cost, edges, region))) = queue.pop() {
+        if costs.get(&region).map_or(false, |&best_cost| best_cost < (cost, edges)) {
+            // an outdated entry, the region was reached more cheaply in the meantime.
+            continue;
+        }
+        if is_end(region) {
+            let mut path = vec![region];
+            let mut cur_region = region;
+            while let Some(&successor) = successors.get(&cur_region) {
+                path.push(successor);
+                cur_region = successor;
+            }
+            path.reverse();
+            return Some((cost, path));
+        }
+        for &predecessor in predecessors.get(&region).into_iter().flatten() {
+            if removed_regions.contains(&predecessor) ||
+                    removed_edges.contains(&(predecessor, region)) {
+                continue;
+            }
+            let new_cost = (cost + edge_cost(predecessor, region), edges + 1);
+            if costs.get(&predecessor).map_or(true, |&best_cost| new_cost < best_cost) {
+                costs.insert(predecessor, new_cost);
+                successors.insert(predecessor, region);
+                queue.push(Reverse((new_cost.0, new_cost.1, predecessor)));
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_shortest_path, find_shortest_paths_backwards};
+    use facts::{PointIndex, Region};
+    use std::collections::{HashMap, HashSet};
+
+    /// Builds the outlives relation from pairs of region numbers, each with a point of its own.
+    fn outlives(edges: &[(usize, usize)]) -> Vec<(Region, Region, PointIndex)> {
+        edges.iter().enumerate()
+            .map(|(point, &(region1, region2))|
+                (Region::from(region1), Region::from(region2), PointIndex::from(point)))
+            .collect()
+    }
+
+    fn regions(path: &[usize]) -> Vec<Region> {
+        path.iter().map(|&region| Region::from(region)).collect()
+    }
+
+    fn unit_cost(_: Region, _: Region) -> usize {
+        1
+    }
+
+    /// Finds up to k paths from region 0 to region 3, with the given cost of the edges.
+    fn find_paths<C>(edges: &[(usize, usize)], edge_cost: &C, k: usize)
+            -> Vec<(usize, Vec<Region>)>
+            where C: Fn(Region, Region) -> usize {
+        find_shortest_paths_backwards(&outlives(edges), Region::from(0), &|region| {
+            region == Region::from(3)
+        }, edge_cost, k)
+    }
+
+    #[test]
+    fn paths_are_ranked_by_their_cost() {
+        // the paths backwards from 0 to 3 are 0-3, 0-1-3 and 0-2-4-3.
+        let edges = [(1, 0), (3, 1), (3, 0), (2, 0), (4, 2), (3, 4)];
+        assert_eq!(find_paths(&edges, &unit_cost, 5), vec![
+            (1, regions(&[0, 3])),
+            (2, regions(&[0, 1, 3])),
+            (3, regions(&[0, 2, 4, 3])),
+        ]);
+        assert_eq!(find_paths(&edges, &unit_cost, 2).len(), 2);
+    }
+
+    #[test]
+    fn paths_are_ranked_by_the_given_edge_cost() {
+        // the direct edge is the most expensive one, so the longer path comes first.
+        let edges = [(1, 0), (3, 1), (3, 0)];
+        let edge_cost = |region1: Region, region2: Region| {
+            if (region1, region2) == (Region::from(3), Region::from(0)) { 5 } else { 1 }
+        };
+        assert_eq!(find_paths(&edges, &edge_cost, 5), vec![
+            (2, regions(&[0, 1, 3])),
+            (5, regions(&[0, 3])),
+        ]);
+    }
+
+    #[test]
+    fn paths_of_equal_cost_are_ranked_by_their_length() {
+        let edges = [(1, 0), (2, 1), (3, 2), (3, 0)];
+        let edge_cost = |region1: Region, region2: Region| {
+            if (region1, region2) == (Region::from(3), Region::from(0)) { 3 } else { 1 }
+        };
+        let paths = find_paths(&edges, &edge_cost, 5);
+        assert_eq!(paths, vec![
+            (3, regions(&[0, 3])),
+            (3, regions(&[0, 1, 2, 3])),
+        ]);
+    }
+
+    #[test]
+    fn the_shortest_path_of_equal_cost_is_found_first() {
+        // the path 0-1-2-3 reaches 3 first (since 2 is visited before 4), the path 0-4-3 has the
+        // same cost, but fewer edges.
+        let edges = [(1, 0), (2, 1), (3, 2), (4, 0), (3, 4)];
+        let edge_cost = |region1: Region, region2: Region| {
+            if (region1, region2) == (Region::from(4), Region::from(0)) { 2 } else { 1 }
+        };
+        assert_eq!(find_paths(&edges, &edge_cost, 1), vec![(3, regions(&[0, 4, 3]))]);
+        assert_eq!(find_paths(&edges, &edge_cost, 5), vec![
+            (3, regions(&[0, 4, 3])),
+            (3, regions(&[0, 1, 2, 3])),
+        ]);
+    }
+
+    #[test]
+    fn edges_at_several_points_give_a_single_path() {
+        let edges = [(1, 0), (1, 0), (3, 1), (3, 1), (3, 1)];
+        assert_eq!(find_paths(&edges, &unit_cost, 5), vec![(2, regions(&[0, 1, 3]))]);
+    }
+
+    #[test]
+    fn all_paths_are_distinct_and_simple() {
+        // the cycle between 0 and 1 must not be used to reach 3 via 2 after going to 1.
+        let edges = [(1, 0), (0, 1), (3, 1), (2, 0), (3, 2), (2, 1)];
+        let paths = find_paths(&edges, &unit_cost, 10);
+        assert_eq!(paths, vec![
+            (2, regions(&[0, 1, 3])),
+            (2, regions(&[0, 2, 3])),
+            (3, regions(&[0, 1, 2, 3])),
+        ]);
+        for (_, path) in paths.iter() {
+            let distinct_regions: HashSet<&Region> = path.iter().collect();
+            assert_eq!(distinct_regions.len(), path.len(), "{:?} is not simple", path);
+        }
+    }
+
+    #[test]
+    fn no_path_is_found_if_the_end_is_not_reachable() {
+        let edges = [(1, 0), (2, 1), (3, 4)];
+        assert!(find_paths(&edges, &unit_cost, 5).is_empty());
+    }
+
+    #[test]
+    fn the_start_can_be_the_end() {
+        let paths = find_shortest_paths_backwards(&outlives(&[(1, 0)]), Region::from(0),
+                                                  &|_| true, &unit_cost, 5);
+        assert_eq!(paths, vec![(0, regions(&[0]))]);
+    }
+
+    #[test]
+    fn removed_edges_and_regions_are_not_used() {
+        let mut predecessors: HashMap<Region, Vec<Region>> = HashMap::new();
+        for (region1, region2, _) in outlives(&[(1, 0), (3, 1), (2, 0), (4, 2), (3, 4)]) {
+            predecessors.entry(region2).or_default().push(region1);
+        }
+        let is_end = |region| region == Region::from(3);
+        let find = |removed_edges: &[(usize, usize)], removed_regions: &[usize]| {
+            let removed_edges: HashSet<(Region, Region)> = removed_edges.iter()
+                .map(|&(region1, region2)| (Region::from(region1), Region::from(region2)))
+                .collect();
+            let removed_regions: HashSet<Region> = regions(removed_regions).into_iter().collect();
+            find_shortest_path(&predecessors, Region::from(0), &is_end, &unit_cost,
+                               &removed_edges, &removed_regions)
+        };
+
+        assert_eq!(find(&[], &[]), Some((2, regions(&[0, 1, 3]))));
+        assert_eq!(find(&[(3, 1)], &[]), Some((3, regions(&[0, 2, 4, 3]))));
+        assert_eq!(find(&[], &[1]), Some((3, regions(&[0, 2, 4, 3]))));
+        assert_eq!(find(&[(3, 1)], &[4]), None);
+    }
+}
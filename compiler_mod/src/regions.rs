@@ -4,93 +4,314 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
-/// Code for finding `rustc::ty::sty::RegionVid` associated with local
-/// reference typed variables.
+/// Code for finding the locals whose types contain a given region (`rustc_middle::ty::RegionVid`),
+/// for linking the regions of closures to the ones of their parents, and for describing the
+/// universal regions of a function in terms of its signature.
 
 use facts;
-use dump_borrowck_info::regex::Regex;
-use dump_borrowck_info::rustc::mir;
-use dump_borrowck_info::rustc_data_structures::indexed_vec::Idx;
-use std::collections::HashMap;
-use std::fs::File;
-use std::io::{self, BufRead};
-use std::path::Path;
-
-/// This function is deprecated, and actually also bugous. Also, the returned result, and actually
-/// even it's type (map from local to region), is nonsense.
-pub fn load_variable_regions(path: &Path) -> io::Result<HashMap<mir::Local, facts::Region>> {
-    trace!("[enter] load_variable_regions(path={:?})", path);
-    let mut variable_regions = HashMap::new();
-    let file = File::open(path)?;
-    // This (all remaining code of this function) is not fully correct, it seems to not read all regions
-    // that are associated with some locals. E.g "local 4 rvid 16" is not emitted for find_error_path_ex0.rs,
-    // but it seems to be in the mir quite clearly and it would probably improve the final result if it would be
-    // returned by this function.
-    let fn_sig = Regex::new(r"^fn [a-zA-Z\d_]+\((?P<args>.*)\) -> (?P<result>.*)\{$").unwrap();
-    let arg = Regex::new(r"^_(?P<local>\d+): &'_#(?P<rvid>\d+)r (mut)? [a-zA-Z\d_]+\s*$").unwrap();
-    let local = Regex::new(r"^\s+(let )?(mut )?_(?P<local>\d+): &'_#(?P<rvid>\d+)r ").unwrap();
-    let local2 = Regex::new(r"^\s+(let )?(mut )?_(?P<local>\d+): ([a-zA-Z]+::[a-zA-Z]+::[a-zA-Z]+<\[?)?&'_#(?P<rvid>\d+)r ").unwrap();
-    let local3 = Regex::new(r"^\s+(let )?(mut )?_(?P<local>\d+): &'_#(\d+)r (mut )?([a-zA-Z]+::[a-zA-Z]+::[a-zA-Z]+<\[?)?&'_#(?P<rvid>\d+)r ").unwrap();
-    for line in io::BufReader::new(file).lines() {
-        let line = line?;
-        if let Some(caps) = fn_sig.captures(&line) {
-            debug!("args: {} result: {}", &caps["args"], &caps["result"]);
-            for arg_str in (&caps["args"]).split(", ") {
-                if let Some(arg_caps) = arg.captures(arg_str) {
-                    debug!("arg {} rvid {}", &arg_caps["local"], &arg_caps["rvid"]);
-                    let local: usize = (&arg_caps["local"]).parse().unwrap();
-                    let rvid: usize = (&arg_caps["rvid"]).parse().unwrap();
-                    variable_regions.insert(mir::Local::new(local), rvid.into());
-                }
+use rustc_hir::def::DefKind;
+use rustc_hir::def_id::{DefId, LocalDefId};
+use rustc_middle::mir;
+use rustc_middle::ty::{self, TyCtxt};
+use std::collections::{HashMap, HashSet};
+use std::iter;
+
+/// The position of a region inside the type of a local.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RegionPosition {
+    /// The local whose type contains the region.
+    pub local: mir::Local,
+    /// The index of the region among all regions in the type of the local, in the order in which
+    /// they are visited. (E.g. for `&'a Foo<'b>`, 'a has index 0 and 'b has index 1.)
+    pub index: usize,
+}
+
+/// Finds the position of every region that occurs in the type of a local of the passed body. The
+/// body must be the one that was used by the borrow checker, i.e. one where all regions were
+/// renumbered to region variables. (As it is given by the borrowck consumer interface.)
+/// If a region occurs in the types of several locals, the position in the local with the highest
+/// index is returned.
+pub fn compute_region_positions<'tcx>(tcx: TyCtxt<'tcx>, body: &mir::Body<'tcx>)
+        -> HashMap<facts::Region, RegionPosition> {
+    let mut result = HashMap::new();
+    for (local, local_decl) in body.local_decls.iter_enumerated() {
+        let mut index = 0;
+        tcx.for_each_free_region(&local_decl.ty, |region| {
+            if let ty::ReVar(region_vid) = region.kind() {
+                result.insert(region_vid.as_usize().into(), RegionPosition { local, index });
             }
+            index += 1;
+        });
+    }
+    result
+}
+
+/// Gives the mapping from regions to the locals that introduce them, i.e. to the locals whose
+/// types contain them. See compute_region_positions() for the requirements on the body.
+pub fn compute_region_to_local_map<'tcx>(tcx: TyCtxt<'tcx>, body: &mir::Body<'tcx>)
+        -> HashMap<facts::Region, mir::Local> {
+    compute_region_positions(tcx, body)
+        .into_iter()
+        .map(|(region, position)| (region, position.local))
+        .collect()
+}
+
+/// Gives the regions (i.e. region variables) in the type of a local of the body. See
+/// compute_region_positions() for the requirements on the body.
+pub fn regions_of_local<'tcx>(tcx: TyCtxt<'tcx>, body: &mir::Body<'tcx>, local: mir::Local)
+        -> Vec<facts::Region> {
+    collect_region_vids(tcx, body.local_decls[local].ty).into_iter().flatten().collect()
+}
+
+/// Links the regions of a closure to the regions of the body that defines the closure (the parent,
+/// i.e. the enclosing function or closure). Both bodies must be ones that were used by the borrow
+/// checker, see compute_region_positions().
+/// The regions are linked by their position in the type of the closure: In the parent, it is the
+/// type of the (first) local that holds the closure, in the closure itself, it is the type of the
+/// closure environment (the first argument, without any references). Since both are the same type
+/// (up to the region variables), the regions at the same position correspond to each other.
+/// Returns the map from the regions of the closure to the ones of the parent. It is empty if the
+/// closure is not found in the parent or the types do not match.
+pub fn link_closure_regions<'tcx>(tcx: TyCtxt<'tcx>, closure_def_id: DefId,
+                                  closure_body: &mir::Body<'tcx>, parent_body: &mir::Body<'tcx>)
+        -> HashMap<facts::Region, facts::Region> {
+    let closure_env_ty = match closure_body.local_decls.get(mir::Local::from_u32(1)) {
+        Some(local_decl) => local_decl.ty.peel_refs(),
+        None => return HashMap::new(),
+    };
+    let parent_closure_ty = parent_body.local_decls.iter().map(|local_decl| local_decl.ty).find(|ty|
+        match ty.kind() {
+            ty::Closure(def_id, _) => *def_id == closure_def_id,
+            _ => false,
         }
-        if let Some(local_caps) = local.captures(&line) {
-            debug!("local {} rvid {}", &local_caps["local"], &local_caps["rvid"]);
-            let local: usize = (&local_caps["local"]).parse().unwrap();
-            let rvid: usize = (&local_caps["rvid"]).parse().unwrap();
-            variable_regions.insert(mir::Local::new(local), rvid.into());
-        }
-        if let Some(local2_caps) = local2.captures(&line) {
-            debug!("local {} rvid {}", &local2_caps["local"], &local2_caps["rvid"]);
-            let local: usize = (&local2_caps["local"]).parse().unwrap();
-            let rvid: usize = (&local2_caps["rvid"]).parse().unwrap();
-            variable_regions.insert(mir::Local::new(local), rvid.into());
-        }
-        if let Some(local3_caps) = local3.captures(&line) {
-            debug!("local {} rvid {}", &local3_caps["local"], &local3_caps["rvid"]);
-            let local: usize = (&local3_caps["local"]).parse().unwrap();
-            let rvid: usize = (&local3_caps["rvid"]).parse().unwrap();
-            variable_regions.insert(mir::Local::new(local), rvid.into());
+    );
+    let parent_closure_ty = match parent_closure_ty {
+        Some(ty) => ty,
+        None => {
+            debug!("The closure {:?} was not found in its parent.", closure_def_id);
+            return HashMap::new();
         }
+    };
+
+    let closure_regions = collect_region_vids(tcx, closure_env_ty);
+    let parent_regions = collect_region_vids(tcx, parent_closure_ty);
+    if closure_regions.len() != parent_regions.len() {
+        debug!("The regions of the closure {:?} do not match the ones of its parent: {:?} vs. {:?}",
+               closure_def_id, closure_regions, parent_regions);
+        return HashMap::new();
     }
-    trace!("[exit] load_variable_regions");
-    Ok(variable_regions)
+    closure_regions.into_iter()
+        .zip(parent_regions)
+        .filter_map(|regions| match regions {
+            (Some(closure_region), Some(parent_region)) => Some((closure_region, parent_region)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Gives all free regions in the type, in the order in which they are visited. Regions that are no
+/// region variables are included as None, so that the positions of all regions are retained.
+fn collect_region_vids<'tcx>(tcx: TyCtxt<'tcx>, ty: ty::Ty<'tcx>) -> Vec<Option<facts::Region>> {
+    let mut regions = Vec::new();
+    tcx.for_each_free_region(&ty, |region| {
+        regions.push(match region.kind() {
+            ty::ReVar(region_vid) => Some(region_vid.as_usize().into()),
+            _ => None,
+        });
+    });
+    regions
 }
 
-/// function that reads informations about the mapping from regions to the locals that are involved
-/// in their introduction from a MIR dump (at the "renumber" pass.)
-/// The path to the dump is passed as argument, and the read mapping from regions to locals
-/// will be returned.
-/// Note that this method parses the MIR dump, and hence the result might depend on the syntax etc.
-/// of the dump, and hence it might change/break when switching to a different compiler version.
-pub fn load_region_to_local_map(path: &Path) -> io::Result<HashMap<facts::Region, mir::Local>> {
-    let mut result: HashMap<facts::Region, mir::Local> = HashMap::new();
-
-    let variable_definition = Regex::new(r"^\s+let (mut )?_(?P<local>\d+): (?P<type>.+)$").unwrap();
-    let region_name = Regex::new(r"'_#(\d+)r").unwrap();
-
-    let file = File::open(path)?;
-    for line in io::BufReader::new(file).lines() {
-        let line = line?;
-        if let Some(variable_definition_caps) = variable_definition.captures(&line) {
-            let local: usize = (&variable_definition_caps["local"]).parse().unwrap();
-            let type_str = &variable_definition_caps["type"];
-            for cap_reg in region_name.captures_iter(type_str) {
-                let region: usize = (&cap_reg[1]).parse().unwrap();
-                result.insert(region.into(), mir::Local::new(local));
+/// A universal region of a function, i.e. a lifetime that is given by the signature of the
+/// function (or 'static).
+#[derive(Clone, Debug)]
+pub struct UniversalRegion {
+    /// The name of the lifetime as it is written in the source code (e.g. `'a`), None for
+    /// anonymous lifetimes.
+    pub name: Option<String>,
+    /// Describes the lifetime for the explanations of errors, e.g. "the lifetime `'a` of
+    /// parameter `x`" or "the anonymous lifetime of the return type".
+    pub description: String,
+}
+
+/// Identifies the universal regions of a function (as given by the universal_region facts) and
+/// describes them in terms of the signature of the function. See compute_region_positions() for
+/// the requirements on the body.
+/// The borrow checker gives the parameters and the return place of the body fresh regions, and
+/// requires them to be equal to the universal regions of the signature. A universal region is
+/// therefore found at the position of a region in the type of a parameter (or the return place),
+/// if the two regions are subsets of each other. The name of the lifetime is taken from the same
+/// position in the signature of the function. (Closures do not have a signature of their own, for
+/// them, the lifetimes are only described by the parameter they belong to.)
+/// 'static is always the first universal region. Universal regions that are not found in the
+/// signature (e.g. the one for the body of the function) are not included.
+pub fn compute_universal_regions<'tcx>(tcx: TyCtxt<'tcx>, def_id: LocalDefId,
+                                       body: &mir::Body<'tcx>, all_facts: &facts::AllInputFacts)
+        -> HashMap<facts::Region, UniversalRegion> {
+    let universal_regions: HashSet<facts::Region> =
+        all_facts.universal_region.iter().cloned().collect();
+    let subsets: HashSet<(facts::Region, facts::Region)> = all_facts.subset_base.iter()
+        .map(|&(region1, region2, _)| (region1, region2))
+        .collect();
+    let mut result = HashMap::new();
+
+    let static_region = facts::Region::from(0);
+    if universal_regions.contains(&static_region) {
+        result.insert(static_region, UniversalRegion {
+            name: Some("'static".to_string()),
+            description: "the lifetime `'static`".to_string(),
+        });
+    }
+
+    // The types of the parameters followed by the return type, as given by the signature. The
+    // late-bound lifetimes are liberated, since bound regions are not visited as free regions.
+    let signature_tys: Option<Vec<ty::Ty<'tcx>>> = match tcx.def_kind(def_id) {
+        DefKind::Fn | DefKind::AssocFn => {
+            let signature = tcx.fn_sig(def_id).instantiate_identity();
+            Some(tcx.liberate_late_bound_regions(def_id.to_def_id(), signature)
+                .inputs_and_output.iter().collect())
+        },
+        _ => None,
+    };
+    let locals = body.args_iter().chain(iter::once(mir::RETURN_PLACE));
+    for (signature_index, local) in locals.enumerate() {
+        let place_description = if local == mir::RETURN_PLACE {
+            "the return type".to_string()
+        } else {
+            match find_name_of_local(body, local) {
+                Some(name) => format!("parameter `{}`", name),
+                None => format!("parameter {}", signature_index + 1),
             }
+        };
+        let local_regions = collect_region_vids(tcx, body.local_decls[local].ty);
+        let signature_names: Option<Vec<Option<String>>> = signature_tys.as_ref()
+            .map(|signature_tys| {
+                let mut signature_names = Vec::new();
+                tcx.for_each_free_region(&signature_tys[signature_index], |region| {
+                    signature_names.push(region.get_name().map(|name| name.to_string()));
+                });
+                signature_names
+            });
+        add_universal_regions_of_local(&local_regions, signature_names.as_deref(),
+                                       &place_description, &universal_regions, &subsets,
+                                       &mut result);
+    }
+    result
+}
 
-        }
+/// Adds the universal regions that are found in the type of a parameter (or the return place) to
+/// the result, see compute_universal_regions(). The regions of the local and the names of the
+/// lifetimes in the type of the signature (None if there is no signature) are given in the order in
+/// which they are visited. A region of the local stands for a universal region if it is the
+/// universal region itself, or if the two regions are subsets of each other. Its name is the one at
+/// the same position in the signature, if both types have the same number of regions. Universal
+/// regions that are in the result already are kept, i.e. the first parameter that has one wins.
+fn add_universal_regions_of_local(local_regions: &[Option<facts::Region>],
+                                  signature_names: Option<&[Option<String>]>,
+                                  place_description: &str,
+                                  universal_regions: &HashSet<facts::Region>,
+                                  subsets: &HashSet<(facts::Region, facts::Region)>,
+                                  result: &mut HashMap<facts::Region, UniversalRegion>) {
+    // the positions only match if the type in the signature has the same number of regions.
+    let signature_names = signature_names.filter(|names| names.len() == local_regions.len());
+
+    for (index, &local_region) in local_regions.iter().enumerate() {
+        let local_region = match local_region {
+            Some(local_region) => local_region,
+            None => continue,
+        };
+        let universal_region = if universal_regions.contains(&local_region) {
+            Some(local_region)
+        } else {
+            universal_regions.iter().cloned().find(|&universal_region|
+                subsets.contains(&(local_region, universal_region)) &&
+                    subsets.contains(&(universal_region, local_region))
+            )
+        };
+        let universal_region = match universal_region {
+            Some(universal_region) if !result.contains_key(&universal_region) =>
+                universal_region,
+            _ => continue,
+        };
+        let name = signature_names.and_then(|names| names[index].clone());
+        let description = match name {
+            Some(ref name) => format!("the lifetime `{}` of {}", name, place_description),
+            None => format!("the anonymous lifetime of {}", place_description),
+        };
+        result.insert(universal_region, UniversalRegion {
+            name,
+            description,
+        });
+    }
+}
+
+/// Gives the name of the local in the source code of the body, if it has one. (The names of the
+/// locals are given by the debug info of the MIR.)
+pub fn find_name_of_local(body: &mir::Body, local: mir::Local) -> Option<String> {
+    body.var_debug_info.iter().find(|var_debug_info| match var_debug_info.value {
+        mir::VarDebugInfoContents::Place(place) => place.as_local() == Some(local),
+        _ => false,
+    }).map(|var_debug_info| var_debug_info.name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{add_universal_regions_of_local, UniversalRegion};
+    use facts::Region;
+    use std::collections::{HashMap, HashSet};
+
+    fn regions(regions: &[usize]) -> HashSet<Region> {
+        regions.iter().map(|&region| Region::from(region)).collect()
+    }
+
+    /// Builds the subset relation from pairs of region numbers.
+    fn subsets(pairs: &[(usize, usize)]) -> HashSet<(Region, Region)> {
+        pairs.iter().map(|&(region1, region2)| (Region::from(region1), Region::from(region2)))
+            .collect()
+    }
+
+    fn describe(result: &HashMap<Region, UniversalRegion>, region: usize)
+            -> Option<(Option<&str>, &str)> {
+        result.get(&Region::from(region)).map(|universal_region|
+            (universal_region.name.as_deref(), universal_region.description.as_str()))
+    }
+
+    #[test]
+    fn universal_regions_are_matched_by_equal_regions_and_named_by_position() {
+        // the parameter has the type `&'?5 Foo<'?6, '?7>` for `&'a Foo<'_, 'b>` in the signature.
+        // '?5 and '?6 are equal to the universal regions '?1 and '?2, '?7 is only a subset of '?3.
+        let mut result = HashMap::new();
+        let names = [Some("'a".to_string()), None, Some("'b".to_string())];
+        add_universal_regions_of_local(
+            &[Some(Region::from(5)), Some(Region::from(6)), Some(Region::from(7))],
+            Some(&names), "parameter `x`", &regions(&[0, 1, 2, 3]),
+            &subsets(&[(5, 1), (1, 5), (6, 2), (2, 6), (7, 3)]), &mut result);
+        assert_eq!(result.len(), 2);
+        assert_eq!(describe(&result, 1),
+                   Some((Some("'a"), "the lifetime `'a` of parameter `x`")));
+        assert_eq!(describe(&result, 2),
+                   Some((None, "the anonymous lifetime of parameter `x`")));
+        assert_eq!(describe(&result, 3), None);
+    }
+
+    #[test]
+    fn names_are_only_taken_from_a_matching_signature() {
+        // the signature has fewer regions than the type of the local, hence the positions of the
+        // names are unknown, and the universal region (the region itself) stays anonymous.
+        let mut result = HashMap::new();
+        let names = [Some("'a".to_string())];
+        add_universal_regions_of_local(&[None, Some(Region::from(1))], Some(&names),
+                                       "the return type", &regions(&[1]), &subsets(&[]),
+                                       &mut result);
+        assert_eq!(describe(&result, 1),
+                   Some((None, "the anonymous lifetime of the return type")));
+
+        // a universal region that was described already (for an earlier parameter) is kept.
+        let names = [Some("'b".to_string())];
+        add_universal_regions_of_local(&[Some(Region::from(1))], Some(&names),
+                                       "parameter `y`", &regions(&[1]), &subsets(&[]),
+                                       &mut result);
+        assert_eq!(describe(&result, 1),
+                   Some((None, "the anonymous lifetime of the return type")));
     }
-    Ok(result)
 }
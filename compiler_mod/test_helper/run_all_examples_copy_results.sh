@@ -7,8 +7,8 @@
 
 for in_ex in ../collected_code/*.rs;
 do
-    printf $in_ex\\n; in_ex_name=$(basename -- $in_ex)
+    printf $in_ex\\n; in_ex_name=$(basename -- $in_ex); in_ex_crate=${in_ex_name%.rs}; in_ex_crate=${in_ex_crate//-/_}
     RUST_BACKTRACE=1 make LOG_LEVEL=info,prusti_viper=trace RUN_FILE=${in_ex} build run
-    cp nll-facts/main/error_graph_improved.dot ~/Dokumente/bsc_thesis_tryouts/copied_graphs/${in_ex_name}_error_graph_improved.dot
-    cp nll-facts/error_graph.json ~/Dokumente/bsc_thesis_tryouts/copied_graphs/${in_ex_name}_error_graph.json
+    cp rust-life-out/${in_ex_crate}/main/error_0/error_graph_improved.dot ~/Dokumente/bsc_thesis_tryouts/copied_graphs/${in_ex_name}_error_graph_improved.dot
+    cp rust-life-out/${in_ex_crate}/main/error_0/error_graph.json ~/Dokumente/bsc_thesis_tryouts/copied_graphs/${in_ex_name}_error_graph.json
 done
@@ -25,7 +25,7 @@ export function rustLifeExe(context: vscode.ExtensionContext): string {
 	return path.join(rustLifeHome(context), RUST_LIFE_EXE_NAME);
 }
 
-const RUST_VERSION = "nightly-2019-05-21-x86_64-unknown-linux-gnu";
+const RUST_VERSION = "nightly-2024-06-20-x86_64-unknown-linux-gnu";
 
 /**
  * This function returns the rust version that must be used to run this edition of rust-life. More exactly, it gives the
@@ -7,6 +7,7 @@
 import * as vscode from 'vscode';
 import * as fs from 'fs';
 import * as path from 'path';
+import * as os from 'os';
 import { performance } from 'perf_hooks';
 import * as util from './util';
 import * as config from './config';
@@ -145,6 +146,7 @@ export abstract class Visualization {
 
 	/**
 	 * Function that runs the rust-life tool on a given document.
+	 * Only the error that is closest to the cursor in the editor of this visualization will be explained.
 	 * It will instruct the underlying rustc (part of Rust Life) to not issue any warning messages, so these will
 	 * not be part of the stderr output.
 	 * Once the tool terminated, the output of it (JSON) will be opened from the file and returned for further usage,
@@ -159,12 +161,20 @@ export abstract class Visualization {
 			vscode.window.setStatusBarMessage("Running Rust Life...");
 			const start = performance.now();
 			const programPath = document.uri.fsPath;
+			// only explain the error that is closest to the cursor (lines and columns are counted from 1 by Rust Life):
+			const cursor = this.editor.selection.active;
+			const position = `${programPath}:${cursor.line + 1}:${cursor.character + 1}`;
+			// use a fresh output directory for every run, so that no results of an earlier run can be picked up:
+			const outputDir = fs.mkdtempSync(path.join(os.tmpdir(), "rust-life-"));
 
 			// run the tool on the document:
 			const output = await util.spawn(
 				//"LD_LIBRARY_PATH=" + config.rustLibPath() + " " + config.rustLifeExe(context),
 				config.rustLifeExe(this.context),
-				["--sysroot", config.rustCompilerPath(), "-A", "warnings", programPath],
+				[
+					"--sysroot", config.rustCompilerPath(), "-A", "warnings",
+					"--rust-life-out-dir", outputDir, "--rust-life-position", position, programPath
+				],
 				{
 					cwd: config.rustLifeHome(this.context),
 					env: {
@@ -183,7 +193,11 @@ export abstract class Visualization {
 				return undefined;
 			}
 
-			let jsonDumpPath = path.join(config.rustLifeHome(this.context), "nll-facts", "error_graph.json");
+			let jsonDumpPath = findFirstErrorGraph(outputDir);
+			if (! jsonDumpPath) {
+				util.log("The manifest that should have been created by Rust Life does not list any explained error.");
+				return undefined;
+			}
 			let rawData;
 			try {
 				rawData = fs.readFileSync(jsonDumpPath, 'utf8');
@@ -206,6 +220,32 @@ export abstract class Visualization {
 
 }
 
+/**
+ * Reads the manifest that Rust Life wrote to its output directory and looks up the first error for which an
+ * explanation (a JSON dump of an EnrichedErrorGraph) was written. The functions are listed in the manifest ordered by
+ * their def path, and their errors by their index.
+ * @param outputDir The output directory of Rust Life, i.e. the directory that contains the manifest.json.
+ * @returns The path of the JSON dump of the first explained error, or undefined if the manifest could not be read or
+ * does not list any explained error.
+ */
+function findFirstErrorGraph(outputDir: string): string | undefined {
+	let manifest;
+	try {
+		manifest = JSON.parse(fs.readFileSync(path.join(outputDir, "manifest.json"), 'utf8'));
+	} catch(ex) {
+		util.log("There was an error while trying to read the manifest that should have been created by Rust Life.");
+		return undefined;
+	}
+	for (let fn of manifest.functions) {
+		for (let error of fn.errors) {
+			if (error.json_file) {
+				return path.join(outputDir, error.json_file);
+			}
+		}
+	}
+	return undefined;
+}
+
 /**
  * @class Implements the Visualization of the error (path) as a graph.
  */